@@ -0,0 +1,59 @@
+// The sound channels are not emulated yet. The APU still produces samples at
+// the output rate so frontends can keep an audio device fed, they are just
+// silent.
+
+use std::io;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+// m-cycles per second
+const CLOCK_SPEED: u32 = 1048576;
+
+// never hold on to more than a second of audio nobody is reading
+const MAX_SAMPLES: usize = SAMPLE_RATE as usize * 2;
+
+pub struct APU {
+    counter: u32,
+    // interleaved left and right samples
+    samples: Vec<i16>,
+}
+
+impl APU {
+    pub fn new() -> APU {
+        APU {
+            counter: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self, cycles: u32) {
+        self.counter += cycles * SAMPLE_RATE;
+        while self.counter >= CLOCK_SPEED {
+            self.counter -= CLOCK_SPEED;
+            if self.samples.len() < MAX_SAMPLES {
+                self.samples.push(0);
+                self.samples.push(0);
+            }
+        }
+    }
+
+    // the samples waiting to be read are output, not state, and are left out
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u32::<LittleEndian>(self.counter)
+    }
+
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.counter = input.read_u32::<LittleEndian>()?;
+        Ok(())
+    }
+
+    // hand over everything generated since the last call
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        let mut samples = Vec::new();
+        ::std::mem::swap(&mut samples, &mut self.samples);
+        samples
+    }
+}
@@ -0,0 +1,10 @@
+// Everything the cpu sees of the rest of the machine. The Interconnect is the
+// real one, tests and tools can plug in their own to run the cpu against flat
+// ram or to watch what it does
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // called for every m-cycle the cpu spends, so the rest of the hardware
+    // keeps up with it
+    fn tick(&mut self, cycles: u32);
+}
@@ -1,14 +1,14 @@
-use byteorder::{ByteOrder, LittleEndian};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io;
 use std::io::prelude::*;
 use std::fs::File;
 use std::fmt;
-use cpu::mem::MMU;
-use cpu::gpu::GPU;
+use cpu::bus::Bus;
 use cpu::op::Opcode;
 use cpu::interconnect::Interconnect;
 
 #[allow(non_snake_case)]
-pub struct CPU {
+pub struct CPU<B: Bus = Interconnect> {
     pub f: u8, // flags
     pub a: u8,
     pub b: u8,
@@ -21,18 +21,18 @@ pub struct CPU {
     pub N: u8, // set if subtraction was performed in the last math instruction
     pub H: u8, // set if a carry occurs from the lower nibble
     pub C: u8, // set if if carry occued in the last math op or if registare A is smaller in CP instruction
-    sp: u16,
+    pub sp: u16,
     pub pc: u16,
     opcode: u8,
-    pub memory: MMU,
-    pub gpu: GPU,
+    pub memory: B,
     m_clock: u32,
     t_clock: u32,
     pub boot: [u8; 256], // boot rom
-    ime: u8,
+    pub ime: u8,
+    breakpoint: bool,
 }
 
-impl fmt::Debug for CPU {
+impl<B: Bus> fmt::Debug for CPU<B> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "A: {:#X}\nB: {:#X}\nC: {:#X}\nD: {:#X}\nE: {:#X}\nH: {:#X}\nL: {:#X}\nSP: {:#X} PC: {:#X}\nFlags: Z {:#X} N {:#X} H {:#X} C {:#X}\nt_clock: {}\tm_clock: {}",
                self.a, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc, self.Z, self.N, self.H, self.C, self.t_clock, self.m_clock)
@@ -40,8 +40,23 @@ impl fmt::Debug for CPU {
 }
 
 impl CPU {
-    pub fn new(boot_rom: File) -> CPU {
-        let mut cpu = CPU {
+    pub fn new(mut boot_rom: File) -> CPU {
+        let mut rom = Vec::new();
+        boot_rom.read_to_end(&mut rom).expect("Could not read rom");
+        CPU::from_bytes(&rom)
+    }
+
+    pub fn from_bytes(rom: &[u8]) -> CPU {
+        let mut cpu = CPU::with_bus(Interconnect::new());
+        cpu.memory.load_rom(rom);
+        cpu
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    // a cpu at power on, running against any bus
+    pub fn with_bus(bus: B) -> CPU<B> {
+        CPU {
             a: 0x0,
             f: 0x0,
             b: 0x0,
@@ -59,55 +74,127 @@ impl CPU {
             m_clock: 0,
             t_clock: 0,
             opcode: 0x0,
-            memory: MMU::new(),
-            gpu: GPU::new(),
+            memory: bus,
             boot: [0; 256],
             ime: 0,
-        };
-
-        let mut count = 0;
-        for byte in boot_rom.bytes() {
-            cpu.memory.load_value_u8(count, byte.unwrap());
-            count += 1;
+            breakpoint: false,
         }
+    }
 
-        cpu
+    // F as the cpu sees it, built from the separate flags
+    pub fn flags(&self) -> u8 {
+        self.Z << 7 | self.N << 6 | self.H << 5 | self.C << 4
+    }
+
+    pub fn set_flags(&mut self, f: u8) {
+        self.f = f & 0xF0;
+        self.Z = (f >> 7) & 1;
+        self.N = (f >> 6) & 1;
+        self.H = (f >> 5) & 1;
+        self.C = (f >> 4) & 1;
+    }
+
+    // the registers as the dmg boot rom leaves them when it jumps to the
+    // cartridge at 0x100
+    pub fn skip_boot(&mut self) {
+        self.a = 0x01;
+        self.f = 0xB0;
+        self.Z = 1;
+        self.N = 0;
+        self.H = 1;
+        self.C = 1;
+        self.b = 0x00;
+        self.c = 0x13;
+        self.d = 0x00;
+        self.e = 0xD8;
+        self.h = 0x01;
+        self.l = 0x4D;
+        self.sp = 0xFFFE;
+        self.pc = 0x100;
     }
 
-    pub fn cycle(&mut self, interconnect: &mut Interconnect) -> u8 {
+    // execute one instruction, returning the number of m-cycles it took
+    pub fn cycle(&mut self) -> u8 {
+        let start = self.m_clock;
         self.get_opcode();
-        self.parse_opcode(interconnect);
-        return 0;
+        self.parse_opcode();
+        self.m_clock.wrapping_sub(start) as u8
     }
     
+    // the registers and clocks, the bus saves itself
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for &register in [self.a, self.flags(), self.b, self.c, self.d, self.e, self.h, self.l].iter() {
+            out.write_u8(register)?;
+        }
+        out.write_u16::<LittleEndian>(self.sp)?;
+        out.write_u16::<LittleEndian>(self.pc)?;
+        out.write_u8(self.ime)?;
+        out.write_u32::<LittleEndian>(self.m_clock)?;
+        out.write_u32::<LittleEndian>(self.t_clock)
+    }
+
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.a = input.read_u8()?;
+        let flags = input.read_u8()?;
+        self.set_flags(flags);
+        self.b = input.read_u8()?;
+        self.c = input.read_u8()?;
+        self.d = input.read_u8()?;
+        self.e = input.read_u8()?;
+        self.h = input.read_u8()?;
+        self.l = input.read_u8()?;
+        self.sp = input.read_u16::<LittleEndian>()?;
+        self.pc = input.read_u16::<LittleEndian>()?;
+        self.ime = input.read_u8()?;
+        self.m_clock = input.read_u32::<LittleEndian>()?;
+        self.t_clock = input.read_u32::<LittleEndian>()?;
+        Ok(())
+    }
+
+    // true once after every LD B, B. Test roms such as mooneye's use it as a
+    // software breakpoint
+    pub fn take_breakpoint(&mut self) -> bool {
+        let hit = self.breakpoint;
+        self.breakpoint = false;
+        hit
+    }
+
     pub fn get_opcode(&mut self) {
         // println!("Mem loc: {:#X}", self.pc);
         // self.opcode = self.boot[self.pc as usize];
-        self.opcode = self.memory.read_value_u8(self.pc as usize);
+        self.opcode = self.memory.read(self.pc);
         // println!("Opcode {:02X}", self.opcode);
     }
 
-    pub fn read_word(&self) -> u16 {
-        let aa: u8 = self.memory.read_value_u8((self.pc + 2) as usize);
-        let bb: u8 = self.memory.read_value_u8((self.pc + 1) as usize);
+    // the word at sp, low byte first
+    fn pop(&mut self) -> u16 {
+        let low = self.memory.read(self.sp);
+        let high = self.memory.read(self.sp.wrapping_add(1));
+        self.sp = self.sp.wrapping_add(2);
+        (high as u16) << 8 | low as u16
+    }
+
+    pub fn read_word(&mut self) -> u16 {
+        let aa: u8 = self.memory.read(self.pc + 2);
+        let bb: u8 = self.memory.read(self.pc + 1);
         let word: u16 = (aa as u16) << 8 | bb as u16;
         word
     }
 
-    pub fn read_byte(&self, count: u16) -> u8 {
-        self.memory.read_value_u8( (self.pc + count) as usize )
+    pub fn read_byte(&mut self, count: u16) -> u8 {
+        self.memory.read(self.pc + count)
     }
 
     fn half_carry_add(&self, initial: u8, value: u8) -> bool {
         let a = initial & 0xF;
         let b = value & 0xF;
-        return (a + b) & 0x10 == 0x10;
+        (a + b) & 0x10 == 0x10
     }
 
     fn half_carry_sub(&self, initial: u8, value: u8) -> bool {
         let a = initial & 0xF;
         let b = value & 0xF;
-        return (a.wrapping_sub(b)) & 0x10 == 0x10;
+        (a.wrapping_sub(b)) & 0x10 == 0x10
     }
     
     fn step(&mut self) { // move the timers forward
@@ -115,59 +202,24 @@ impl CPU {
         // update t clock 4 cycles
 
         self.m_clock += 1;
+        self.memory.tick(1);
 
         // for _ in 0..4 { // do something more here
         //     self.t_clock += 1;
         // }
         
         // update timers
-
-        // update display
-        self.render_screen();
     }
     
     pub fn update_timers(&mut self) {
     }
 
-    // 0xFF40 - LCD Control Register
-    // Bit 7 - LCD Power (0=Off, 1=On)
-    // Bit 6 - Window Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
-    // Bit 5 - Window Enable (0=Disabled, 1=Enabled)
-    // Bit 4 - BG & Window Tileset (0=8800h-97FFh, 1=8000h-8FFFh)
-    // Bit 3 - BG Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
-    // Bit 2 - Sprite Size (0=8×8, 1=8×16)
-    // Bit 1 - Sprites Enabled (0=Disabled, 1=Enabled)
-    // Bit 0 - BG Enabled (in DMG) (0=Disabled, 1=Enabled)
-    // 0xFF41 - LCD Status 
-    // Bit 6 - LYC Check
-    // Bit 5 - Mode 2 OAM Checj
-    // Bit 4 - Mode 1 V Blank check 
-    // Bit 3 - Mode 0 H Blank Check
-    // Bit 2 - LYC Comp signal
-    // Bit 1/0 - Screen mode
-    // 0: H blank
-    // 1: V blank
-    // 2: Searching OAM
-    // 3: Transfer data to lcd
-    pub fn render_screen(&self) {
-        // check 0xFF40
-        if (self.memory.read_value_u8(0x0FF40) & 0b1000000) != 0 {
-            println!("SCREEN ON!!!!!");
-            panic!("SCREEN ON");
-        }
-    }
-    pub fn interrupts(&mut self) {
-        if self.ime != 0 {
-            println!("INTERRUPT TRIGGERED!!!!!!");
-        }
-        // println!("interrups");
-    }
-    
-    fn parse_opcode(&mut self, interconnect: &mut Interconnect) {
+    fn parse_opcode(&mut self) {
         match Opcode::parse(self.pc, self.opcode) {
         // match self.opcode {
             Opcode::NoOp => {
                 self.pc += 1;
+                self.step();
             }
             Opcode::IncB => {
                 inc_reg!(self; b);
@@ -195,7 +247,7 @@ impl CPU {
             },
             Opcode::LdB => {
             // 0x06 => { // LD b, d8
-                let value = self.memory.read_value_u8((self.pc + 1) as usize);
+                let value = self.memory.read(self.pc + 1);
                 store_reg!(self; b; value);
                 // self.b = value;
                 self.pc += 2;
@@ -203,7 +255,7 @@ impl CPU {
                 self.step();
             },
             Opcode::LdD => { // 0x16 LD D, d8
-                let value = self.memory.read_value_u8((self.pc + 1) as usize);
+                let value = self.memory.read(self.pc + 1);
                 store_reg!(self; d; value);
                 self.pc += 2;
                 self.step();
@@ -225,6 +277,9 @@ impl CPU {
                 self.d = self.read_byte(2);
                 self.e = self.read_byte(1);
                 self.pc += 3;
+                self.step();
+                self.step();
+                self.step();
             },
             Opcode::RLA => { // RLA rotate A left through carry
                 let old_value = self.a;
@@ -234,14 +289,16 @@ impl CPU {
             Opcode::LdADE => { // LD A, (DE)
                 // println!("LD A, (DE)");
                 let location = (self.d as u16) << 8 | self.e as u16;
-                // self.a = self.memory.read_value_u8( location as usize );
+                // self.a = self.memory.read(location);
                 // println!("\n\n\n\n\n {:#X}", location);
-                self.a = self.memory.read_value_u8( location as usize );
+                self.a = self.memory.read(location);
                 self.pc += 1;
+                self.step();
+                self.step();
                 // self.a = ;
             },
             Opcode::JRNZ => { // JR NZ 
-                let mut location = LittleEndian::read_int(&[self.memory.read_value_u8( (self.pc + 1) as usize )], 1);
+                let mut location = LittleEndian::read_int(&[self.memory.read(self.pc + 1)], 1);
                 if self.Z == 1 {
                     location  = (((self.pc + 2) as i32) + (((location as u8) as i8) as i32)) as i64;
                     self.pc = location as u16;
@@ -255,7 +312,7 @@ impl CPU {
 					  Opcode::JRZ => {
                 // println!("{:#X}", self.pc );
                 // panic!();
-                let mut location = LittleEndian::read_int(&[self.memory.read_value_u8( (self.pc + 1) as usize )], 1);
+                let mut location = LittleEndian::read_int(&[self.memory.read(self.pc + 1)], 1);
                 if self.Z == 0 {
 
                     location  = (((self.pc + 2) as i32) + (((location as u8) as i8) as i32)) as i64;
@@ -268,7 +325,7 @@ impl CPU {
                 }
             },
             Opcode::Jr => {
-                let mut location = LittleEndian::read_int(&[self.memory.read_value_u8( (self.pc + 1) as usize )], 1);
+                let mut location = LittleEndian::read_int(&[self.memory.read(self.pc + 1)], 1);
                 location  = (((self.pc + 2) as i32) + (((location as u8) as i8) as i32)) as i64;
                 self.pc = location as u16;
                 
@@ -277,7 +334,8 @@ impl CPU {
                 self.step();
             },
             Opcode::LdHL => { // LD HL, $aabb
-                store_reg16!(self; h, l; self.read_word());
+                let value = self.read_word();
+                store_reg16!(self; h, l; value);
                 // self.h = self.read_byte(2);
                 // self.l = self.read_byte(1);
                 // println!("H {:X} L {:X}", self.h, self.l);
@@ -301,8 +359,7 @@ impl CPU {
 
                 let location = (self.h as u16) << 8 | (self.l as u16);
                 // println!("loocation {:X}", location);
-                self.memory.load_value_u8(location as usize, self.a);
-                interconnect.write_word(location as u8, location);
+                self.memory.write(location, self.a);
                 // decrement HL
                 let hl = location.wrapping_sub(1);
                 // println!("HL {:X} decrement {:X}", HL, HL - 1);
@@ -316,8 +373,7 @@ impl CPU {
 
                 let location = (self.h as u16) << 8 | (self.l as u16);
                 // println!("loocation {:X}", location);
-                self.memory.load_value_u8(location as usize, self.a);
-                interconnect.write_word(self.a, location as u16);
+                self.memory.write(location, self.a);
 
                 // decrement HL
                 let hl = location.wrapping_add(1);
@@ -357,9 +413,11 @@ impl CPU {
                 self.step();
             },
             Opcode::LdA => { // ld a d8
-                let val = self.memory.read_value_u8((self.pc + 1) as usize);
+                let val = self.memory.read(self.pc + 1);
                 self.a = val;
                 self.pc += 2;
+                self.step();
+                self.step();
             }
             Opcode::LdAE => {
                 load_from_reg!(self; a, e);
@@ -372,7 +430,6 @@ impl CPU {
                 self.pc += 1;
             }
             Opcode::XORA => { // XOR A set Z
-                println!("XOR A");
                 self.a ^= self.a;
                 self.f = 0b10000000;
                 if self.a == 0 {
@@ -388,11 +445,12 @@ impl CPU {
             Opcode::LdCA => { // LD C, A
                 self.c = self.a;
                 self.pc += 1;
+                self.step();
             }, 
             Opcode::PopBC => { // POP BC
 
-                self.b = self.memory.read_value_u8((self.sp + 1) as usize);
-                self.c = self.memory.read_value_u8(self.sp as usize);
+                self.b = self.memory.read(self.sp + 1);
+                self.c = self.memory.read(self.sp);
                 self.sp += 2;
                 self.pc += 1;
                 // println!("sp 0x{:02X}", self.sp);
@@ -403,75 +461,84 @@ impl CPU {
             Opcode::PushBC => { // PUSH BC push b then c onto the stack
                 self.step();
                 self.sp -= 2;
-                self.memory.load_value_u8((self.sp + 1) as usize, self.b);
-                interconnect.write_word(self.b, (self.sp + 1) as u16);
-                self.memory.load_value_u8(self.sp as usize, self.c);
-                interconnect.write_word(self.c, (self.sp) as u16);
+                self.memory.write(self.sp + 1, self.b);
+                self.memory.write(self.sp, self.c);
                 self.pc += 1;
                 // println!("sp 0x{:02X}", self.sp);
             },
             Opcode::CallNN => { // call nn
                 self.sp -= 2; // decrememnt stack pointer
-                self.memory.load_value_u8(self.sp as usize, (self.pc + 3) as u8); // put the next location on the stack
-                let location: u16 = (self.memory.read_value_u8( (self.pc + 2) as usize ) as u16) << 8 | (self.memory.read_value_u8( (self.pc + 1) as usize ) as u16);
+                // put the next location on the stack, high byte first
+                let next = self.pc + 3;
+                self.memory.write(self.sp + 1, (next >> 8) as u8);
+                self.memory.write(self.sp, next as u8);
+                let location: u16 = (self.memory.read(self.pc + 2) as u16) << 8 | (self.memory.read(self.pc + 1) as u16);
                 self.pc = location;
                 self.step();
                 self.step();
                 self.step();
             },
             Opcode::RET => { // RET pop two bytes from the stack
-                let first = self.memory.read_value_u8(self.sp as usize);
-                let second = self.memory.read_value_u8((self.sp + 1) as usize);
-                let location = (second as u16) << 8 | first as u16;
-                self.pc = first as u16;
-                self.sp = self.sp.wrapping_add(2);
+                self.pc = self.pop();
                 self.step();
                 self.step();
                 self.step();
                 self.step();
             },
             Opcode::RetNZ => {
-                if self.C == 0 {
-                    let first = self.memory.read_value_u8(self.sp as usize);
-                    let second = self.memory.read_value_u8((self.sp + 1) as usize);
-                    let location = (second as u16) << 8 | first as u16;
-                    self.pc = first as u16;
+                if self.Z == 0 {
+                    self.pc = self.pop();
+                    self.step();
+                    self.step();
+                    self.step();
+                    self.step();
                 } else {
-
+                    self.pc += 1;
+                    self.step();
                 }
             },
             Opcode::LdC => { // LD C, d8
                 let val: u8 = self.read_byte(1);
                 self.c = val;
                 self.pc += 2;
+                self.step();
+                self.step();
             },
             Opcode::LdhA => { // LDH ($FF00 + n), A - load A into 0xFF00 + d8
                 let val: u8 = self.read_byte(1);
-                self.memory.load_value_u8((0xFF00 | val as u16) as usize, self.a);
+                self.memory.write(0xFF00 | val as u16, self.a);
                 self.pc += 2;
+                self.step();
+                self.step();
+                self.step();
             },
             Opcode::LdHAA8 => {
                 let val: u8 = self.read_byte(1);
-                self.a = self.memory.read_value_u8((0xFF00 | val as u16) as usize);
+                self.a = self.memory.read(0xFF00 | val as u16);
                 self.pc += 2;
+                self.step();
+                self.step();
+                self.step();
             },
             Opcode::LdCADDA => { // LD (0xFF00+C), A load A into location 0xFF00 + self.c
                 let location = 0xFF00 + self.c as u16;
-                self.memory.load_value_u8(location as usize, self.c);
+                self.memory.write(location, self.c);
                 self.pc += 1;
+                self.step();
+                self.step();
             },
             Opcode::LdHLA => { // LD (HL), A - load A into the address at HL 
                 let location = (self.h as u16) << 8 | (self.l as u16);
-                self.memory.load_value_u8(location as usize, self.a);
+                self.memory.write(location, self.a);
                 self.pc += 1;
                 self.step();
                 self.step();
             },
             Opcode::CpD8 => {
-                let imm = self.memory.read_value_u8((self.pc + 1) as usize);
+                let imm = self.memory.read(self.pc + 1);
                 let value = self.a.wrapping_sub(imm);
 
-                if (value & 0xFF) == 0 {
+                if value == 0 {
                     self.Z = 1;
                 }
                 if self.half_carry_sub(value, imm) {
@@ -485,7 +552,7 @@ impl CPU {
             },
             Opcode::CpHL => {
                 let address = get_reg16!(self; h, l);
-                let imm = self.memory.read_value_u8(address as usize);
+                let imm = self.memory.read(address);
                 let value = self.a;
 
                 if value == imm {
@@ -507,7 +574,7 @@ impl CPU {
             },
             Opcode::Lda16A => { // LD (a16), A
                 let location = self.read_word();
-                self.memory.load_value_u8(location as usize, self.a);
+                self.memory.write(location, self.a);
                 self.pc += 3;
 
                 self.step();
@@ -531,7 +598,7 @@ impl CPU {
                 self.pc += 1;
             }
             Opcode::LdE => {
-                let val = self.memory.read_value_u8((self.pc + 1) as usize);
+                let val = self.memory.read(self.pc + 1);
                 self.e = val;
                 self.step();
                 self.step();
@@ -545,9 +612,7 @@ impl CPU {
                 if self.a == 0 {
                     self.Z = 1;
                 }
-                if self.a < 0 {
-                    self.N = 1;
-                }
+                self.N = 1;
                 // TODO - CHECK FOR CARRY 
                 if self.b > self.a {
                     self.C = 1;
@@ -564,7 +629,7 @@ impl CPU {
             },
             Opcode::LdAHLADD => {
                 let mut hl = (self.h as u16) << 8 | (self.l as u16);
-                let value = self.memory.read_value_u8(hl as usize);
+                let value = self.memory.read(hl);
                 
                 self.a = value;
 
@@ -579,7 +644,7 @@ impl CPU {
             },
             Opcode::LdDEA => { // LD (DE), A
                 let address = get_reg16!(self; d, e);
-                self.memory.load_value_u8(address as usize, self.a);
+                self.memory.write(address, self.a);
                 self.step();
                 self.step();
                 self.pc += 1;
@@ -594,13 +659,13 @@ impl CPU {
             },
             Opcode::LdBCA => {
                 let address = get_reg16!(self; b, c);
-                self.memory.load_value_u8(address as usize, self.a);
+                self.memory.write(address, self.a);
                 self.step();
                 self.step();
                 self.pc += 1;
             },
             Opcode::AdcA => {
-                let value = self.memory.read_value_u8((self.pc + 1) as usize);
+                let value = self.memory.read(self.pc + 1);
                 let addition = value + self.C;
                 self.N = 0;
 
@@ -617,11 +682,18 @@ impl CPU {
                     self.Z = 1;
                 }
                 self.pc += 2;
+                self.step();
+                self.step();
             },
             Opcode::LdHHL => {
-                let address = get_reg16!(self; h, l) as usize;
-                self.h = self.memory.read_value_u8(address);
+                let address = get_reg16!(self; h, l);
+                self.h = self.memory.read(address);
+                self.step();
                 self.step();
+                self.pc += 1;
+            },
+            Opcode::LdBB => {
+                self.breakpoint = true;
                 self.step();
                 self.pc += 1;
             },
@@ -664,6 +736,8 @@ impl CPU {
                     self.Z = 1;
                 }
                 self.pc += 1;
+                self.step();
+                self.step();
             },
             _ => panic!("Not Implemented Redirect. Opcode: {:X}", self.opcode)
         }
@@ -1,24 +1,139 @@
+use std::io;
 use std::io::prelude::*;
 use std::fs::File;
 
-use cpu::interconnect::Interconnect;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
 use cpu::cpu::CPU;
+use cpu::gpu::{CYCLES_PER_FRAME, LCD_CONTROL, BG_PALETTE};
+use cpu::joypad::Buttons;
+use movie::crc32;
+use trace::Trace;
 
 pub struct Gameboy {
     pub cpu: CPU,
-    pub interconnect: Interconnect,
+    pub cycles: u64, // total m-cycles run since power on
+    frame_cycles: u32, // m-cycles since the last frame ended
+    // logs every instruction step runs, not part of save states
+    pub trace: Option<Trace>,
+    // frames step has finished that take_frame has not handed out yet
+    frames: u32,
+    // crc32 of the rom the machine started with, save states are tied to it
+    pub rom_crc: u32,
 }
 
 impl Gameboy {
-    pub fn new(boot_rom: File) -> Self {
+    pub fn new(mut boot_rom: File) -> Self {
+        let mut rom = Vec::new();
+        boot_rom.read_to_end(&mut rom).expect("Could not read rom");
+        Gameboy::from_bytes(&rom)
+    }
+
+    pub fn from_bytes(rom: &[u8]) -> Self {
         Gameboy {
-            cpu: CPU::new(boot_rom),
-            interconnect: Interconnect::new(),
+            cpu: CPU::from_bytes(rom),
+            cycles: 0,
+            frame_cycles: 0,
+            trace: None,
+            frames: 0,
+            rom_crc: crc32(rom),
         }
     }
 
+    // start a cartridge without a boot rom, in the state the boot rom hands
+    // over in. Test roms are normally run this way
+    pub fn skip_boot(&mut self) {
+        self.cpu.skip_boot();
+        self.cpu.memory.load_value_u8(LCD_CONTROL, 0x91);
+        self.cpu.memory.load_value_u8(BG_PALETTE, 0xFC);
+    }
+
     pub fn step(&mut self) -> u8 {
-        self.cpu.cycle(&mut self.interconnect)
+        if let Some(ref mut trace) = self.trace {
+            if let Err(e) = trace.log(&self.cpu) {
+                trace.error = Some(e);
+            }
+        }
+        // the hardware is stepped by the cpu as it goes through the bus
+        let cycles = self.cpu.cycle();
+        self.cycles += cycles as u64;
+        self.frame_cycles += cycles as u32;
+        // while the lcd is off a frame still ends every CYCLES_PER_FRAME, so
+        // everything counting frames agrees on where they end
+        if self.cpu.memory.gpu.take_frame() || self.frame_cycles >= CYCLES_PER_FRAME {
+            self.frame_cycles = 0;
+            self.frames += 1;
+        }
+        cycles
+    }
+
+    // run until the end of the frame, see take_frame. Returns the m-cycles
+    // that were run. step ends a frame at most CYCLES_PER_FRAME m-cycles
+    // after the last one, and every opcode the cpu runs takes at least one
+    // (tests/frames.rs checks them all), so this always returns
+    pub fn run_frame(&mut self) -> u32 {
+        let start = self.cycles;
+        loop {
+            self.step();
+            if self.take_frame() {
+                return (self.cycles - start) as u32;
+            }
+        }
+    }
+
+    // true once for every frame step has finished, when the lcd finishes
+    // drawing one or CYCLES_PER_FRAME have gone by with it off. Frames
+    // finished by several steps in a row are each handed out in turn
+    pub fn take_frame(&mut self) -> bool {
+        if self.frames > 0 {
+            self.frames -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // true once for every LD B, B the cpu runs
+    pub fn take_breakpoint(&mut self) -> bool {
+        self.cpu.take_breakpoint()
+    }
+
+    // 160x144 shades from 0 (white) to 3 (black), row by row
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.cpu.memory.gpu.framebuffer
+    }
+
+    // interleaved stereo samples at apu::SAMPLE_RATE generated since the
+    // last call
+    pub fn audio_samples(&mut self) -> Vec<i16> {
+        self.cpu.memory.apu.take_samples()
+    }
+
+    // bytes the game has sent out of the link port since power on
+    pub fn serial_output(&self) -> &[u8] {
+        self.cpu.memory.serial.output()
+    }
+
+    // the machine without a header, see state.rs for the file format
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u64::<LittleEndian>(self.cycles)?;
+        out.write_u32::<LittleEndian>(self.frame_cycles)?;
+        self.cpu.save_state(out)?;
+        self.cpu.memory.save_state(out)
+    }
+
+    // frames finished before the load are dropped
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.frames = 0;
+        self.cycles = input.read_u64::<LittleEndian>()?;
+        self.frame_cycles = input.read_u32::<LittleEndian>()?;
+        self.cpu.load_state(input)?;
+        self.cpu.memory.load_state(input)
+    }
+
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.cpu.memory.joypad.set_buttons(buttons);
+        self.cpu.memory.update_interrupts();
     }
 
 }
@@ -1,25 +1,356 @@
-#[allow(dead_code)]
-pub struct GPU {
-    status: bool
+use std::io;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::mem::{INTERRUPT_VBLANK, INTERRUPT_STAT};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
 
+// the grey level of shades 0 (white) to 3 (black), the one palette the
+// framebuffer is shown in
+pub const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+// a shade as rgb
+pub fn shade_rgb(shade: u8) -> [u8; 3] {
+    let grey = SHADES[shade as usize & 0b11];
+    [grey, grey, grey]
+}
+
+// 0xFF40 - LCD Control Register
+// Bit 7 - LCD Power (0=Off, 1=On)
+// Bit 6 - Window Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
+// Bit 5 - Window Enable (0=Disabled, 1=Enabled)
+// Bit 4 - BG & Window Tileset (0=8800h-97FFh, 1=8000h-8FFFh)
+// Bit 3 - BG Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
+// Bit 2 - Sprite Size (0=8×8, 1=8×16)
+// Bit 1 - Sprites Enabled (0=Disabled, 1=Enabled)
+// Bit 0 - BG Enabled (in DMG) (0=Disabled, 1=Enabled)
+pub const LCD_CONTROL: usize = 0xFF40;
+// 0xFF41 - LCD Status
+// Bit 6 - LYC Check
+// Bit 5 - Mode 2 OAM Check
+// Bit 4 - Mode 1 V Blank check
+// Bit 3 - Mode 0 H Blank Check
+// Bit 2 - LYC Comp signal
+// Bit 1/0 - Screen mode
+pub const LCD_STATUS: usize = 0xFF41;
+pub const SCROLL_Y: usize = 0xFF42;
+pub const SCROLL_X: usize = 0xFF43;
+pub const LY: usize = 0xFF44;
+pub const LYC: usize = 0xFF45;
+// writing XX copies XX00-XX9F to sprite memory, a byte per m-cycle
+pub const OAM_DMA: usize = 0xFF46;
+pub const BG_PALETTE: usize = 0xFF47;
+pub const OBJ_PALETTE_0: usize = 0xFF48;
+pub const OBJ_PALETTE_1: usize = 0xFF49;
+pub const WINDOW_Y: usize = 0xFF4A;
+pub const WINDOW_X: usize = 0xFF4B;
+
+const LCD_ON: u8 = 0b10000000;
+pub const WINDOW_MAP: u8 = 0b01000000;
+pub const WINDOW_ON: u8 = 0b00100000;
+const TILE_DATA: u8 = 0b00010000;
+pub const BG_MAP: u8 = 0b00001000;
+const SPRITE_SIZE: u8 = 0b00000100;
+const SPRITES_ON: u8 = 0b00000010;
+const BG_ON: u8 = 0b00000001;
+
+const STAT_LYC_CHECK: u8 = 0b01000000;
+const STAT_OAM_CHECK: u8 = 0b00100000;
+const STAT_VBLANK_CHECK: u8 = 0b00010000;
+const STAT_HBLANK_CHECK: u8 = 0b00001000;
+const STAT_LYC_EQUAL: u8 = 0b00000100;
+
+// sprite attribute flags
+const SPRITE_BEHIND_BG: u8 = 0b10000000;
+const SPRITE_FLIP_Y: u8 = 0b01000000;
+const SPRITE_FLIP_X: u8 = 0b00100000;
+const SPRITE_PALETTE: u8 = 0b00010000;
+
+// Screen modes
+// 0: H blank
+// 1: V blank
+// 2: Searching OAM
+// 3: Transfer data to lcd
+pub const MODE_HBLANK: u8 = 0;
+pub const MODE_VBLANK: u8 = 1;
+pub const MODE_OAM: u8 = 2;
+pub const MODE_TRANSFER: u8 = 3;
+
+// timings are in m-cycles, a whole line is 114 and a frame 154 lines
+const OAM_CYCLES: u32 = 20;
+const TRANSFER_CYCLES: u32 = 43;
+const HBLANK_CYCLES: u32 = 51;
+const LINE_CYCLES: u32 = OAM_CYCLES + TRANSFER_CYCLES + HBLANK_CYCLES;
+const LINES: u8 = 154;
+
+pub const CYCLES_PER_FRAME: u32 = LINE_CYCLES * LINES as u32;
+
+pub const OAM: usize = 0xFE00;
+pub const OAM_SIZE: usize = 0xA0;
+const SPRITES_PER_LINE: usize = 10;
+
+pub struct GPU {
+    mode: u8,
+    clock: u32,
+    line: u8,
+    window_line: u8,
+    frame_ready: bool,
+    // one shade (0-3) per pixel, after the palettes have been applied
+    pub framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
 }
 
-#[allow(dead_code)]
 impl GPU {
     pub fn new() -> GPU {
-        let gpu = GPU {
-            status: false
-        };
-        gpu
+        GPU {
+            // line 0 starts with the oam search when the lcd is turned on
+            mode: MODE_OAM,
+            clock: 0,
+            line: 0,
+            window_line: 0,
+            frame_ready: false,
+            framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn status(&self) -> u8 {
+        self.mode
     }
 
-    pub fn status(&self) -> bool {
-        self.status
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u8(self.mode)?;
+        out.write_u32::<LittleEndian>(self.clock)?;
+        out.write_u8(self.line)?;
+        out.write_u8(self.window_line)?;
+        out.write_u8(self.frame_ready as u8)?;
+        out.write_all(&self.framebuffer)
     }
 
-    
-    
-    pub fn step(&self) {
-        
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.mode = input.read_u8()?;
+        self.clock = input.read_u32::<LittleEndian>()?;
+        self.line = input.read_u8()?;
+        self.window_line = input.read_u8()?;
+        self.frame_ready = input.read_u8()? != 0;
+        input.read_exact(&mut self.framebuffer)
     }
+
+    // true once for every frame that has been fully drawn
+    pub fn take_frame(&mut self) -> bool {
+        let ready = self.frame_ready;
+        self.frame_ready = false;
+        ready
+    }
+
+    // move the lcd forward by a number of m-cycles. Returns the interrupts
+    // that should be requested in IF
+    pub fn step(&mut self, cycles: u32, memory: &mut [u8]) -> u8 {
+        if memory[LCD_CONTROL] & LCD_ON == 0 {
+            // the screen is blank and LY sits on 0 while the lcd is off,
+            // STAT reads mode 0 but line 0 is drawn first once it is back on
+            self.mode = MODE_OAM;
+            self.clock = 0;
+            self.line = 0;
+            self.window_line = 0;
+            memory[LY] = 0;
+            memory[LCD_STATUS] &= !0b00000011;
+            return 0;
+        }
+
+        let mut interrupts = 0;
+        self.clock += cycles;
+
+        loop {
+            let mode_cycles = match self.mode {
+                MODE_OAM => OAM_CYCLES,
+                MODE_TRANSFER => TRANSFER_CYCLES,
+                MODE_HBLANK => HBLANK_CYCLES,
+                _ => LINE_CYCLES,
+            };
+            if self.clock < mode_cycles {
+                break;
+            }
+            self.clock -= mode_cycles;
+
+            match self.mode {
+                MODE_OAM => {
+                    self.mode = MODE_TRANSFER;
+                },
+                MODE_TRANSFER => {
+                    self.render_line(memory);
+                    self.mode = MODE_HBLANK;
+                    interrupts |= self.stat_interrupt(memory, STAT_HBLANK_CHECK);
+                },
+                MODE_HBLANK => {
+                    self.line += 1;
+                    interrupts |= self.compare_line(memory);
+                    if self.line as usize == SCREEN_HEIGHT {
+                        self.mode = MODE_VBLANK;
+                        self.frame_ready = true;
+                        interrupts |= INTERRUPT_VBLANK;
+                        interrupts |= self.stat_interrupt(memory, STAT_VBLANK_CHECK);
+                    } else {
+                        self.mode = MODE_OAM;
+                        interrupts |= self.stat_interrupt(memory, STAT_OAM_CHECK);
+                    }
+                },
+                _ => {
+                    self.line += 1;
+                    if self.line == LINES {
+                        self.line = 0;
+                        self.window_line = 0;
+                        self.mode = MODE_OAM;
+                        interrupts |= self.stat_interrupt(memory, STAT_OAM_CHECK);
+                    }
+                    interrupts |= self.compare_line(memory);
+                },
+            }
+        }
+
+        memory[LY] = self.line;
+        memory[LCD_STATUS] = (memory[LCD_STATUS] & !0b00000011) | self.mode;
+        interrupts
+    }
+
+    fn stat_interrupt(&self, memory: &[u8], check: u8) -> u8 {
+        if memory[LCD_STATUS] & check != 0 {
+            INTERRUPT_STAT
+        } else {
+            0
+        }
+    }
+
+    fn compare_line(&self, memory: &mut [u8]) -> u8 {
+        memory[LY] = self.line;
+        if memory[LYC] == self.line {
+            memory[LCD_STATUS] |= STAT_LYC_EQUAL;
+            self.stat_interrupt(memory, STAT_LYC_CHECK)
+        } else {
+            memory[LCD_STATUS] &= !STAT_LYC_EQUAL;
+            0
+        }
+    }
+
+    fn render_line(&mut self, memory: &[u8]) {
+        let control = memory[LCD_CONTROL];
+        let line = self.line as usize;
+        // colour numbers before the palette, sprites need them for priority
+        let mut colours = [0u8; SCREEN_WIDTH];
+
+        if control & BG_ON != 0 {
+            let map = if control & BG_MAP != 0 { 0x9C00 } else { 0x9800 };
+            let y = (line + memory[SCROLL_Y] as usize) & 0xFF;
+            for (x, colour) in colours.iter_mut().enumerate() {
+                let map_x = (x + memory[SCROLL_X] as usize) & 0xFF;
+                *colour = tile_map_pixel(memory, control, map, map_x, y);
+            }
+
+            let window_y = memory[WINDOW_Y] as usize;
+            let window_x = memory[WINDOW_X] as usize;
+            if control & WINDOW_ON != 0 && window_y <= line && window_x <= 166 {
+                let map = if control & WINDOW_MAP != 0 { 0x9C00 } else { 0x9800 };
+                let start = window_x.saturating_sub(7);
+                for (x, colour) in colours.iter_mut().enumerate().skip(start) {
+                    let map_x = x + 7 - window_x;
+                    *colour = tile_map_pixel(memory, control, map, map_x, self.window_line as usize);
+                }
+                self.window_line += 1;
+            }
+        }
+
+        let offset = line * SCREEN_WIDTH;
+        for (x, &colour) in colours.iter().enumerate() {
+            self.framebuffer[offset + x] = palette_shade(memory[BG_PALETTE], colour);
+        }
+
+        if control & SPRITES_ON != 0 {
+            self.render_sprites(memory, control, &colours);
+        }
+    }
+
+    fn render_sprites(&mut self, memory: &[u8], control: u8, colours: &[u8; SCREEN_WIDTH]) {
+        let line = self.line as i32;
+        let height = if control & SPRITE_SIZE != 0 { 16 } else { 8 };
+
+        // only the first ten sprites found in OAM on a line are drawn
+        let mut sprites: Vec<usize> = (0..40)
+            .map(|i| OAM + i * 4)
+            .filter(|&address| {
+                let y = memory[address] as i32 - 16;
+                line >= y && line < y + height
+            })
+            .take(SPRITES_PER_LINE)
+            .collect();
+
+        // the lowest x wins on DMG, ties go to the first in OAM. Draw the
+        // winners last so they end up on top
+        sprites.sort_by_key(|&address| (memory[address + 1], address));
+
+        let offset = self.line as usize * SCREEN_WIDTH;
+        for &address in sprites.iter().rev() {
+            let sprite_y = memory[address] as i32 - 16;
+            let sprite_x = memory[address + 1] as i32 - 8;
+            let mut tile = memory[address + 2];
+            let flags = memory[address + 3];
+
+            let mut row = line - sprite_y;
+            if flags & SPRITE_FLIP_Y != 0 {
+                row = height - 1 - row;
+            }
+            if height == 16 {
+                tile &= 0xFE;
+            }
+
+            let palette = if flags & SPRITE_PALETTE != 0 {
+                memory[OBJ_PALETTE_1]
+            } else {
+                memory[OBJ_PALETTE_0]
+            };
+
+            for column in 0..8 {
+                let x = sprite_x + column;
+                if x < 0 || x >= SCREEN_WIDTH as i32 {
+                    continue;
+                }
+                let tile_x = if flags & SPRITE_FLIP_X != 0 { 7 - column } else { column };
+                let colour = tile_pixel(memory, 0x8000 + tile as usize * 16, tile_x as usize, row as usize);
+                if colour == 0 {
+                    continue;
+                }
+                if flags & SPRITE_BEHIND_BG != 0 && colours[x as usize] != 0 {
+                    continue;
+                }
+                self.framebuffer[offset + x as usize] = palette_shade(palette, colour);
+            }
+        }
+    }
+}
+
+// colour number of a pixel in the 256x256 background made from a tile map
+pub fn tile_map_pixel(memory: &[u8], control: u8, map: usize, x: usize, y: usize) -> u8 {
+    let index = memory[map + (y / 8) * 32 + x / 8];
+    tile_pixel(memory, tile_address(control, index), x % 8, y % 8)
+}
+
+// tiles are either indexed unsigned from 0x8000 or signed from 0x9000
+pub fn tile_address(control: u8, index: u8) -> usize {
+    if control & TILE_DATA != 0 {
+        0x8000 + index as usize * 16
+    } else {
+        (0x9000 + (index as i8 as i32) * 16) as usize
+    }
+}
+
+// each row of a tile is two bytes, the low and high bit of every pixel
+pub fn tile_pixel(memory: &[u8], tile: usize, x: usize, y: usize) -> u8 {
+    let low = memory[tile + y * 2];
+    let high = memory[tile + y * 2 + 1];
+    let bit = 7 - x;
+    (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+}
+
+pub fn palette_shade(palette: u8, colour: u8) -> u8 {
+    (palette >> (colour * 2)) & 0b11
 }
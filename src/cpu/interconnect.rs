@@ -1,58 +1,219 @@
-use cpu::cpu::CPU;
-use cpu::mem::MMU;
-use cpu::gpu::GPU;
+use std::io;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::serial::{Serial, SERIAL_DATA, SERIAL_CONTROL};
+use cpu::joypad::{Joypad, JOYPAD};
+use cpu::gpu::{GPU, LCD_STATUS, LY, OAM, OAM_DMA, OAM_SIZE};
+use cpu::apu::APU;
+use cpu::bus::Bus;
+use cpu::mem::{self, ROM_SIZE, INTERRUPT_FLAG, INTERRUPT_SERIAL, INTERRUPT_JOYPAD};
+use cpu::watch::{Watchpoint, WatchHit};
 
 pub struct Interconnect {
-    pub mmu: MMU,
+    memory: Vec<u8>,
+    pub serial: Serial,
+    pub joypad: Joypad,
     pub gpu: GPU,
-    
+    pub apu: APU,
+    // the next byte an oam dma copies, while one is running
+    dma: Option<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    // the first watchpoint hit since the debugger last looked
+    watch_hit: Option<WatchHit>,
 }
 
 impl Interconnect {
+
+    // [0000-3FFF] Cartridge ROM, bank 0
+    // [0000-00FF] BIOS
+    // [0100-014F] Cartridge header
+    // [4000-7FFF] Cartridge ROM, other banks
+    // [8000-9FFF] Graphics RAM
+    // [A000-BFFF] Cartridge (External) RAM
+    // [C000-DFFF] Working RAM
+    // [E000-FDFF] Working RAM (shadow)
+    // [FE00-FE9F] Graphics
+    // [FF00-FF7F] Memory-mapped I/O
+    // [FF80-FFFF] Zero-page RAM
+
+    // the lcd registers are described in gpu.rs
+
     pub fn new() -> Self {
         Interconnect {
-            mmu: MMU::new(),
+            memory: vec![0; 0x10000],
+            serial: Serial::new(),
+            joypad: Joypad::new(),
             gpu: GPU::new(),
+            apu: APU::new(),
+            dma: None,
+            watchpoints: Vec::new(),
+            watch_hit: None,
         }
     }
 
-    pub fn read_word(&mut self) -> u16 {
-        0
+    // copy a cartridge rom into the start of memory
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        let size = if rom.len() < ROM_SIZE { rom.len() } else { ROM_SIZE };
+        self.memory[..size].copy_from_slice(&rom[..size]);
     }
 
-    pub fn write_word(&mut self, value: u8, location: u16) {
-        match location {
+    // the bank mapped at an address, numbered the way rgbds does. There are no
+    // memory bank controllers yet, so the switchable regions always hold
+    // their first bank
+    pub fn bank(&self, address: u16) -> u16 {
+        match mem::region(address) {
+            "ROMX" | "WRAMX" => 1,
+            _ => 0,
+        }
+    }
+
+    // memory as the gpu draws from it
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    // a byte as the cpu would read it, without tripping watchpoints
+    pub fn peek(&self, address: u16) -> u8 {
+        self.read_value_u8(address as usize)
+    }
 
-            0x0000 ... 0x00FF => {
-                println!("Restart and Interrupt Vectors");
+    // store a byte without the side effects a write has, so no dma starts
+    // and registers the hardware guards, like LY, take it as it is. The
+    // registers that only live inside the joypad and serial port still go
+    // through them
+    pub fn poke(&mut self, address: u16, value: u8) {
+        match address as usize {
+            JOYPAD | SERIAL_DATA | SERIAL_CONTROL => self.load_value_u8(address as usize, value),
+            location => self.memory[location] = value,
+        }
+    }
+
+    pub fn read_value_u8(&self, location: usize) -> u8 {
+        match location {
+            JOYPAD => self.joypad.read(),
+            SERIAL_DATA => self.serial.read_data(),
+            SERIAL_CONTROL => self.serial.read_control(),
+            _ => self.memory[location],
+        }
+    }
+    
+    pub fn load_value_u8(&mut self, location: usize, value: u8) {
+        match location {
+            JOYPAD => self.joypad.write(value),
+            // only the interrupt selects are writable, the rest is lcd state
+            LCD_STATUS => self.memory[location] = (self.memory[location] & 0b10000111) | (value & 0b01111000),
+            LY => (),
+            SERIAL_DATA => self.serial.write_data(value),
+            SERIAL_CONTROL => self.serial.write_control(value),
+            OAM_DMA => {
+                self.memory[location] = value;
+                self.dma = Some((value as u16) << 8);
             },
-            0x0100 ... 0x014F => {	println!("Cartridge Header Area") },
-            0x0150 ... 0x3FFF => {	println!("Cartridge ROM  -  Bank 0 (fixed)") },
-            0x4000 ... 0x7FFF => {	println!("Cartridge ROM  -  Switchable Banks 1 - xx") },
-            0x8000 ... 0x97FF => {	println!("Character RAM") },
-            0x9800 ... 0x9BFF => {	println!("BG Map Data 1") },
-            0x9C00 ... 0x9FFF => {	println!("BG Map Data 2") },
-            0xA000 ... 0xBFFF => {	println!("Cartridge RAM (If Available)") },
-            0xC000 ... 0xCFFF => {	println!("Internal RAM  -  Bank 0 (fixed)") },
-            0xD000 ... 0xDFFF => {	println!("Internal RAM  -  Bank 1 - 7 (switchable  -  CGB only)") },
-            0xE000 ... 0xFDFF => {	println!("Echo RAM  -  Reserved, Do Not Use") },
-            0xFE00 ... 0xFE9F => {	println!("OAM  - Object Attribute Memory") },
-            0xFEA0 ... 0xFEFF => {	println!("Unusable Memory") },
-            0xFF00 ... 0xFF7F => {
-                if location == 0xFF01 {
-                    println!("CONSOLE OUTPUT {}", value);
-                }
-                println!("Hardware I/O Registers")
-                    
+            _ => self.memory[location] = value,
+        }
+        // println!("Loaded\n Value: {:X} Location: {:X}, value: {:X}", value, location, self.memory[location]);
+    }
+
+    // all of memory, cartridge ram included, then each piece of hardware
+    // everything but the rom, which the machine is started with
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&self.memory[ROM_SIZE..])?;
+        self.serial.save_state(out)?;
+        self.joypad.save_state(out)?;
+        self.gpu.save_state(out)?;
+        self.apu.save_state(out)?;
+        match self.dma {
+            Some(source) => {
+                out.write_u8(1)?;
+                out.write_u16::<LittleEndian>(source)
             },
-            0xFF80 ... 0xFFFE => {	println!("Zero Page  127 bytes") },
-            _ => { panic!("Trying to write to invalid memory location {}", location)}
+            None => out.write_u8(0),
         }
-        // println!("\n\n\n\n\n\n\n\n\n\n\n\n");
     }
-    
-    pub fn step(&mut self) {
-        println!("I'm a carrot!");
+
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        input.read_exact(&mut self.memory[ROM_SIZE..])?;
+        self.serial.load_state(input)?;
+        self.joypad.load_state(input)?;
+        self.gpu.load_state(input)?;
+        self.apu.load_state(input)?;
+        self.dma = match input.read_u8()? {
+            0 => None,
+            _ => Some(input.read_u16::<LittleEndian>()?),
+        };
+        Ok(())
+    }
+
+    // move the memory mapped hardware forward by a number of m-cycles
+    pub fn step(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.step_dma();
+        }
+        self.serial.step(cycles);
+        self.apu.step(cycles);
+        let interrupts = self.gpu.step(cycles, &mut self.memory);
+        self.memory[INTERRUPT_FLAG] |= interrupts;
+        self.update_interrupts();
     }
 
+    fn step_dma(&mut self) {
+        let source = match self.dma {
+            Some(source) => source,
+            None => return,
+        };
+        let offset = source as usize & 0xFF;
+        let value = self.read_value_u8(source as usize);
+        self.memory[OAM + offset] = value;
+        self.watch(source, value, false, true);
+        self.watch((OAM + offset) as u16, value, true, true);
+        self.dma = if offset + 1 < OAM_SIZE { Some(source + 1) } else { None };
+    }
+
+    fn watch(&mut self, address: u16, value: u8, write: bool, dma: bool) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+        let hit = self.watchpoints.iter().position(|watchpoint| watchpoint.matches(address, value, write));
+        if let Some(index) = hit {
+            self.watch_hit = Some(WatchHit { index, address, value, write, dma });
+        }
+    }
+
+    // the first watchpoint hit since the last call
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    pub fn update_interrupts(&mut self) {
+        if self.serial.take_interrupt() {
+            self.memory[INTERRUPT_FLAG] |= INTERRUPT_SERIAL;
+        }
+        if self.joypad.take_interrupt() {
+            self.memory[INTERRUPT_FLAG] |= INTERRUPT_JOYPAD;
+        }
+    }
+
+}
+
+impl Bus for Interconnect {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.read_value_u8(address as usize);
+        if !self.watchpoints.is_empty() {
+            self.watch(address, value, false, false);
+        }
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.load_value_u8(address as usize, value);
+        if !self.watchpoints.is_empty() {
+            self.watch(address, value, true, false);
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.step(cycles);
+    }
 }
@@ -0,0 +1,92 @@
+// 0xFF00 - P1 Joypad
+// Bit 5 - Select button keys (0=Select)
+// Bit 4 - Select direction keys (0=Select)
+// Bit 3 - Down or Start (0=Pressed)
+// Bit 2 - Up or Select (0=Pressed)
+// Bit 1 - Left or B (0=Pressed)
+// Bit 0 - Right or A (0=Pressed)
+
+use std::io;
+use std::io::prelude::*;
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+pub const JOYPAD: usize = 0xFF00;
+
+const SELECT_BUTTONS: u8 = 0b00100000;
+const SELECT_DIRECTIONS: u8 = 0b00010000;
+
+bitflags! {
+    pub struct Buttons: u8 {
+        const RIGHT  = 0b00000001;
+        const LEFT   = 0b00000010;
+        const UP     = 0b00000100;
+        const DOWN   = 0b00001000;
+        const A      = 0b00010000;
+        const B      = 0b00100000;
+        const SELECT = 0b01000000;
+        const START  = 0b10000000;
+    }
+}
+
+pub struct Joypad {
+    select: u8,
+    pressed: Buttons,
+    interrupt: bool,
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad {
+            select: SELECT_BUTTONS | SELECT_DIRECTIONS,
+            pressed: Buttons::empty(),
+            interrupt: false,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut lines = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            lines |= self.pressed.bits() & 0x0F;
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            lines |= self.pressed.bits() >> 4;
+        }
+        0b11000000 | self.select | (!lines & 0x0F)
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & (SELECT_BUTTONS | SELECT_DIRECTIONS);
+    }
+
+    pub fn buttons(&self) -> Buttons {
+        self.pressed
+    }
+
+    // replace the held buttons, any new press requests the joypad interrupt
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        if !(buttons - self.pressed).is_empty() {
+            self.interrupt = true;
+        }
+        self.pressed = buttons;
+    }
+
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u8(self.select)?;
+        out.write_u8(self.pressed.bits())?;
+        out.write_u8(self.interrupt as u8)
+    }
+
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.select = input.read_u8()?;
+        self.pressed = Buttons::from_bits_truncate(input.read_u8()?);
+        self.interrupt = input.read_u8()? != 0;
+        Ok(())
+    }
+
+    pub fn take_interrupt(&mut self) -> bool {
+        let interrupt = self.interrupt;
+        self.interrupt = false;
+        interrupt
+    }
+}
@@ -0,0 +1,60 @@
+use cpu::gb::Gameboy;
+
+// Two gameboys joined by a virtual link cable inside the same process.
+//
+// The machines are stepped in lockstep: whichever one is behind in m-cycles
+// runs the next instruction, so they never drift apart by more than one
+// instruction. A transfer clocked by one side is only delivered once the
+// other side has caught up to the cycle the last bit was shifted on.
+pub struct LinkCable {
+    pub left: Gameboy,
+    pub right: Gameboy,
+}
+
+impl LinkCable {
+    pub fn new(mut left: Gameboy, mut right: Gameboy) -> LinkCable {
+        left.cpu.memory.serial.linked = true;
+        right.cpu.memory.serial.linked = true;
+        LinkCable {
+            left,
+            right,
+        }
+    }
+
+    // run one instruction on the machine that is behind
+    pub fn step(&mut self) -> u8 {
+        let cycles = if self.left.cycles <= self.right.cycles {
+            self.left.step()
+        } else {
+            self.right.step()
+        };
+
+        LinkCable::exchange(&mut self.left, &mut self.right);
+        LinkCable::exchange(&mut self.right, &mut self.left);
+        cycles
+    }
+
+    // unplug the cable and hand both machines back
+    pub fn disconnect(mut self) -> (Gameboy, Gameboy) {
+        self.left.cpu.memory.serial.linked = false;
+        self.right.cpu.memory.serial.linked = false;
+        (self.left, self.right)
+    }
+
+    fn exchange(master: &mut Gameboy, slave: &mut Gameboy) {
+        let outgoing = match master.cpu.memory.serial.pending_transfer() {
+            Some(byte) => byte,
+            None => return,
+        };
+
+        // wait for the slave to reach the cycle the transfer finished on
+        if slave.cycles < master.cycles {
+            return;
+        }
+
+        let incoming = slave.cpu.memory.serial.receive(outgoing);
+        master.cpu.memory.serial.complete_transfer(incoming);
+        master.cpu.memory.update_interrupts();
+        slave.cpu.memory.update_interrupts();
+    }
+}
@@ -65,11 +65,11 @@ macro_rules! load_from_reg (($c:expr; $dst:ident, $src:ident) => {
 pub fn half_carry_add(initial: u8, value: u8) -> bool {
     let a = initial & 0xF;
     let b = value & 0xF;
-    return (a + b) & 0x10 == 0x10;
+    (a + b) & 0x10 == 0x10
 }
 
 pub fn half_carry_sub(initial: u8, value: u8) -> bool {
     let a = initial & 0xF;
     let b = value & 0xF;
-    return (a.wrapping_sub(b)) & 0x10 == 0x10;
+    (a.wrapping_sub(b)) & 0x10 == 0x10
 }
@@ -1,76 +1,58 @@
-pub struct MMU {
-    boot: [u8; 0xffff],
-}
+// addresses and bits shared by the hardware on the interconnect
 
-impl MMU {
+// cartridges without a memory bank controller map 32KB of rom
+pub const ROM_SIZE: usize = 0x8000;
 
-    // [0000-3FFF] Cartridge ROM, bank 0
-    // [0000-00FF] BIOS
-    // [0100-014F] Cartridge header
-    // [4000-7FFF] Cartridge ROM, other banks
-    // [8000-9FFF] Graphics RAM
-    // [A000-BFFF] Cartridge (External) RAM
-    // [C000-DFFF] Working RAM
-    // [E000-FDFF] Working RAM (shadow)
-    // [FE00-FE9F] Graphics
-    // [FF00-FF7F] Memory-mapped I/O
-    // [FF80-FFFF] Zero-page RAM
+pub const INTERRUPT_FLAG: usize = 0xFF0F;
+pub const INTERRUPT_VBLANK: u8 = 0b00000001;
+pub const INTERRUPT_STAT: u8 = 0b00000010;
+pub const INTERRUPT_SERIAL: u8 = 0b00001000;
+pub const INTERRUPT_JOYPAD: u8 = 0b00010000;
 
-    // 0xFF40 - LCD Control Register
-    // Bit 7 - LCD Power (0=Off, 1=On)
-    // Bit 6 - Window Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
-    // Bit 5 - Window Enable (0=Disabled, 1=Enabled)
-    // Bit 4 - BG & Window Tileset (0=8800h-97FFh, 1=8000h-8FFFh)
-    // Bit 3 - BG Tile Map (0=9800h-9BFFh, 1=9C00h-9FFFh)
-    // Bit 2 - Sprite Size (0=8×8, 1=8×16)
-    // Bit 1 - Sprites Enabled (0=Disabled, 1=Enabled)
-    // Bit 0 - BG Enabled (in DMG) (0=Disabled, 1=Enabled)
-    // 0xFF41 - LCD Status 
-    // Bit 6 - LYC Check
-    // Bit 5 - Mode 2 OAM Checj
-    // Bit 4 - Mode 1 V Blank check 
-    // Bit 3 - Mode 0 H Blank Check
-    // Bit 2 - LYC Comp signal
-    // Bit 1/0 - Screen mode
-    // 0: H blank
-    // 1: V blank
-    // 2: Searching OAM
-    // 3: Transfer data to lcd
-    
-    pub fn new() -> MMU {
-        MMU {
-            boot: [0; 0xffff]
-        }
+// the part of the memory map an address is in, by the names rgbds gives them
+pub fn region(address: u16) -> &'static str {
+    match address {
+        0x0000..=0x3FFF => "ROM0",
+        0x4000..=0x7FFF => "ROMX",
+        0x8000..=0x9FFF => "VRAM",
+        0xA000..=0xBFFF => "SRAM",
+        0xC000..=0xCFFF => "WRAM0",
+        0xD000..=0xDFFF => "WRAMX",
+        0xE000..=0xFDFF => "ECHO",
+        0xFE00..=0xFE9F => "OAM",
+        0xFEA0..=0xFEFF => "UNUSED",
+        0xFF00..=0xFF7F => "IO",
+        0xFF80..=0xFFFE => "HRAM",
+        0xFFFF => "IE",
     }
+}
 
-    pub fn read_value_u8(&self, location: usize) -> u8 {
-        self.boot[location]
-    }
-    
-    pub fn load_value_u8(&mut self, location: usize, value: u8) {
-        if location == 0xFF40 {
-            println!("LCD Control");
-        }
-        if location == 0xFF41 {
-            println!("LCD STATUS");
-        }
-        self.boot[location] = value;
-        // println!("Loaded\n Value: {:X} Location: {:X}, value: {:X}", value, location, self.boot[location]);
+// the hardware registers by their pan docs names
+pub const IO_REGISTERS: [(u16, &str); 43] = [
+    (0xFF00, "P1"), (0xFF01, "SB"), (0xFF02, "SC"),
+    (0xFF04, "DIV"), (0xFF05, "TIMA"), (0xFF06, "TMA"), (0xFF07, "TAC"),
+    (0xFF0F, "IF"),
+    (0xFF10, "NR10"), (0xFF11, "NR11"), (0xFF12, "NR12"), (0xFF13, "NR13"), (0xFF14, "NR14"),
+    (0xFF16, "NR21"), (0xFF17, "NR22"), (0xFF18, "NR23"), (0xFF19, "NR24"),
+    (0xFF1A, "NR30"), (0xFF1B, "NR31"), (0xFF1C, "NR32"), (0xFF1D, "NR33"), (0xFF1E, "NR34"),
+    (0xFF20, "NR41"), (0xFF21, "NR42"), (0xFF22, "NR43"), (0xFF23, "NR44"),
+    (0xFF24, "NR50"), (0xFF25, "NR51"), (0xFF26, "NR52"),
+    (0xFF40, "LCDC"), (0xFF41, "STAT"), (0xFF42, "SCY"), (0xFF43, "SCX"),
+    (0xFF44, "LY"), (0xFF45, "LYC"), (0xFF46, "DMA"), (0xFF47, "BGP"),
+    (0xFF48, "OBP0"), (0xFF49, "OBP1"), (0xFF4A, "WY"), (0xFF4B, "WX"),
+    (0xFF50, "BANK"), (0xFFFF, "IE"),
+];
+
+// the name of the hardware register at an address, the sixteen bytes of wave
+// pattern all go by one
+pub fn io_register(address: u16) -> Option<&'static str> {
+    match address {
+        0xFF30..=0xFF3F => Some("WAVE"),
+        _ => IO_REGISTERS.iter().find(|&&(at, _)| at == address).map(|&(_, name)| name),
     }
+}
 
-    // pub fn load_value_u16(&mut self, location: usize, value: u16) {
-    //     if location == 0xFF40 {
-    //         panic!("LCD Control");
-    //     }
-    //     if location == 0xFF41 {
-    //         panic!("LCD STATUS");
-    //     }
-    //     if location == 0xFF47 {
-    //         panic!("HERE");
-    //     }
-    //     let upper: u8 = (value >> 8) as u8;
-    //     let lower: u8 = (value & 0xFF00) as u8;
-    //     self.boot[location] = upper;
-    //     self.boot[location + 1] = lower;
-    // }
+// the address of a hardware register, by name in any case
+pub fn io_address(name: &str) -> Option<u16> {
+    IO_REGISTERS.iter().find(|&&(_, at)| at.eq_ignore_ascii_case(name)).map(|&(address, _)| address)
 }
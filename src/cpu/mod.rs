@@ -3,7 +3,14 @@ mod macros;
 
 pub mod cpu;
 pub mod mem;
+pub mod bus;
 pub mod gpu;
+pub mod apu;
 pub mod op;
 pub mod interconnect;
+pub mod watch;
 pub mod gb;
+pub mod joypad;
+pub mod serial;
+pub mod link;
+pub mod tcp_link;
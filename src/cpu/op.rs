@@ -61,6 +61,7 @@ const OPCODE_JP: u8          = 0xC3;
 const OPCODE_ADC_A: u8       = 0xCE;
 const OPCODE_LD_H_HL: u8        = 0x66;
 const OPCODE_CALL_Z:u8       = 0xCC;
+const OPCODE_LD_B_B: u8      = 0x40;
 
 const OPCODE_RET: u8         = 0xC9;
 
@@ -126,6 +127,7 @@ pub enum Opcode {
     AdcA,
     LdHHL,
     CallZ,
+    LdBB,
 }
 
 impl Opcode {
@@ -190,6 +192,7 @@ impl Opcode {
             OPCODE_DEC_BC      => Opcode::DecBC,
             OPCODE_INC_BC      => Opcode::IncBC,
             OPCODE_LD_H_HL     => Opcode::LdHHL,
+            OPCODE_LD_B_B      => Opcode::LdBB,
             _                  => {
                 println!("Unimplemented opcode PC: 0x{:04X} OP: 0x{:02X}", address, bits);
                 panic!();
@@ -208,68 +211,69 @@ impl Opcode {
 
 impl fmt::Display for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let code = match self {
-            &Opcode::NoOp     => "NOOP",
-            &Opcode::IncB     => "INC B",
-            &Opcode::DecB     => "DEC B",
-            &Opcode::DecE     => "DEC E",
-            &Opcode::LdB      => "LD B, d8",
-            &Opcode::IncC     => "INC C",
-            &Opcode::LdDE     => "LD DE, d16",
-            &Opcode::RLA      => "RLA",
-            &Opcode::JRNZ     => "JR NZ",
-            &Opcode::LdSP     => "LD SP, d16",
-            &Opcode::LdHL     => "LD HL, (d16)",
-            &Opcode::LdADE    => "LD A, (DE)",
-            &Opcode::LdHLDECA => "LD (HL-), A",
-            &Opcode::LdHLADDA => "LD (HL+), A",
-            &Opcode::IncHL    => "INC HL",
-            &Opcode::IncDE    => "INC DE",
-            &Opcode::LdA      => "LD A, d8",
-            &Opcode::LdAE     => "LD A, E",
-            &Opcode::XORA     => "XOR A",
-            &Opcode::LdCA     => "LD C, A",
-            &Opcode::PopBC    => "POP BC",
-            &Opcode::PushBC   => "PUSH BC",
-            &Opcode::CallNN   => "CALL NN",
-            &Opcode::LdhA     => "LDH ($FF00+n), A",
-            &Opcode::LdHAA8   => "LDH A, ($FF00+a8)",
-            &Opcode::LdCADDA  => "LD ($FF00+C), A",
-            &Opcode::LdHLA    => "LD (HL), A",
-            &Opcode::LdC      => "LD C, d8",
-            &Opcode::CB       => "",
-            &Opcode::CB7C     => "BIT 7, H",
-            &Opcode::CBRLC    => "RL C",
-            &Opcode::RET      => "RET",
-            &Opcode::CpD8     => "CP d8",
-            &Opcode::Lda16A   => "LD (a16), A",
-            &Opcode::DecA     => "DEC A",
-            &Opcode::JRZ      => "JR Z, r8",
-            &Opcode::LdHA     => "LD H, A",
-            &Opcode::LdDA     => "LD D, A",
-            &Opcode::LdE      => "LD E, d8",
-            &Opcode::DecC     => "DEC C",
-            &Opcode::IncH     => "INC H",
-            &Opcode::LdAH     => "LD A, H",
-            &Opcode::SubB     => "SUB B",
-            &Opcode::DecD     => "DEC D",
-            &Opcode::LdD      => "LD D, d8",
-            &Opcode::Jr       => "JR d8",
-            &Opcode::Jp       => "JP (a16)",
-            &Opcode::CpHL     => "CP (HL)",
-            &Opcode::LdBA     => "LD B, A",
-            &Opcode::LdAHLADD => "Ld A, (HL+)",
-            &Opcode::LdDEA    => "Ld (DE), A",
-            &Opcode::IncE     => "INC E",
-            &Opcode::IncD     => "INC D",
-            &Opcode::LdAB     => "LD A, B",
-            &Opcode::RetNZ    => "RET NZ",
-            &Opcode::LdBCA    => "LD (BC), A",
-            &Opcode::AdcA     => "ADC A, d8",
-            &Opcode::LdHHL    => "LD H, (HL)",
-            &Opcode::CallZ    => "CALL Z, a16",
-            &Opcode::DecBC    => "DEC BC",
-            &Opcode::IncBC    => "INC BC",
+        let code = match *self {
+            Opcode::NoOp     => "NOOP",
+            Opcode::IncB     => "INC B",
+            Opcode::DecB     => "DEC B",
+            Opcode::DecE     => "DEC E",
+            Opcode::LdB      => "LD B, d8",
+            Opcode::IncC     => "INC C",
+            Opcode::LdDE     => "LD DE, d16",
+            Opcode::RLA      => "RLA",
+            Opcode::JRNZ     => "JR NZ",
+            Opcode::LdSP     => "LD SP, d16",
+            Opcode::LdHL     => "LD HL, (d16)",
+            Opcode::LdADE    => "LD A, (DE)",
+            Opcode::LdHLDECA => "LD (HL-), A",
+            Opcode::LdHLADDA => "LD (HL+), A",
+            Opcode::IncHL    => "INC HL",
+            Opcode::IncDE    => "INC DE",
+            Opcode::LdA      => "LD A, d8",
+            Opcode::LdAE     => "LD A, E",
+            Opcode::XORA     => "XOR A",
+            Opcode::LdCA     => "LD C, A",
+            Opcode::PopBC    => "POP BC",
+            Opcode::PushBC   => "PUSH BC",
+            Opcode::CallNN   => "CALL NN",
+            Opcode::LdhA     => "LDH ($FF00+n), A",
+            Opcode::LdHAA8   => "LDH A, ($FF00+a8)",
+            Opcode::LdCADDA  => "LD ($FF00+C), A",
+            Opcode::LdHLA    => "LD (HL), A",
+            Opcode::LdC      => "LD C, d8",
+            Opcode::CB       => "",
+            Opcode::CB7C     => "BIT 7, H",
+            Opcode::CBRLC    => "RL C",
+            Opcode::RET      => "RET",
+            Opcode::CpD8     => "CP d8",
+            Opcode::Lda16A   => "LD (a16), A",
+            Opcode::DecA     => "DEC A",
+            Opcode::JRZ      => "JR Z, r8",
+            Opcode::LdHA     => "LD H, A",
+            Opcode::LdDA     => "LD D, A",
+            Opcode::LdE      => "LD E, d8",
+            Opcode::DecC     => "DEC C",
+            Opcode::IncH     => "INC H",
+            Opcode::LdAH     => "LD A, H",
+            Opcode::SubB     => "SUB B",
+            Opcode::DecD     => "DEC D",
+            Opcode::LdD      => "LD D, d8",
+            Opcode::Jr       => "JR d8",
+            Opcode::Jp       => "JP (a16)",
+            Opcode::CpHL     => "CP (HL)",
+            Opcode::LdBA     => "LD B, A",
+            Opcode::LdAHLADD => "Ld A, (HL+)",
+            Opcode::LdDEA    => "Ld (DE), A",
+            Opcode::IncE     => "INC E",
+            Opcode::IncD     => "INC D",
+            Opcode::LdAB     => "LD A, B",
+            Opcode::RetNZ    => "RET NZ",
+            Opcode::LdBCA    => "LD (BC), A",
+            Opcode::AdcA     => "ADC A, d8",
+            Opcode::LdHHL    => "LD H, (HL)",
+            Opcode::CallZ    => "CALL Z, a16",
+            Opcode::DecBC    => "DEC BC",
+            Opcode::IncBC    => "INC BC",
+            Opcode::LdBB     => "LD B, B",
         };
         write!(f, "{}", code)
     }
@@ -0,0 +1,168 @@
+// 0xFF01 - SB Serial transfer data
+// 0xFF02 - SC Serial transfer control
+// Bit 7 - Transfer start flag (0=No transfer, 1=Start/in progress)
+// Bit 0 - Shift clock (0=External clock, 1=Internal clock 8192Hz)
+//
+// With the internal clock a byte takes 8 bits * 128 m-cycles to shift out.
+// When the transfer finishes bit 7 of SC is cleared and the serial
+// interrupt (bit 3 of IF) is requested.
+
+use std::io;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+pub const SERIAL_DATA: usize = 0xFF01;
+pub const SERIAL_CONTROL: usize = 0xFF02;
+
+const TRANSFER_START: u8 = 0b10000000;
+const INTERNAL_CLOCK: u8 = 0b00000001;
+
+const CYCLES_PER_BIT: u32 = 128;
+const CYCLES_PER_BYTE: u32 = CYCLES_PER_BIT * 8;
+
+// the first bytes sent out are kept, up to this many
+const MAX_OUTPUT: usize = 0x10000;
+
+pub struct Serial {
+    data: u8,
+    control: u8,
+    counter: u32,
+    pending: bool,
+    interrupt: bool,
+    output: Vec<u8>,
+    // when set the transfers are completed by whoever owns the other end of
+    // the cable, otherwise an internal transfer reads 0xFF like real hardware
+    // with nothing plugged in
+    pub linked: bool,
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            data: 0,
+            control: 0,
+            counter: 0,
+            pending: false,
+            interrupt: false,
+            output: Vec::new(),
+            linked: false,
+        }
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data
+    }
+
+    pub fn read_control(&self) -> u8 {
+        // unused bits read back as 1
+        self.control | 0b01111110
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        self.data = value;
+    }
+
+    pub fn write_control(&mut self, value: u8) {
+        // a transfer cut short starts over, the cycles run past the end of
+        // the last one still count towards the next
+        if self.transferring() {
+            self.counter = 0;
+        }
+        self.control = value & (TRANSFER_START | INTERNAL_CLOCK);
+        self.pending = false;
+    }
+
+    pub fn transferring(&self) -> bool {
+        self.control & TRANSFER_START != 0
+    }
+
+    pub fn internal_clock(&self) -> bool {
+        self.control & INTERNAL_CLOCK != 0
+    }
+
+    // move the internal shift clock forward by a number of m-cycles
+    pub fn step(&mut self, cycles: u32) {
+        if !self.transferring() || !self.internal_clock() || self.pending {
+            return;
+        }
+
+        self.counter += cycles;
+        if self.counter >= CYCLES_PER_BYTE {
+            self.counter -= CYCLES_PER_BYTE;
+            if self.output.len() < MAX_OUTPUT {
+                self.output.push(self.data);
+            }
+            if self.linked {
+                self.pending = true;
+            } else {
+                self.finish(0xFF);
+            }
+        }
+    }
+
+    // the byte waiting to go out once all 8 bits have been clocked by this
+    // side, None if there is no finished master transfer
+    pub fn pending_transfer(&self) -> Option<u8> {
+        if self.pending {
+            Some(self.data)
+        } else {
+            None
+        }
+    }
+
+    // complete a master transfer with the byte shifted in from the peer
+    pub fn complete_transfer(&mut self, incoming: u8) {
+        if self.pending {
+            self.pending = false;
+            self.finish(incoming);
+        }
+    }
+
+    // the peer clocked a full byte into us. Only a side waiting on the
+    // external clock shifts, anything else looks like a disconnected line
+    pub fn receive(&mut self, incoming: u8) -> u8 {
+        if !self.transferring() || self.internal_clock() {
+            return 0xFF;
+        }
+        let outgoing = self.data;
+        self.finish(incoming);
+        outgoing
+    }
+
+    // every byte this side has clocked out, test roms use this as a console
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    // whether a cable is plugged in belongs to the frontend and the output
+    // log to whoever is watching it, neither is saved
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u8(self.data)?;
+        out.write_u8(self.control)?;
+        out.write_u32::<LittleEndian>(self.counter)?;
+        out.write_u8(self.pending as u8)?;
+        out.write_u8(self.interrupt as u8)
+    }
+
+    pub fn load_state<R: Read>(&mut self, input: &mut R) -> io::Result<()> {
+        self.data = input.read_u8()?;
+        self.control = input.read_u8()?;
+        self.counter = input.read_u32::<LittleEndian>()?;
+        self.pending = input.read_u8()? != 0;
+        self.interrupt = input.read_u8()? != 0;
+        Ok(())
+    }
+
+    pub fn take_interrupt(&mut self) -> bool {
+        let interrupt = self.interrupt;
+        self.interrupt = false;
+        interrupt
+    }
+
+    fn finish(&mut self, incoming: u8) {
+        self.data = incoming;
+        self.control &= !TRANSFER_START;
+        self.interrupt = true;
+    }
+}
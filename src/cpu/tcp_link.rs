@@ -0,0 +1,285 @@
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cpu::gb::Gameboy;
+
+// A gameboy whose link port is plugged into a peer over TCP.
+//
+// Every message on the wire is a three byte frame, [kind, sequence, value]:
+//   HELLO    [0x00, 0, version]    sent by both sides straight after connecting
+//   TRANSFER [0x01, sequence, byte] the clock master finished shifting out a byte
+//   REPLY    [0x02, sequence, byte] the byte the other side shifted back in
+//
+// Transfers are exchanged synchronously: once the master has clocked out all
+// 8 bits it stops and waits for the REPLY before running the next
+// instruction. The slave polls the socket while it runs and answers any
+// TRANSFER straight away, so there is never any state to roll back.
+//
+// A REPLY carries the sequence number of the TRANSFER it answers, which goes
+// up by one for every transfer. When the master gives up on a reply it can
+// still arrive later, and the sequence number keeps it from being taken as
+// the answer to the next transfer.
+
+const PROTOCOL_VERSION: u8 = 2;
+
+const FRAME_HELLO: u8 = 0x00;
+const FRAME_TRANSFER: u8 = 0x01;
+const FRAME_REPLY: u8 = 0x02;
+
+// how often, in m-cycles, the socket is checked for a transfer from the peer
+const POLL_CYCLES: u32 = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeoutBehaviour {
+    // finish the transfer with 0xFF, as if the peer had left its line idle,
+    // and stay connected for the next one
+    ReadFF,
+    // keep waiting until the peer answers
+    Wait,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TcpLinkConfig {
+    pub timeout: Duration,
+    pub on_timeout: TimeoutBehaviour,
+}
+
+impl TcpLinkConfig {
+    pub fn new() -> TcpLinkConfig {
+        TcpLinkConfig {
+            timeout: Duration::from_millis(1000),
+            on_timeout: TimeoutBehaviour::ReadFF,
+        }
+    }
+}
+
+pub struct TcpLink {
+    pub gameboy: Gameboy,
+    stream: Option<TcpStream>,
+    config: TcpLinkConfig,
+    frame: [u8; 3],
+    received: usize,
+    poll_counter: u32,
+    // the sequence number of the next transfer we send
+    sequence: u8,
+    // why the peer was dropped, until the frontend takes it
+    error: Option<io::Error>,
+}
+
+impl TcpLink {
+    // wait for a peer to connect on the given address, for as long as the
+    // config waits on a transfer
+    pub fn listen<A: ToSocketAddrs>(gameboy: Gameboy, address: A, config: TcpLinkConfig) -> io::Result<TcpLink> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let deadline = match config.on_timeout {
+            TimeoutBehaviour::ReadFF => Some(Instant::now() + config.timeout),
+            TimeoutBehaviour::Wait => None,
+        };
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => return TcpLink::from_stream(gameboy, stream, config),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if let Some(deadline) = deadline {
+                        if Instant::now() >= deadline {
+                            return Err(io::Error::new(io::ErrorKind::TimedOut, "no peer connected"));
+                        }
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn connect<A: ToSocketAddrs>(gameboy: Gameboy, address: A, config: TcpLinkConfig) -> io::Result<TcpLink> {
+        let stream = TcpStream::connect(address)?;
+        TcpLink::from_stream(gameboy, stream, config)
+    }
+
+    // say hello over a connection that is already open
+    pub fn from_stream(mut gameboy: Gameboy, stream: TcpStream, config: TcpLinkConfig) -> io::Result<TcpLink> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        gameboy.cpu.memory.serial.linked = true;
+
+        let mut link = TcpLink {
+            gameboy,
+            stream: Some(stream),
+            config,
+            frame: [0; 3],
+            received: 0,
+            poll_counter: 0,
+            sequence: 0,
+            error: None,
+        };
+
+        link.send(FRAME_HELLO, 0, PROTOCOL_VERSION)?;
+        let deadline = link.deadline();
+        match link.read_frame(deadline)? {
+            Some([FRAME_HELLO, _, PROTOCOL_VERSION]) => Ok(link),
+            Some([FRAME_HELLO, _, version]) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("peer speaks link protocol version {}, expected {}", version, PROTOCOL_VERSION))),
+            Some(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "peer did not send a hello frame")),
+            None => Err(io::Error::new(io::ErrorKind::TimedOut, "peer did not say hello")),
+        }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // why the peer was dropped, once, None while it is still there
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    // run one instruction and service the link port
+    pub fn step(&mut self) -> u8 {
+        let cycles = self.gameboy.step();
+
+        if let Some(outgoing) = self.gameboy.cpu.memory.serial.pending_transfer() {
+            let incoming = self.exchange(outgoing);
+            self.gameboy.cpu.memory.serial.complete_transfer(incoming);
+            self.gameboy.cpu.memory.update_interrupts();
+        }
+
+        self.poll_counter += cycles as u32;
+        if self.poll_counter >= POLL_CYCLES {
+            self.poll_counter = 0;
+            self.poll();
+        }
+        cycles
+    }
+
+    // unplug the cable and hand the machine back
+    pub fn disconnect(mut self) -> Gameboy {
+        self.gameboy.cpu.memory.serial.linked = false;
+        self.gameboy
+    }
+
+    // send our byte as clock master and block for the peer's reply
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        if let Err(e) = self.send(FRAME_TRANSFER, sequence, outgoing) {
+            self.drop_peer(e);
+            return 0xFF;
+        }
+
+        let deadline = self.deadline();
+        loop {
+            match self.read_frame(deadline) {
+                Ok(Some([FRAME_REPLY, reply, incoming])) if reply == sequence => return incoming,
+                Ok(Some([FRAME_TRANSFER, theirs, _])) => {
+                    // both sides tried to drive the clock, neither is
+                    // listening so the peer just sees an idle line
+                    if let Err(e) = self.send(FRAME_REPLY, theirs, 0xFF) {
+                        self.drop_peer(e);
+                        return 0xFF;
+                    }
+                },
+                // replies to transfers we already gave up on
+                Ok(Some(_)) => (),
+                Ok(None) => return 0xFF,
+                Err(e) => {
+                    self.drop_peer(e);
+                    return 0xFF;
+                }
+            }
+        }
+    }
+
+    // when to stop waiting on the peer, None to wait for as long as it takes
+    fn deadline(&self) -> Option<Instant> {
+        match self.config.on_timeout {
+            TimeoutBehaviour::ReadFF => Some(Instant::now() + self.config.timeout),
+            TimeoutBehaviour::Wait => None,
+        }
+    }
+
+    // answer any transfer the peer clocked into us
+    fn poll(&mut self) {
+        loop {
+            match self.read_frame(Some(Instant::now())) {
+                Ok(Some([FRAME_TRANSFER, sequence, incoming])) => {
+                    let outgoing = self.gameboy.cpu.memory.serial.receive(incoming);
+                    self.gameboy.cpu.memory.update_interrupts();
+                    if let Err(e) = self.send(FRAME_REPLY, sequence, outgoing) {
+                        self.drop_peer(e);
+                        return;
+                    }
+                },
+                // a reply that turned up after we gave up waiting on it
+                Ok(Some(_)) => (),
+                Ok(None) => return,
+                Err(e) => {
+                    self.drop_peer(e);
+                    return;
+                },
+            }
+        }
+    }
+
+    // read one frame, giving up at the deadline. A deadline of None waits forever
+    fn read_frame(&mut self, deadline: Option<Instant>) -> io::Result<Option<[u8; 3]>> {
+        loop {
+            let result = match self.stream {
+                Some(ref mut stream) => stream.read(&mut self.frame[self.received..]),
+                None => return Ok(None),
+            };
+
+            match result {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer hung up")),
+                Ok(n) => {
+                    self.received += n;
+                    if self.received == self.frame.len() {
+                        self.received = 0;
+                        return Ok(Some(self.frame));
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if let Some(deadline) = deadline {
+                        if Instant::now() >= deadline {
+                            return Ok(None);
+                        }
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn send(&mut self, kind: u8, sequence: u8, value: u8) -> io::Result<()> {
+        let frame = [kind, sequence, value];
+        let mut sent = 0;
+        while sent < frame.len() {
+            let result = match self.stream {
+                Some(ref mut stream) => stream.write(&frame[sent..]),
+                None => return Err(io::Error::new(io::ErrorKind::NotConnected, "no peer")),
+            };
+
+            match result {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "peer hung up")),
+                Ok(n) => sent += n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => thread::yield_now(),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // the peer went away, carry on as if nothing is plugged in
+    fn drop_peer(&mut self, error: io::Error) {
+        self.error = Some(error);
+        self.stream = None;
+        self.gameboy.cpu.memory.serial.linked = false;
+    }
+}
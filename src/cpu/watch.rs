@@ -0,0 +1,47 @@
+// Watchpoints live on the Interconnect so every access goes past them, the
+// cpu's and the oam dma's alike, instruction fetches included. The debugger
+// sets them up and reports hits
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    // inclusive, the same as start for a single address
+    pub end: u16,
+    pub watch: Watch,
+    // only trigger when this value is read or written
+    pub value: Option<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    // index into the interconnect's watchpoints
+    pub index: usize,
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+    // made by the oam dma rather than the cpu
+    pub dma: bool,
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, watch: Watch, value: Option<u8>) -> Watchpoint {
+        Watchpoint { start, end, watch, value }
+    }
+
+    pub fn matches(&self, address: u16, value: u8, write: bool) -> bool {
+        let kind = match self.watch {
+            Watch::Read => !write,
+            Watch::Write => write,
+            Watch::Access => true,
+        };
+        kind && address >= self.start && address <= self.end
+            && self.value.is_none_or(|expected| expected == value)
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::prelude::*;
+
+use debug::disasm::{self, Instruction, Operand};
+use movie;
+
+// Turns a whole rom into RGBDS source that assembles back to the same bytes.
+//
+// Code is found by following jumps and calls from the entry point, the rst
+// targets and the interrupt vectors, and everything it does not reach is
+// written out as data. Which bank is mapped at 0x4000 is only known at power
+// on, where it is bank 1, and after the usual `ld a, n` then `ld [$2000], a`
+// bank switch, so code only reached through other bank switches stays data.
+//
+// Banks are numbered the way Interconnect::bank numbers them and offsets are
+// positions in the rom file.
+
+const BANK_SIZE: usize = 0x4000;
+
+// data lines hold this many bytes
+const DATA_WIDTH: usize = 8;
+
+// runs of one byte at least this long are written with ds
+const FILL_LENGTH: usize = 16;
+
+const ENTRIES: [(u16, &str); 14] = [
+    (0x00, "RST_00"),
+    (0x08, "RST_08"),
+    (0x10, "RST_10"),
+    (0x18, "RST_18"),
+    (0x20, "RST_20"),
+    (0x28, "RST_28"),
+    (0x30, "RST_30"),
+    (0x38, "RST_38"),
+    (0x40, "VBlankInterrupt"),
+    (0x48, "StatInterrupt"),
+    (0x50, "TimerInterrupt"),
+    (0x58, "SerialInterrupt"),
+    (0x60, "JoypadInterrupt"),
+    (0x100, "Entry"),
+];
+
+// where rom bank 0 maps the bank switch register of every mbc
+const BANK_SELECT: (u16, u16) = (0x2000, 0x3FFF);
+
+struct Traced {
+    instruction: Instruction,
+    // the bank mapped at 0x4000 when it runs, if known
+    romx: Option<usize>,
+}
+
+pub struct Analysis {
+    // by offset
+    instructions: BTreeMap<usize, Traced>,
+    labels: HashMap<usize, String>,
+}
+
+impl Analysis {
+    // trace the code in a rom
+    pub fn new(rom: &[u8]) -> Analysis {
+        let mut analysis = Analysis {
+            instructions: BTreeMap::new(),
+            labels: HashMap::new(),
+        };
+        let mut code = vec![false; rom.len()];
+        let mut pending: Vec<(u16, Option<usize>)> = ENTRIES.iter()
+            .map(|&(address, _)| (address, Some(1)))
+            .collect();
+        while let Some((address, romx)) = pending.pop() {
+            analysis.trace(rom, &mut code, address, romx, &mut pending);
+        }
+
+        for &(address, name) in ENTRIES.iter() {
+            if analysis.instructions.contains_key(&(address as usize)) {
+                analysis.labels.insert(address as usize, name.to_string());
+            }
+        }
+        let mut targets: Vec<(usize, bool)> = Vec::new();
+        for traced in analysis.instructions.values() {
+            if let Some(target) = analysis.target(traced) {
+                targets.push((target, is_call(traced.instruction.opcode)));
+            }
+        }
+        // calls name what they reach first, since a call target is a function
+        targets.sort_by_key(|&(_, call)| !call);
+        for (target, call) in targets {
+            if analysis.instructions.contains_key(&target) && !analysis.labels.contains_key(&target) {
+                let kind = if call { "Call" } else { "Jump" };
+                let name = format!("{}_{:03X}_{:04X}", kind, target / BANK_SIZE, address_of(target));
+                analysis.labels.insert(target, name);
+            }
+        }
+        analysis
+    }
+
+    // follow straight line code from address until it stops or runs into
+    // code already traced, queueing up where it branches to
+    fn trace(&mut self, rom: &[u8], code: &mut [bool], mut address: u16, mut romx: Option<usize>,
+             pending: &mut Vec<(u16, Option<usize>)>) {
+        let mut last: Option<u8> = None;
+        loop {
+            let offset = match offset_of(rom, address, romx) {
+                Some(offset) => offset,
+                None => return,
+            };
+            let bank_end = (offset / BANK_SIZE + 1) * BANK_SIZE;
+            let instruction = disasm::decode(address, |a| {
+                let at = offset + a.wrapping_sub(address) as usize;
+                if at < bank_end { rom.get(at).cloned().unwrap_or(0) } else { 0 }
+            });
+            let end = offset + instruction.length as usize;
+            if !disasm::is_valid(instruction.opcode) || end > bank_end.min(rom.len())
+                || code[offset..end].iter().any(|&c| c) {
+                return;
+            }
+            for c in &mut code[offset..end] {
+                *c = true;
+            }
+
+            // ld a, n then ld [$2000], a switches banks
+            if instruction.opcode == 0xEA && last == Some(0x3E) {
+                if let Some(Operand::Memory(target)) = instruction.operand {
+                    if target >= BANK_SELECT.0 && target <= BANK_SELECT.1 {
+                        let bank = (rom[offset - 1] as usize).max(1);
+                        romx = if bank * BANK_SIZE < rom.len() { Some(bank) } else { None };
+                    }
+                }
+            }
+            last = Some(instruction.opcode);
+
+            if let Some(Operand::Jump(target)) = instruction.operand {
+                pending.push((target, romx));
+            }
+            let stops = instruction.ends_block();
+            address = address.wrapping_add(instruction.length);
+            self.instructions.insert(offset, Traced { instruction, romx });
+            if stops {
+                return;
+            }
+        }
+    }
+
+    // the offset a jump or call goes to, rsts left out as they take a number
+    fn target(&self, traced: &Traced) -> Option<usize> {
+        match traced.instruction.operand {
+            Some(Operand::Jump(target)) if traced.instruction.opcode & 0xC7 != 0xC7 => {
+                traced_offset(target, traced.romx)
+            },
+            _ => None,
+        }
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn labels(&self) -> usize {
+        self.labels.len()
+    }
+
+    // the instruction starting at an offset
+    pub fn instruction(&self, offset: usize) -> Option<&Instruction> {
+        self.instructions.get(&offset).map(|traced| &traced.instruction)
+    }
+
+    pub fn label(&self, offset: usize) -> Option<&str> {
+        self.labels.get(&offset).map(|label| label.as_str())
+    }
+
+    // an instruction the way the listing writes it, with its target named
+    pub fn text(&self, offset: usize) -> Option<String> {
+        let traced = self.instructions.get(&offset)?;
+        let instruction = &traced.instruction;
+        let bytes_only = match (instruction.opcode, instruction.operand) {
+            // rgbasm picks the byte after stop itself
+            (0x10, _) => true,
+            // rgbasm may shorten these to ldh
+            (0xEA, Some(Operand::Memory(address))) | (0xFA, Some(Operand::Memory(address))) => address >= 0xFF00,
+            _ => false,
+        };
+        if bytes_only {
+            return None;
+        }
+        let label = self.target(traced).and_then(|target| self.labels.get(&target));
+        Some(instruction.text_with(|_| label.cloned()))
+    }
+
+    pub fn write<W: Write>(&self, rom: &[u8], out: &mut W) -> io::Result<()> {
+        writeln!(out, "; Disassembled by gb-rs from a rom with crc32 {:08X}", movie::crc32(rom))?;
+        for bank in 0..rom.len().div_ceil(BANK_SIZE) {
+            writeln!(out)?;
+            if bank == 0 {
+                writeln!(out, "SECTION \"ROM Bank $000\", ROM0[$0000]")?;
+            } else {
+                writeln!(out, "SECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:X}]", bank, bank)?;
+            }
+            let end = ((bank + 1) * BANK_SIZE).min(rom.len());
+            let mut offset = bank * BANK_SIZE;
+            while offset < end {
+                if let Some(label) = self.labels.get(&offset) {
+                    writeln!(out)?;
+                    writeln!(out, "{}:", label)?;
+                }
+                offset = match self.instructions.get(&offset) {
+                    Some(traced) => {
+                        let next = offset + traced.instruction.length as usize;
+                        match self.text(offset) {
+                            Some(text) => writeln!(out, "\t{}", text)?,
+                            None => writeln!(out, "\t{} ; {}", data(&rom[offset..next]), traced.instruction.text())?,
+                        }
+                        if traced.instruction.ends_block() {
+                            writeln!(out)?;
+                        }
+                        next
+                    },
+                    None => self.write_data(rom, offset, end, out)?,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // one line of the data at offset, returning where it stopped
+    fn write_data<W: Write>(&self, rom: &[u8], offset: usize, end: usize, out: &mut W) -> io::Result<usize> {
+        // stop short of the next instruction or label
+        let mut stop = offset + 1;
+        while stop < end && !self.instructions.contains_key(&stop) && !self.labels.contains_key(&stop) {
+            stop += 1;
+        }
+        let run = rom[offset..stop].iter().take_while(|&&byte| byte == rom[offset]).count();
+        if run >= FILL_LENGTH {
+            writeln!(out, "\tds {}, ${:02X}", run, rom[offset])?;
+            return Ok(offset + run);
+        }
+        // keep what could be filled for its own line
+        let mut length = 1;
+        while length < DATA_WIDTH && offset + length < stop {
+            let rest = &rom[offset + length..stop];
+            if rest.len() >= FILL_LENGTH && rest[..FILL_LENGTH].iter().all(|&byte| byte == rest[0]) {
+                break;
+            }
+            length += 1;
+        }
+        writeln!(out, "\t{}", data(&rom[offset..offset + length]))?;
+        Ok(offset + length)
+    }
+}
+
+fn data(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+    format!("db {}", bytes.join(", "))
+}
+
+fn is_call(opcode: u8) -> bool {
+    matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC)
+}
+
+// where in the rom an address is with romx mapped at 0x4000
+fn traced_offset(address: u16, romx: Option<usize>) -> Option<usize> {
+    match address {
+        0x0000..=0x3FFF => Some(address as usize),
+        0x4000..=0x7FFF => Some(romx? * BANK_SIZE + address as usize - 0x4000),
+        _ => None,
+    }
+}
+
+fn offset_of(rom: &[u8], address: u16, romx: Option<usize>) -> Option<usize> {
+    traced_offset(address, romx).filter(|&offset| offset < rom.len())
+}
+
+// the address an offset is seen at when its bank is mapped
+pub fn address_of(offset: usize) -> u16 {
+    if offset < BANK_SIZE {
+        offset as u16
+    } else {
+        (0x4000 + offset % BANK_SIZE) as u16
+    }
+}
@@ -1,11 +1,87 @@
-use byteorder::{ByteOrder, LittleEndian};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cpu::bus::Bus;
 use cpu::cpu::CPU;
-use cpu::op::Opcode;
 use cpu::gb::Gameboy;
+use cpu::interconnect::Interconnect;
+use cpu::mem;
+use cpu::watch::{Watch, Watchpoint};
+use debug::disasm::{self, Instruction, Operand};
+use debug::expr::Condition;
+use debug::registers;
+use debug::stack::{self, Before, CallStack, Kind};
+use debug::symbols::Symbols;
+use state;
+
+pub struct Breakpoint {
+    pub location: u16,
+    // only stop while this bank is mapped at location
+    pub bank: Option<u16>,
+    // only stop when this is true
+    pub condition: Option<Condition>,
+    // times it was reached with the condition true
+    pub hits: u32,
+    // hits still to go through without stopping
+    pub ignore: u32,
+    // removed the first time it stops
+    pub temporary: bool,
+}
+
+impl Breakpoint {
+    pub fn new(location: u16) -> Breakpoint {
+        Breakpoint {
+            location,
+            bank: None,
+            condition: None,
+            hits: 0,
+            ignore: 0,
+            temporary: false,
+        }
+    }
+}
 
 pub struct Debug {
     pub location: u16,
-    pub breakpoints: Vec<u16>,
+    pub breakpoints: Vec<Breakpoint>,
+    // the breakpoint just stopped at, so continuing does not stop there again
+    resume: Option<u16>,
+    // numbered save state slots are kept next to this rom
+    pub rom_path: Option<PathBuf>,
+    pub symbols: Symbols,
+    pub stack: CallStack,
+    // where step, next, finish and until will stop
+    stop: Option<Stop>,
+    // commands from source and the init file still to run
+    script: VecDeque<String>,
+    // source commands running inside each other
+    sourcing: usize,
+    // the last command typed, which an empty line runs again
+    last: Option<String>,
+    // the frontend shows the vram viewer, toggled by the vram command
+    pub show_vram: bool,
+}
+
+// read from the rom's directory and the current one when a rom is set
+pub const INIT_FILE: &str = ".gbrsinit";
+
+// how deep source commands nest, so a file that sources itself stops
+const MAX_SOURCE_DEPTH: usize = 16;
+
+// a stop the debugger sets for itself, dropped whenever the machine stops
+enum Stop {
+    // instructions left to run
+    Steps(u32),
+    // a temporary breakpoint, that only counts with sp at or above sp so a
+    // recursive call reaching it does not stop early
+    Reach {
+        address: u16,
+        sp: Option<u16>,
+        // an instruction has run since it was set
+        started: bool,
+    },
 }
 
 pub enum Actions  {
@@ -17,331 +93,939 @@ pub enum Actions  {
 
 impl Debug {
     pub fn new() -> Debug {
-        let debugger = Debug {
+        Debug {
             location: 0,
             breakpoints: Vec::new(),
+            resume: None,
+            rom_path: None,
+            symbols: Symbols::new(),
+            stack: CallStack::new(),
+            stop: None,
+            script: VecDeque::new(),
+            sourcing: 0,
+            last: None,
+            show_vram: false,
+        }
+    }
+
+    // save states go next to the rom, and symbols are read from the .sym
+    // file beside it if there is one. The .gbrsinit files in the rom's
+    // directory and then the current one are queued up, see run_script
+    pub fn set_rom(&mut self, rom: &Path) {
+        self.rom_path = Some(rom.to_path_buf());
+        let symbols = rom.with_extension("sym");
+        if symbols.is_file() {
+            match self.load_symbols(&symbols) {
+                Ok(count) => println!("Loaded {} symbols from {}", count, symbols.display()),
+                Err(e) => println!("Could not read symbols from {}, {}", symbols.display(), e),
+            }
+        }
+        let beside_rom = rom.parent().unwrap_or(Path::new("")).join(INIT_FILE);
+        let mut init_files = vec![beside_rom.clone()];
+        if !same_file(&beside_rom, Path::new(INIT_FILE)) {
+            init_files.push(PathBuf::from(INIT_FILE));
+        }
+        let mut lines = Vec::new();
+        for path in init_files.iter().filter(|path| path.is_file()) {
+            match read_script(path) {
+                Ok(script) => lines.extend(script),
+                Err(e) => println!("Could not read {}, {}", path.display(), e),
+            }
+        }
+        self.script.extend(lines);
+    }
+
+    // queue the commands in a file to run before any others still queued
+    pub fn source<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
+        let lines = read_script(path.as_ref())?;
+        let count = lines.len();
+        for line in lines.into_iter().rev() {
+            self.script.push_front(line);
+        }
+        Ok(count)
+    }
+
+    // Run queued commands until one hands the machine back to the frontend,
+    // a continue or quit, which is returned with the rest left for the next
+    // time it stops. Steps are run here. NOOP once the queue is empty
+    pub fn run_script(&mut self, machine: &mut Gameboy) -> Actions {
+        while let Some(line) = self.script.pop_front() {
+            println!("> {}", line);
+            match self.parse_input(&line, machine) {
+                Actions::STEP => {
+                    self.step_and_watch(machine);
+                },
+                Actions::NOOP => (),
+                action => return action,
+            }
+        }
+        Actions::NOOP
+    }
+
+    // a line typed at the prompt, where an empty line runs the last command
+    // again
+    pub fn parse_typed(&mut self, input: &str, machine: &mut Gameboy) -> Actions {
+        let input = input.trim();
+        let command = if input.is_empty() {
+            match self.last.clone() {
+                Some(last) => last,
+                None => return Actions::NOOP,
+            }
+        } else {
+            self.last = Some(input.to_string());
+            input.to_string()
         };
-        
-        debugger
+        self.parse_input(&command, machine)
+    }
+
+    pub fn load_symbols<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
+        self.symbols = Symbols::load(path)?;
+        Ok(self.symbols.len())
+    }
+
+    // a label, hardware register, bank:address or address, all in hex
+    fn parse_location(&self, text: &str) -> Option<(Option<u16>, u16)> {
+        if let Some(symbol) = self.symbols.lookup(text) {
+            return Some((Some(symbol.bank), symbol.address));
+        }
+        if let Some(address) = mem::io_address(text) {
+            return Some((None, address));
+        }
+        let mut parts = text.splitn(2, ':');
+        let first = parts.next()?;
+        match parts.next() {
+            Some(address) => Some((Some(parse_hex(first)?), parse_hex(address)?)),
+            None => Some((None, parse_hex(first)?)),
+        }
+    }
+
+    // an address with the label it is in, like 0x0153 (Main+0x3)
+    pub fn describe(&self, memory: &Interconnect, address: u16) -> String {
+        match self.symbols.nearest(memory.bank(address), address) {
+            Some((symbol, 0)) => format!("0x{:04X} ({})", address, symbol.name),
+            Some((symbol, offset)) => format!("0x{:04X} ({}+0x{:X})", address, symbol.name, offset),
+            None => format!("0x{:04X}", address),
+        }
     }
 
     pub fn set_breakpoint(&mut self, location: u16) {
-        self.breakpoints.push(location);
+        self.breakpoints.push(Breakpoint::new(location));
     }
 
-    pub fn check_breakpoints(&self, location: u16) -> bool {
-        if self.breakpoints.contains(&location) {
+    // true if the machine should stop before running the instruction at pc
+    pub fn check_breakpoints(&mut self, machine: &Gameboy) -> bool {
+        let pc = machine.cpu.pc;
+        if self.check_stop(machine) {
+            println!("Stopped at {}", self.describe(&machine.cpu.memory, pc));
+            self.resume = Some(pc);
+            return true;
+        }
+        if self.resume.take() == Some(pc) {
+            return false;
+        }
+        for i in 0..self.breakpoints.len() {
+            let breakpoint = &mut self.breakpoints[i];
+            if breakpoint.location != pc {
+                continue;
+            }
+            if breakpoint.bank.is_some_and(|bank| bank != machine.cpu.memory.bank(pc)) {
+                continue;
+            }
+            if let Some(ref condition) = breakpoint.condition {
+                if !condition.is_true(machine) {
+                    continue;
+                }
+            }
+            breakpoint.hits += 1;
+            if breakpoint.ignore > 0 {
+                breakpoint.ignore -= 1;
+                continue;
+            }
+
+            let (hits, temporary) = (breakpoint.hits, breakpoint.temporary);
+            println!("Breakpoint {} at {}, hit {} times", i, self.describe(&machine.cpu.memory, pc), hits);
+            if temporary {
+                self.breakpoints.remove(i);
+            }
+            self.resume = Some(pc);
+            self.stop = None;
             return true;
         }
         false
     }
 
-    pub fn disassemble(&self, machine: &Gameboy) {
-       // disassemble 
-        let mut pc = machine.cpu.pc as usize;
-        let mut line_count = 0;
-        while line_count < 40 {
-            // println!("0x{:02X}\t{}", pc, Opcode::parse(pc as u16, machine.cpu.memory.read_value_u8(pc)));
-            match machine.cpu.memory.read_value_u8( pc ){
-                0x00 => {
-                    println!("0x{:02X}\tNOP", pc);
-                    pc += 1;
-                },
-                0x02 => {
-                    println!("0x{:02X}\tLD (BC), A", pc);
-                    pc += 1;
-                },
-                0x03 => {
-                    println!("0x{:02X}\tINC BC", pc);
-                    pc += 1;
-                },
-                0x04 => {
-                    println!("0x{:02X}\tINC B", pc);
-                    pc += 1;
-                }
-                0x05 => {
-                    println!("0x{:02X}\tDEC B", pc);
-                    pc += 1;
-                },
-                0x0B => {
-                    println!("0x{:02X}\tDEC BC", pc);
-                    pc += 1;
-                },
-                0x0D => {
-                    println!("0x{:02X}\tDEC C", pc);
-                    pc += 1;
-                },
-                0x1D => {
-                    println!("0x{:02X}\tDEC E", pc);
-                    pc += 1;
-                },
-                0x06 => {
-                    println!("0x{:02X}\tLD B, 0x{:02X}", pc, machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 2;
-                },
-                0x0C => {
-                    println!("0x{:02X}\tINC C", pc);
-                    pc += 1;
-                },
-                0x0E => { 
-                    println!("0x{:02X}\tLD C, 0x{:02X}", pc, machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 2;
-                },
-                0x11 => {
-                    println!("0x{:02X}\tLD DE, 0x{:02X}{:X}", pc, machine.cpu.memory.read_value_u8( pc + 2 ), machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 3;
-                },
-                0x17 => {
-                    println!("0x{:02X}\tRLA", pc);
-                    pc += 1;
-                }
-                0x12 => {
-                    println!("0x{:02X}\tLD (DE), A", pc);
-                    pc += 1;
-                }
-                0x13 => {
-                    println!("0x{:02X}\tINC DE", pc);
-                    pc += 1;
-                },
-                0x14 => {
-                    println!("0x{:02X}\tINC D", pc);
-                    pc += 1;
-                },
-                0x15 => {
-                    println!("0x{:02X}\tDEC D", pc);
-                    pc += 1;
-                },
-                0x16 => {
-                    println!("0x{:02X}\tLD D, 0x{:02X}", pc, machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 2;
-                },
-                0x18 => {
-                    let value =  machine.cpu.memory.read_value_u8( pc + 1 );
-                    println!("0x{:02X}\tJR 0x{:02X} 0x{:02X}", pc, (pc + value as usize), value);
-                    pc += 2;
-                },
-                0x1A => {
-                    println!("0x{:02X}\tLD A, (DE)", pc);
-                    pc += 1;
-                },
-                0x1C => {
-                    println!("0x{:02X}\tINC E", pc);
-                    pc += 1;
-                }
-                0x1E => {
-                    println!("0x{:02X}\tLD E, d8 0x{:02X}", pc, machine.cpu.memory.read_value_u8(pc + 1));
-                    pc += 2;
-                },
-                0x20 => {
-                    // calculate jmp location
-                    let mut location = LittleEndian::read_int(&[machine.cpu.memory.read_value_u8( (pc + 1) as usize )], 1);
-                    location  = (((pc + 2) as i32) + (((location as u8) as i8) as i32)) as i64;
-                    println!("0x{:02X}\tJR NZ, 0x{:02X}", pc, location);
-                    pc += 2;
-                },
-                0x21 => {
-                    println!("0x{:02X}\tLD HL, 0x{:02X}{:X}", pc, machine.cpu.memory.read_value_u8( (pc + 2) as usize ), machine.cpu.memory.read_value_u8( (pc + 1) as usize ));
-                    pc += 3;
-                },
-                0x22 => {
-                    println!("0x{:02X}\tLD (HL+), A", pc);
-                    pc += 1;
-                },
-                0x23 => {
-                    println!("0x{:02X}\tINC HL", pc);
-                    pc += 1;
-                },
-                0x24 => {
-                    println!("0x{:02X}\tINC H", pc);
-                    pc += 1;
-                },
-                0x28 => {
-                    let mut location = LittleEndian::read_int(&[machine.cpu.memory.read_value_u8( (pc + 1) as usize )], 1);
-                    location  = (((pc + 2) as i32) + (((location as u8) as i8) as i32)) as i64;
-                    println!("0x{:02X}\tJR Z, r8 0x{:04X}", pc, location);
-                    pc += 2;
-                },
-                0x2A => {
-                    println!("0x{:02X}\tLD A, (HL+)", pc);
-                    pc += 1;
+    // true once the stop step, next, finish or until set is reached
+    fn check_stop(&mut self, machine: &Gameboy) -> bool {
+        let reached = match self.stop {
+            Some(Stop::Steps(0)) => true,
+            Some(Stop::Steps(ref mut left)) => {
+                *left -= 1;
+                false
+            },
+            Some(Stop::Reach { address, sp, ref mut started }) => {
+                let reached = *started && machine.cpu.pc == address
+                    && sp.is_none_or(|sp| machine.cpu.sp >= sp);
+                *started = true;
+                reached
+            },
+            None => false,
+        };
+        if reached {
+            self.stop = None;
+        }
+        reached
+    }
+
+    // <location> [if <condition>]
+    fn add_breakpoint(&mut self, arguments: &[&str], temporary: bool) {
+        let (bank, location) = match arguments.first().and_then(|location| self.parse_location(location)) {
+            Some(location) => location,
+            None => {
+                println!("Usage: b|tb <address|bank:address|label> [if <condition>]");
+                return;
+            }
+        };
+        let condition = match arguments.get(1) {
+            Some(&"if") => match Condition::parse(&arguments[2..].join(" ")) {
+                Ok(condition) => Some(condition),
+                Err(e) => {
+                    println!("Bad condition, {}", e);
+                    return;
                 }
-                0x31 => {
-                    println!("0x{:02X}\tLD SP, 0x{:02X}{:X}", pc, machine.cpu.memory.read_value_u8( (pc + 2) as usize ), machine.cpu.memory.read_value_u8( (pc + 1) as usize ));
-                    pc += 3;
-                },
-                0x32 => {
-                    println!("0x{:02X}\tLD (HL-),A\t", pc);
-                    pc += 1;
-                },
-                0x3E => {
-                    
-                    println!("0x{:02X}\tLD A, 0x{:02X}", pc, machine.cpu.memory.read_value_u8(( pc + 1) as usize ));
-                    pc += 2;
-                },
-                0x4F => {
-                    println!("0x{:02X}\tLD C, A", pc);
-                    pc += 1;
-                },
-                0x47 => {
-                    println!("0x{:02X}\tLD B, A", pc);
-                    pc += 1;
-                },
-                0x57 => {
-                    println!("0x{:02X}\tLD D, A", pc);
-                    pc += 1;
-                },
-                0x66 => {
-                    println!("0x{:02X}\tLD H, (HL)", pc);
-                    pc += 1;
-                },
-                0x7C => {
-                    println!("0x{:02X}\tLD A, H", pc);
-                    pc += 1;
+            },
+            Some(_) => {
+                println!("Usage: b|tb <address|bank:address|label> [if <condition>]");
+                return;
+            },
+            None => None,
+        };
+        let mut breakpoint = Breakpoint::new(location);
+        breakpoint.bank = bank;
+        breakpoint.condition = condition;
+        breakpoint.temporary = temporary;
+        self.breakpoints.push(breakpoint);
+        println!("Breakpoint {} set", self.breakpoints.len() - 1);
+    }
+
+    fn list_breakpoints(&self) {
+        for (i, b) in self.breakpoints.iter().enumerate() {
+            let mut line = match b.bank {
+                Some(bank) => format!("{}: {:02X}:{:04X}", i, bank, b.location),
+                None => format!("{}: 0x{:04X}", i, b.location),
+            };
+            if let Some(symbol) = b.bank.and_then(|bank| self.symbols.at(bank, b.location)) {
+                line += &format!(" ({})", symbol.name);
+            }
+            if let Some(ref condition) = b.condition {
+                line += &format!(" if {}", condition.source);
+            }
+            line += &format!(", hit {} times", b.hits);
+            if b.ignore > 0 {
+                line += &format!(", ignoring the next {}", b.ignore);
+            }
+            if b.temporary {
+                line += ", temporary";
+            }
+            println!("{}", line);
+        }
+    }
+
+    // the breakpoint a command argument numbers
+    fn breakpoint_mut(&mut self, argument: Option<&&str>) -> Option<&mut Breakpoint> {
+        let n = argument?.parse::<usize>().ok()?;
+        self.breakpoints.get_mut(n)
+    }
+
+    // report the watchpoint the last instruction hit, if any. pc is where
+    // that instruction started
+    pub fn check_watchpoints(&mut self, machine: &mut Gameboy, pc: u16) -> bool {
+        let hit = match machine.cpu.memory.take_watch_hit() {
+            Some(hit) => hit,
+            None => return false,
+        };
+        self.stop = None;
+        let access = if hit.write { "write of" } else { "read of" };
+        let by = if hit.dma {
+            "oam dma".to_string()
+        } else {
+            let memory = &machine.cpu.memory;
+            let instruction = disasm::decode(pc, |a| memory.read_value_u8(a as usize));
+            format!("{} {}", self.describe(memory, pc), self.instruction_text(memory, &instruction))
+        };
+        println!("Watchpoint {}: {} 0x{:02X} at 0x{:04X} by {}", hit.index, access, hit.value, hit.address, by);
+        true
+    }
+
+    // watch <address|start-end> [value]
+    fn add_watchpoint(&self, watch: Watch, arguments: &[&str], machine: &mut Gameboy) {
+        let range = arguments.first().and_then(|range| {
+            let mut ends = range.splitn(2, '-');
+            let start = parse_hex(ends.next()?)?;
+            let end = match ends.next() {
+                Some(end) => parse_hex(end)?,
+                None => start,
+            };
+            Some((start, end))
+        });
+        let value = match arguments.get(1) {
+            Some(value) => match parse_hex(value) {
+                Some(value) if value <= 0xFF => Some(Some(value as u8)),
+                _ => None,
+            },
+            None => Some(None),
+        };
+        match (range, value) {
+            (Some((start, end)), Some(value)) if start <= end => {
+                let watchpoints = &mut machine.cpu.memory.watchpoints;
+                watchpoints.push(Watchpoint::new(start, end, watch, value));
+                println!("Watchpoint {} set", watchpoints.len() - 1);
+            },
+            _ => println!("Usage: watch|rwatch|awatch <address|start-end> [value]"),
+        }
+    }
+
+    // list count instructions from start, or up to end when there is one,
+    // with => at pc and * at breakpoints
+    pub fn disassemble(&self, machine: &Gameboy, start: u16, end: Option<u16>, count: usize) {
+        let memory = &machine.cpu.memory;
+        let mut address = start;
+        let mut listed = 0;
+        while end.map_or(listed < count, |end| address <= end) {
+            let bank = memory.bank(address);
+            if let Some(symbol) = self.symbols.at(bank, address) {
+                println!("{}:", symbol.name);
+            }
+            let instruction = disasm::decode(address, |a| memory.read_value_u8(a as usize));
+            let bytes: Vec<String> = (0..instruction.length)
+                .map(|i| format!("{:02X}", memory.read_value_u8(address.wrapping_add(i) as usize)))
+                .collect();
+            let current = if address == machine.cpu.pc { "=>" } else { "  " };
+            let breakpoint = self.breakpoints.iter()
+                .any(|b| b.location == address && b.bank.is_none_or(|b| b == bank));
+            println!("{} {} {:02X}:{:04X}  {:<8}  {}", current, if breakpoint { "*" } else { " " },
+                     bank, address, bytes.join(" "), self.instruction_text(memory, &instruction));
+            listed += 1;
+            match address.checked_add(instruction.length) {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+    }
+
+    // an instruction with the addresses in it named by their labels
+    fn instruction_text(&self, memory: &Interconnect, instruction: &Instruction) -> String {
+        instruction.text_with(|operand| {
+            let address = match operand {
+                Operand::Jump(address) | Operand::Memory(address) => address,
+            };
+            self.symbols.at(memory.bank(address), address).map(|symbol| symbol.name.clone())
+        })
+    }
+
+    // l [location[-location]] [count]
+    fn list(&self, arguments: &[&str], machine: &Gameboy) {
+        let memory = &machine.cpu.memory;
+        let range = match arguments.first() {
+            Some(range) => {
+                let mut ends = range.splitn(2, '-');
+                let start = ends.next().and_then(|start| self.parse_location(start));
+                let end = match ends.next() {
+                    Some(end) => self.parse_location(end).map(Some),
+                    None => Some(None),
+                };
+                start.and_then(|start| end.map(|end| (start, end)))
+            },
+            None => Some(((None, machine.cpu.pc), None)),
+        };
+        let count = match arguments.get(1) {
+            Some(count) => count.parse::<usize>().ok(),
+            None => Some(40),
+        };
+        let ((bank, start), end) = match (range, count) {
+            (Some(range), Some(_)) => range,
+            _ => {
+                println!("Usage: l [location[-location]] [count]");
+                return;
+            },
+        };
+        // only what is mapped in can be read
+        for &(bank, address) in [(bank, start)].iter().chain(end.iter()) {
+            if let Some(bank) = bank {
+                if bank != memory.bank(address) {
+                    println!("Bank 0x{:02X} is not mapped at 0x{:04X}", bank, address);
+                    return;
                 }
-                0x77 => {
-                    println!("0x{:02X}\tLD (HL), A", pc);
-                    pc += 1;
-                },
-                0x78 => {
-                    println!("0x{:02X}\tLD A, B", pc);
-                    pc += 1;
-                },
-                0x7B => {
-                    println!("0x{:02X}\tLD A, E", pc);
-                    pc += 1;
-                },
-                0x90 => {
-                    println!("0x{:02X}\tSUB B", pc);
-                    pc += 1;
+            }
+        }
+        match end {
+            Some((_, end)) if end < start => println!("The range ends before it starts"),
+            end => self.disassemble(machine, start, end.map(|(_, end)| end), count.unwrap()),
+        }
+    }
+
+    // an address with what is there, a label, a hardware register or the
+    // region, like 0xFF40 (LCDC)
+    fn describe_data(&self, memory: &Interconnect, address: u16) -> String {
+        let name = match self.symbols.at(memory.bank(address), address) {
+            Some(symbol) => symbol.name.as_str(),
+            None => mem::io_register(address).unwrap_or_else(|| mem::region(address)),
+        };
+        format!("0x{:04X} ({})", address, name)
+    }
+
+    // start-end, both ends included
+    fn parse_range(&self, text: &str) -> Option<(u16, u16)> {
+        let mut ends = text.splitn(2, '-');
+        let (_, start) = self.parse_location(ends.next()?)?;
+        let (_, end) = self.parse_location(ends.next()?)?;
+        Some((start, end))
+    }
+
+    // x <location> [length], hex and ascii a line at a time, the hardware
+    // registers a line each with their names. Reading has no side effects
+    fn examine(&self, arguments: &[&str], machine: &Gameboy) {
+        let start = arguments.first().and_then(|location| self.parse_location(location));
+        let length = match arguments.get(1) {
+            Some(length) => parse_count(length),
+            None => Some(64),
+        };
+        let (start, length) = match (start, length) {
+            (Some((_, start)), Some(length)) if length > 0 => (start as usize, length),
+            _ => {
+                println!("Usage: x <location> [length]");
+                return;
+            },
+        };
+        let memory = &machine.cpu.memory;
+        let end = (start + length).min(0x10000);
+        let mut address = start;
+        while address < end {
+            let region = mem::region(address as u16);
+            if region == "IO" || region == "IE" {
+                let name = mem::io_register(address as u16).unwrap_or("-");
+                println!("{:<6}{:04X}  {:02X}  {}", region, address, memory.peek(address as u16), name);
+                address += 1;
+                continue;
+            }
+            // lines stop where their region does
+            let mut line_end = (address + 16).min(end);
+            while mem::region((line_end - 1) as u16) != region {
+                line_end -= 1;
+            }
+            let bytes: Vec<u8> = (address..line_end).map(|a| memory.peek(a as u16)).collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let ascii: String = bytes.iter()
+                .map(|&byte| if byte == b' ' || byte.is_ascii_graphic() { byte as char } else { '.' })
+                .collect();
+            println!("{:<6}{:04X}  {:<47}  |{}|", region, address, hex.join(" "), ascii);
+            address = line_end;
+        }
+    }
+
+    // io lists every hardware register with its fields on one line, io <name>
+    // shows one a field to a line
+    // the lines the io command prints
+    pub fn io(&self, arguments: &[&str], machine: &Gameboy) -> Vec<String> {
+        let memory = &machine.cpu.memory;
+        let mut lines = Vec::new();
+        match arguments.first() {
+            None => {
+                for &(address, name) in mem::IO_REGISTERS.iter() {
+                    let value = memory.peek(address);
+                    let fields: Vec<String> = registers::fields(address, value).iter()
+                        .map(|field| format!("{} {}", field.name, field.value))
+                        .collect();
+                    // plain numbers are shown in decimal
+                    let text = if fields.is_empty() { value.to_string() } else { fields.join(", ") };
+                    lines.push(format!("{:<5} {:04X}  {:02X}  {}", name, address, value, text));
                 }
-                0xAF => {
-                    println!("0x{:02X}\tXOR A", pc);
-                    pc += 1;
-                },
-                0xBE => {
-                    println!("0x{:02X}\tCP (HL)", pc);
-                    pc += 1;
-                },
-                0xC0 => {
-                    println!("0x{:02X}\tRET NZ", pc);
-                    pc += 1;
-                },
-                0xC1 => {
-                    println!("0x{:02X}\tPOP BC", pc);
-                    pc += 1;
-                },
-                0xC3 => {
-                    let location = machine.cpu.read_word();
-                    println!("0x{:02X}\tJP 0x{:02X}", pc, location);
-                    pc += 2;
-                },
-                0xC5 =>  {
-                    println!("0x{:02X}\tPUSH BC", pc);
-                    pc += 1;
-                },
-                0xCB => { // redirect 
-                    match machine.cpu.memory.read_value_u8( pc + 1 ) {
-                        0x11 => println!("0x{:02X}\tRL C", pc + 1),
-                        0x7C => println!("0x{:02X}\tBIT 7, H", pc + 1),
-                        _ =>  println!("Not disassembled redirect 0x{:02X}", pc + 1),
-                    };
-                    pc += 2;
-                },
-                0xCC => {
-                    println!("0x{:02X}\tCALL Z, 0x{:02X}{:X}", pc, machine.cpu.memory.read_value_u8( (pc + 2) as usize ), machine.cpu.memory.read_value_u8( (pc + 1) as usize ));
-                    pc += 3;
-                },
-                0xCD => {
-                    println!("0x{:02X}\tCALL 0x{:02X}{:X}", pc, machine.cpu.memory.read_value_u8( (pc + 2) as usize ), machine.cpu.memory.read_value_u8( (pc + 1) as usize ));
-                    pc += 3;
-                },
-                0xCE => {
-                    println!("0x{:02X}\tADC A, 0x{:02X}", pc, machine.cpu.memory.read_value_u8((pc + 1) as usize));
-                    pc += 2;
+            },
+            Some(name) => match mem::io_address(name) {
+                Some(address) if arguments.len() == 1 => {
+                    let value = memory.peek(address);
+                    lines.push(format!("{} {:04X} = 0x{:02X} ({})", mem::io_register(address).unwrap(), address, value, value));
+                    for field in registers::fields(address, value) {
+                        lines.push(format!("  {:<4} {:<17} {}", field.bits, field.name, field.value));
+                    }
                 },
-                0xC9 => {
-                    println!("0x{:02X}\tRET", pc);
-                    pc += 1;
+                _ => lines.push("Usage: io [register name]".to_string()),
+            },
+        }
+        lines
+    }
+
+    // set writes through the bus the way the cpu would, so writing DMA starts
+    // a dma and LY ignores it, while poke stores the byte as it is
+    fn write(&self, machine: &mut Gameboy, address: u16, value: u8, bus: bool) {
+        let memory = &mut machine.cpu.memory;
+        if bus {
+            memory.write(address, value);
+        } else {
+            memory.poke(address, value);
+        }
+    }
+
+    // watchpoints still see what the debugger writes
+    fn report_own_watch_hit(&self, machine: &mut Gameboy) {
+        if let Some(hit) = machine.cpu.memory.take_watch_hit() {
+            println!("Watchpoint {}: write of 0x{:02X} at 0x{:04X} by the debugger", hit.index, hit.value, hit.address);
+        }
+    }
+
+    // set|poke <location> <value>
+    fn store(&self, arguments: &[&str], machine: &mut Gameboy, bus: bool) {
+        let address = arguments.first().and_then(|location| self.parse_location(location));
+        let value = arguments.get(1).and_then(|value| parse_byte(value));
+        match (address, value, arguments.len()) {
+            (Some((_, address)), Some(value), 2) => {
+                self.write(machine, address, value, bus);
+                let memory = &machine.cpu.memory;
+                println!("{} = 0x{:02X}", self.describe_data(memory, address), memory.peek(address));
+                self.report_own_watch_hit(machine);
+            },
+            _ => println!("Usage: set|poke <location> <value>"),
+        }
+    }
+
+    // fill|pfill <start-end|start length> <value>, fill through the bus and
+    // pfill without side effects
+    fn fill(&self, arguments: &[&str], machine: &mut Gameboy, bus: bool) {
+        let range = match arguments.len() {
+            2 => self.parse_range(arguments[0]),
+            3 => self.parse_location(arguments[0]).and_then(|(_, start)| {
+                let length = parse_count(arguments[1]).filter(|&length| length > 0)?;
+                Some((start, (start as usize + length - 1).min(0xFFFF) as u16))
+            }),
+            _ => None,
+        };
+        let value = arguments.last().and_then(|value| parse_byte(value));
+        match (range, value) {
+            (Some((start, end)), Some(value)) if start <= end => {
+                for address in start..=end {
+                    self.write(machine, address, value, bus);
                 }
-                0xE0 => {
-                    println!("0x{:02X}\tLDH ($FF00+0x{:02X}), A", pc, machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 2;
-                },
-                0xF0 => {
-                    println!("0x{:02X}\tLDH A, ($FF00+0x{:02X})", pc, machine.cpu.memory.read_value_u8( pc + 1 ));
-                    pc += 2;
-                },
-                0xE2 => {
-                    println!("0x{:02X}\tLD ($FF00+C), A", pc);
-                    pc += 1;
-                },
-                0xEA => {
-                    println!("0x{:02X}\tLD (a16), A", pc);
-                    pc += 3;
-                },
-                0xFE => {
-                    println!("0x{:02X}\tCP d8, (0x{:02X})", pc, machine.cpu.memory.read_value_u8(pc + 1));
-                    pc += 2;
-                    
-                },
-                _ => println!("Not disassembled 0x{:02X}, 0x{:02X}", pc, machine.cpu.memory.read_value_u8( pc )) 
-            }
-            line_count += 1;
+                println!("Filled 0x{:04X}-0x{:04X} with 0x{:02X}", start, end, value);
+                self.report_own_watch_hit(machine);
+            },
+            _ => println!("Usage: fill|pfill <start-end|start length> <value>"),
         }
     }
 
-    pub fn parse_input(&mut self, input: &str, machine: &Gameboy) -> Actions {
+    // r [register value], without arguments it prints them all
+    fn set_register(&mut self, arguments: &[&str], cpu: &mut CPU) {
+        let value = arguments.get(1).and_then(|value| parse_hex(value));
+        let (name, value) = match (arguments.first(), value, arguments.len()) {
+            (_, _, 0) => {
+                self.print_status(cpu);
+                return;
+            },
+            (Some(name), Some(value), 2) => (name.to_lowercase(), value),
+            _ => {
+                println!("Usage: r [register value]");
+                return;
+            },
+        };
+        let (high, low) = ((value >> 8) as u8, value as u8);
+        let byte = if value <= 0xFF { Some(low) } else { None };
+        match (name.as_str(), byte) {
+            ("a", Some(value)) => cpu.a = value,
+            ("b", Some(value)) => cpu.b = value,
+            ("c", Some(value)) => cpu.c = value,
+            ("d", Some(value)) => cpu.d = value,
+            ("e", Some(value)) => cpu.e = value,
+            ("f", Some(value)) => cpu.set_flags(value),
+            ("h", Some(value)) => cpu.h = value,
+            ("l", Some(value)) => cpu.l = value,
+            ("af", _) => {
+                cpu.a = high;
+                cpu.set_flags(low);
+            },
+            ("bc", _) => {
+                cpu.b = high;
+                cpu.c = low;
+            },
+            ("de", _) => {
+                cpu.d = high;
+                cpu.e = low;
+            },
+            ("hl", _) => {
+                cpu.h = high;
+                cpu.l = low;
+            },
+            ("sp", _) => cpu.sp = value,
+            ("pc", _) => {
+                cpu.pc = value;
+                // continuing from here should not stop on a breakpoint first
+                self.resume = Some(value);
+            },
+            _ => {
+                println!("Usage: r [register value], registers are a-l, f, af, bc, de, hl, sp and pc");
+                return;
+            },
+        }
+        println!("{} = 0x{:X}", name.to_uppercase(), value);
+    }
+
+    pub fn parse_input(&mut self, input: &str, machine: &mut Gameboy) -> Actions {
 
         let split: Vec<&str> = input.split(" ").collect();
-        match split[0].as_ref() {
-            ":q" => return Actions::EXIT,
-            "quit" => return Actions::EXIT,
-            "s" => return Actions::STEP,
-            "step" => return Actions::STEP,
+        match split[0] {
+            ":q" => Actions::EXIT,
+            "quit" => Actions::EXIT,
+            "s" | "step" => self.step_command(&split[1..]),
+            "n" | "next" => self.next(machine),
+            "finish" => self.finish(machine),
+            "u" | "until" => self.until(&split[1..]),
+            "bt" | "backtrace" => {
+                self.backtrace(machine);
+                Actions::NOOP
+            },
             "p" => {
                 self.print_status(&machine.cpu);
-                return Actions::NOOP
+                Actions::NOOP
             },
-            "c" => return Actions::BREAK,
-            "continue" => return Actions::BREAK,
+            "c" => Actions::BREAK,
+            "continue" => Actions::BREAK,
             "b" => {
                 // set a breakpoint, assume numbers are in hex format
-                if split.len() > 1 {
-                    // let loc: u16 = split[1].parse().unwrap();
-                    let loc: u16 = u16::from_str_radix(split[1], 16).unwrap();
-                    self.set_breakpoint(loc);
-                }
-                return Actions::NOOP;
+                self.add_breakpoint(&split[1..], false);
+                Actions::NOOP
+            }
+            "tb" => {
+                // a breakpoint that is removed once it stops
+                self.add_breakpoint(&split[1..], true);
+                Actions::NOOP
             }
             "bs" => {
-                let mut i = 0;
-                for b in &self.breakpoints {
-                    println!("{}: 0x{:02X}", i, b);
-                    i += 1;
-                }
-                return Actions::NOOP;
+                self.list_breakpoints();
+                Actions::NOOP
             },
             "bc" => {
-                let loc: u16 = split[1].parse().unwrap();
-                self.breakpoints.remove(loc as usize);
-                return Actions::NOOP;
+                match split.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if n < self.breakpoints.len() => {
+                        self.breakpoints.remove(n);
+                    },
+                    _ => println!("Usage: bc <breakpoint number>"),
+                }
+                Actions::NOOP
+            },
+            "ignore" => {
+                // ignore <breakpoint> <count>
+                let count = split.get(2).and_then(|count| count.parse::<u32>().ok());
+                match (count, self.breakpoint_mut(split.get(1))) {
+                    (Some(count), Some(breakpoint)) => breakpoint.ignore = count,
+                    _ => println!("Usage: ignore <breakpoint number> <count>"),
+                }
+                Actions::NOOP
+            },
+            "cond" => {
+                // cond <breakpoint> [condition], without one it always stops
+                let condition = match split.len() {
+                    0..=2 => Ok(None),
+                    _ => Condition::parse(&split[2..].join(" ")).map(Some),
+                };
+                match (condition, self.breakpoint_mut(split.get(1))) {
+                    (Ok(condition), Some(breakpoint)) => breakpoint.condition = condition,
+                    (Err(e), Some(_)) => println!("Bad condition, {}", e),
+                    (_, None) => println!("Usage: cond <breakpoint number> [condition]"),
+                }
+                Actions::NOOP
+            },
+            "w" | "watch" => {
+                self.add_watchpoint(Watch::Write, &split[1..], machine);
+                Actions::NOOP
+            },
+            "rw" | "rwatch" => {
+                self.add_watchpoint(Watch::Read, &split[1..], machine);
+                Actions::NOOP
+            },
+            "aw" | "awatch" => {
+                self.add_watchpoint(Watch::Access, &split[1..], machine);
+                Actions::NOOP
+            },
+            "ws" => {
+                for (i, w) in machine.cpu.memory.watchpoints.iter().enumerate() {
+                    let value = w.value.map(|value| format!(" == 0x{:02X}", value)).unwrap_or_default();
+                    println!("{}: {:?} 0x{:04X}-0x{:04X}{}", i, w.watch, w.start, w.end, value);
+                }
+                Actions::NOOP
+            },
+            "wc" => {
+                let watchpoints = &mut machine.cpu.memory.watchpoints;
+                match split.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if n < watchpoints.len() => {
+                        watchpoints.remove(n);
+                    },
+                    _ => println!("Usage: wc <watchpoint number>"),
+                }
+                Actions::NOOP
             },
             "l" => {
-                // disassemble the next ten lines
-                self.disassemble(&machine);
-                return Actions::NOOP
+                // disassemble from pc or a location
+                self.list(&split[1..], machine);
+                Actions::NOOP
+            },
+            "x" => {
+                self.examine(&split[1..], machine);
+                Actions::NOOP
+            },
+            "io" => {
+                for line in self.io(&split[1..], machine) {
+                    println!("{}", line);
+                }
+                Actions::NOOP
+            },
+            "vram" => {
+                self.show_vram = !self.show_vram;
+                println!("VRAM viewer {}", if self.show_vram { "on" } else { "off" });
+                Actions::NOOP
+            },
+            "set" | "poke" => {
+                self.store(&split[1..], machine, split[0] == "set");
+                Actions::NOOP
+            },
+            "fill" | "pfill" => {
+                self.fill(&split[1..], machine, split[0] == "fill");
+                Actions::NOOP
+            },
+            "r" => {
+                self.set_register(&split[1..], &mut machine.cpu);
+                Actions::NOOP
+            },
+            "source" => match split.get(1) {
+                Some(_) if self.sourcing >= MAX_SOURCE_DEPTH => {
+                    println!("Not sourcing more than {} files inside each other", MAX_SOURCE_DEPTH);
+                    Actions::NOOP
+                },
+                Some(path) if split.len() == 2 => match self.source(path) {
+                    Ok(_) => {
+                        self.sourcing += 1;
+                        let action = self.run_script(machine);
+                        self.sourcing -= 1;
+                        action
+                    },
+                    Err(e) => {
+                        println!("Could not read {}, {}", path, e);
+                        Actions::NOOP
+                    },
+                },
+                _ => {
+                    println!("Usage: source <file>");
+                    Actions::NOOP
+                },
+            },
+            "sym" => {
+                match split.get(1) {
+                    Some(path) => match self.load_symbols(path) {
+                        Ok(count) => println!("Loaded {} symbols", count),
+                        Err(e) => println!("Could not read symbols from {}, {}", path, e),
+                    },
+                    None => println!("Usage: sym <file>"),
+                }
+                Actions::NOOP
+            },
+            "save" => {
+                match self.state_path(split.get(1).cloned()) {
+                    Ok(path) => match state::save_file(machine, &path) {
+                        Ok(()) => println!("Saved state to {}", path.display()),
+                        Err(e) => println!("Could not save state to {}, {}", path.display(), e),
+                    },
+                    Err(why) => println!("{}, usage: save <slot 1-{}|file>", why, state::SLOTS),
+                }
+                Actions::NOOP
             },
-            _ => return Actions::NOOP,
+            "load" => {
+                match self.state_path(split.get(1).cloned()) {
+                    Ok(path) => match state::load_file(machine, &path) {
+                        Ok(()) => {
+                            self.rebuild_stack(machine);
+                            self.print_status(&machine.cpu);
+                        },
+                        Err(e) => println!("Could not load state from {}, {}", path.display(), e),
+                    },
+                    Err(why) => println!("{}, usage: load <slot 1-{}|file>", why, state::SLOTS),
+                }
+                Actions::NOOP
+            },
+            _ => Actions::NOOP,
         }
     }
     
-    pub fn step(&self, machine: &mut Gameboy) {
-        let res = machine.step();
+    // a slot next to the rom, or a file. A bare number is always taken as a
+    // slot, never as a file named after it
+    fn state_path(&self, argument: Option<&str>) -> Result<PathBuf, String> {
+        let argument = argument.ok_or_else(|| String::from("No slot or file given"))?;
+        let slot = match argument.parse::<u8>() {
+            Ok(slot) => slot,
+            Err(_) if argument.chars().all(|c| c.is_ascii_digit()) => return Err(format!("No slot {}", argument)),
+            Err(_) => return Ok(PathBuf::from(argument)),
+        };
+        match self.rom_path {
+            _ if !(1..=state::SLOTS).contains(&slot) => Err(format!("No slot {}", slot)),
+            Some(ref rom) => Ok(state::slot_path(rom, slot)),
+            None => Err(String::from("Slots need a rom to go next to, give a file")),
+        }
+    }
+
+    // run one instruction and keep the call stack up to date, everything the
+    // debugger runs goes through here
+    pub fn step(&mut self, machine: &mut Gameboy) {
+        let before = Before {
+            pc: machine.cpu.pc,
+            sp: machine.cpu.sp,
+            opcode: machine.cpu.memory.peek(machine.cpu.pc),
+            bank: machine.cpu.memory.bank(machine.cpu.pc),
+        };
+        machine.step();
+        self.stack.track(before, &machine.cpu.memory, machine.cpu.pc, machine.cpu.sp);
+    }
+
+    // a step asked for from the prompt or a script, which reports the
+    // watchpoints it hits. True if it hit one
+    pub fn step_and_watch(&mut self, machine: &mut Gameboy) -> bool {
+        let pc = machine.cpu.pc;
+        self.step(machine);
+        self.check_watchpoints(machine, pc)
+    }
+
+    // after a state is loaded the frames the stack had are not the ones on
+    // the game's stack, so they are looked for there
+    pub fn rebuild_stack(&mut self, machine: &Gameboy) {
+        self.stack.rebuild(&machine.cpu.memory, machine.cpu.sp);
+    }
+
+    // bt prints pc and then where each frame returns to, innermost first
+    fn backtrace(&self, machine: &Gameboy) {
+        let memory = &machine.cpu.memory;
+        let pc = machine.cpu.pc;
+        println!("#0  {}", self.describe_in(memory.bank(pc), pc));
+        for (i, frame) in self.stack.frames().enumerate() {
+            let kind = match frame.kind {
+                Kind::Call => "",
+                Kind::Rst => ", rst",
+                Kind::Interrupt => ", interrupt",
+                Kind::Recovered => ", found on the stack",
+            };
+            println!("#{:<2} {} from {}{}", i + 1, self.describe_in(frame.bank, frame.return_address),
+                     self.describe_in(frame.bank, frame.call_site), kind);
+        }
+        if let Some(mismatch) = self.stack.mismatch {
+            println!("Warning: ret at {} went to {} instead of {}, the stack was changed by hand",
+                     self.describe(memory, mismatch.ret), self.describe(memory, mismatch.landed),
+                     self.describe(memory, mismatch.expected));
+        }
+    }
+
+    // like describe with the bank given, as 01:4005 (Label+0x5)
+    fn describe_in(&self, bank: u16, address: u16) -> String {
+        match self.symbols.nearest(bank, address) {
+            Some((symbol, 0)) => format!("{:02X}:{:04X} ({})", bank, address, symbol.name),
+            Some((symbol, offset)) => format!("{:02X}:{:04X} ({}+0x{:X})", bank, address, symbol.name, offset),
+            None => format!("{:02X}:{:04X}", bank, address),
+        }
+    }
+
+    // step [count], more than one runs until that many have gone
+    fn step_command(&mut self, arguments: &[&str]) -> Actions {
+        match arguments.first().map(|count| count.parse::<u32>()) {
+            None | Some(Ok(1)) => Actions::STEP,
+            Some(Ok(count)) if count > 1 => {
+                self.stop = Some(Stop::Steps(count));
+                Actions::BREAK
+            },
+            _ => {
+                println!("Usage: s|step [count]");
+                Actions::NOOP
+            },
+        }
+    }
+
+    // next steps over calls and rsts by stopping at the return address with
+    // the stack back where it was
+    fn next(&mut self, machine: &Gameboy) -> Actions {
+        let cpu = &machine.cpu;
+        match stack::call_length(cpu.memory.peek(cpu.pc)) {
+            Some(length) => {
+                self.reach(cpu.pc.wrapping_add(length), Some(cpu.sp));
+                Actions::BREAK
+            },
+            None => Actions::STEP,
+        }
+    }
+
+    // finish runs until the function the call stack says is running returns
+    fn finish(&mut self, machine: &Gameboy) -> Actions {
+        let frame = match self.stack.current() {
+            Some(frame) => *frame,
+            None => {
+                println!("Not in a function the debugger saw called");
+                return Actions::NOOP;
+            },
+        };
+        println!("Run till exit from {}", self.describe(&machine.cpu.memory, frame.function));
+        self.reach(frame.return_address, Some(frame.sp.wrapping_add(2)));
+        Actions::BREAK
+    }
+
+    // until <location>
+    fn until(&mut self, arguments: &[&str]) -> Actions {
+        match arguments.first().and_then(|location| self.parse_location(location)) {
+            Some((_, location)) if arguments.len() == 1 => {
+                self.reach(location, None);
+                Actions::BREAK
+            },
+            _ => {
+                println!("Usage: until <location>");
+                Actions::NOOP
+            },
+        }
+    }
+
+    fn reach(&mut self, address: u16, sp: Option<u16>) {
+        self.stop = Some(Stop::Reach { address, sp, started: false });
     }
     
     pub fn print_status(&self, cpu: &CPU) {
-        println!("DEBUGGER PC: {}", cpu.pc);
+        println!("DEBUGGER PC: {}", self.describe(&cpu.memory, cpu.pc));
         println!("{:?}", cpu);
     }
 
 }
+
+
+// a byte in hex
+fn parse_byte(text: &str) -> Option<u8> {
+    parse_hex(text).filter(|&value| value <= 0xFF).map(|value| value as u8)
+}
+
+// a length, decimal unless it starts with 0x or $
+fn parse_count(text: &str) -> Option<usize> {
+    if text.starts_with("0x") || text.starts_with('$') {
+        parse_hex(text).map(|count| count as usize)
+    } else {
+        text.parse().ok()
+    }
+}
+
+// hex with or without a 0x or $ in front
+fn parse_hex(text: &str) -> Option<u16> {
+    let digits = text.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).ok()
+}
+
+// the commands in a script, without blank lines and # comments
+fn read_script(path: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
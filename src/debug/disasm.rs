@@ -0,0 +1,194 @@
+// Decodes every SM83 instruction, CB prefixed ones included, into RGBDS
+// syntax so the output can be assembled again.
+//
+// Opcodes are split the usual way, xxyyyzzz with yyy also read as ppq, which
+// lines them up with the register and condition tables below. See
+// https://gbdev.io/gb-opcodes/optables/ for the full grid.
+
+const R: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+const RP: [&str; 4] = ["bc", "de", "hl", "sp"];
+const RP2: [&str; 4] = ["bc", "de", "hl", "af"];
+const CC: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add a,", "adc a,", "sub", "sbc a,", "and", "xor", "or", "cp"];
+const ROT: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const MISC: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+// an address in an instruction, which a listing may want to name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    // where a jump, call or rst goes
+    Jump(u16),
+    // the memory an ld reads or writes
+    Memory(u16),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub length: u16,
+    pub operand: Option<Operand>,
+    // with @ where the operand goes
+    template: String,
+}
+
+impl Instruction {
+    // the instruction with its address operand as $XXXX
+    pub fn text(&self) -> String {
+        self.text_with(|_| None)
+    }
+
+    // the instruction with its address operand named by name, $XXXX where
+    // name has nothing
+    pub fn text_with<F: Fn(Operand) -> Option<String>>(&self, name: F) -> String {
+        match self.operand {
+            Some(operand) => {
+                let address = match operand {
+                    Operand::Jump(address) | Operand::Memory(address) => address,
+                };
+                let text = name(operand).unwrap_or_else(|| format!("${:04X}", address));
+                self.template.replace('@', &text)
+            },
+            None => self.template.clone(),
+        }
+    }
+
+    // execution does not carry on to the next instruction
+    pub fn ends_block(&self) -> bool {
+        match self.opcode {
+            0x18 | 0xC3 | 0xC9 | 0xD9 | 0xE9 => true,
+            _ => !is_valid(self.opcode),
+        }
+    }
+}
+
+// opcodes the cpu locks up on
+pub fn is_valid(opcode: u8) -> bool {
+    !matches!(opcode, 0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD)
+}
+
+// decode the instruction at address, reading memory through read
+pub fn decode<F: Fn(u16) -> u8>(address: u16, read: F) -> Instruction {
+    let opcode = read(address);
+    let d8 = read(address.wrapping_add(1));
+    let d16 = (read(address.wrapping_add(2)) as u16) << 8 | d8 as u16;
+    // where a jr lands
+    let relative = address.wrapping_add(2).wrapping_add(d8 as i8 as u16);
+
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 7) as usize;
+    let z = opcode & 7;
+    let p = y >> 1;
+    let q = y & 1;
+
+    let mut operand = None;
+    let mut jump = |target: u16| {
+        operand = Some(Operand::Jump(target));
+    };
+    let (length, template) = match (x, z) {
+        (0, 0) => match y {
+            0 => (1, "nop".to_string()),
+            1 => {
+                operand = Some(Operand::Memory(d16));
+                (3, "ld [@], sp".to_string())
+            },
+            2 => (2, "stop".to_string()),
+            3 => {
+                jump(relative);
+                (2, "jr @".to_string())
+            },
+            _ => {
+                jump(relative);
+                (2, format!("jr {}, @", CC[y - 4]))
+            },
+        },
+        (0, 1) if q == 0 => (3, format!("ld {}, ${:04X}", RP[p], d16)),
+        (0, 1) => (1, format!("add hl, {}", RP[p])),
+        (0, 2) => {
+            let memory = ["[bc]", "[de]", "[hl+]", "[hl-]"][p];
+            if q == 0 {
+                (1, format!("ld {}, a", memory))
+            } else {
+                (1, format!("ld a, {}", memory))
+            }
+        },
+        (0, 3) => (1, format!("{} {}", if q == 0 { "inc" } else { "dec" }, RP[p])),
+        (0, 4) => (1, format!("inc {}", R[y])),
+        (0, 5) => (1, format!("dec {}", R[y])),
+        (0, 6) => (2, format!("ld {}, ${:02X}", R[y], d8)),
+        (0, _) => (1, MISC[y].to_string()),
+        (1, 6) if y == 6 => (1, "halt".to_string()),
+        (1, _) => (1, format!("ld {}, {}", R[y], R[z as usize])),
+        (2, _) => (1, format!("{} {}", ALU[y], R[z as usize])),
+        (3, 0) => match y {
+            0..=3 => (1, format!("ret {}", CC[y])),
+            4 => (2, format!("ldh [$FF{:02X}], a", d8)),
+            5 => (2, format!("add sp, {}", d8 as i8)),
+            6 => (2, format!("ldh a, [$FF{:02X}]", d8)),
+            _ if d8 >= 0x80 => (2, format!("ld hl, sp - {}", -(d8 as i8 as i16))),
+            _ => (2, format!("ld hl, sp + {}", d8)),
+        },
+        (3, 1) if q == 0 => (1, format!("pop {}", RP2[p])),
+        (3, 1) => (1, ["ret", "reti", "jp hl", "ld sp, hl"][p].to_string()),
+        (3, 2) => match y {
+            0..=3 => {
+                jump(d16);
+                (3, format!("jp {}, @", CC[y]))
+            },
+            4 => (1, "ldh [c], a".to_string()),
+            5 => {
+                operand = Some(Operand::Memory(d16));
+                (3, "ld [@], a".to_string())
+            },
+            6 => (1, "ldh a, [c]".to_string()),
+            _ => {
+                operand = Some(Operand::Memory(d16));
+                (3, "ld a, [@]".to_string())
+            },
+        },
+        (3, 3) => match y {
+            0 => {
+                jump(d16);
+                (3, "jp @".to_string())
+            },
+            1 => (2, prefixed(d8)),
+            6 => (1, "di".to_string()),
+            7 => (1, "ei".to_string()),
+            _ => (1, format!("db ${:02X}", opcode)),
+        },
+        (3, 4) if y < 4 => {
+            jump(d16);
+            (3, format!("call {}, @", CC[y]))
+        },
+        (3, 5) if q == 0 => (1, format!("push {}", RP2[p])),
+        (3, 5) if p == 0 => {
+            jump(d16);
+            (3, "call @".to_string())
+        },
+        (3, 6) => (2, format!("{} ${:02X}", ALU[y], d8)),
+        (3, 7) => {
+            jump(y as u16 * 8);
+            (1, "rst @".to_string())
+        },
+        _ => (1, format!("db ${:02X}", opcode)),
+    };
+
+    Instruction {
+        address,
+        opcode,
+        length,
+        operand,
+        template,
+    }
+}
+
+fn prefixed(opcode: u8) -> String {
+    let y = (opcode >> 3) & 7;
+    let r = R[(opcode & 7) as usize];
+    match opcode >> 6 {
+        0 => format!("{} {}", ROT[y as usize], r),
+        1 => format!("bit {}, {}", y, r),
+        2 => format!("res {}, {}", y, r),
+        _ => format!("set {}, {}", y, r),
+    }
+}
@@ -0,0 +1,296 @@
+use cpu::gb::Gameboy;
+
+// Expressions for breakpoint conditions, for example
+//
+//     A == 0x3 && [HL] > 0x10
+//
+// Registers are A-L, AF, BC, DE, HL, SP and PC, flags ZF, NF, HF and CF, and
+// CYCLES is the m-cycles since power on. [x] reads the byte at x without
+// touching the hardware. Numbers are decimal unless written 0x10 or $10.
+// Operators, loosest first: || && == != < <= > >= | ^ & + - and unary ! -,
+// comparisons give 1 or 0 and anything but 0 is true
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Register {
+    A, B, C, D, E, F, H, L,
+    AF, BC, DE, HL, SP, PC,
+    ZF, NF, HF, CF,
+    Cycles,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Or, And,
+    Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual,
+    BitOr, BitXor, BitAnd,
+    Add, Sub,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+// longest first so == is not read as two =
+const SYMBOLS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "<", ">", "|", "^", "&", "+", "-", "!", "[", "]", "(", ")",
+];
+
+// binary operators by precedence, loosest first
+const LEVELS: [&[(&str, Op)]; 7] = [
+    &[("||", Op::Or)],
+    &[("&&", Op::And)],
+    &[("==", Op::Equal), ("!=", Op::NotEqual), ("<=", Op::LessEqual),
+      (">=", Op::GreaterEqual), ("<", Op::Less), (">", Op::Greater)],
+    &[("|", Op::BitOr)],
+    &[("^", Op::BitXor)],
+    &[("&", Op::BitAnd)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+];
+
+// a parsed expression along with the text it came from
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let expr = parser.expression(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {}", describe(token)));
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    pub fn evaluate(&self, machine: &Gameboy) -> i64 {
+        evaluate(&self.expr, machine)
+    }
+
+    pub fn is_true(&self, machine: &Gameboy) -> bool {
+        self.evaluate(machine) != 0
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap();
+        let length = if first.is_ascii_alphanumeric() || first == '$' || first == '_' {
+            let length = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '$' || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(word(&rest[..length])?);
+            length
+        } else {
+            match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                Some(symbol) => {
+                    tokens.push(Token::Symbol(symbol));
+                    symbol.len()
+                },
+                None => return Err(format!("unexpected '{}'", first)),
+            }
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn word(text: &str) -> Result<Token, String> {
+    let number = if text.starts_with("0x") || text.starts_with("0X") {
+        i64::from_str_radix(&text[2..], 16).ok()
+    } else if let Some(digits) = text.strip_prefix('$') {
+        i64::from_str_radix(digits, 16).ok()
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        return Ok(Token::Name(text.to_string()));
+    };
+    number.map(Token::Number).ok_or_else(|| format!("bad number {}", text))
+}
+
+fn describe(token: &Token) -> String {
+    match *token {
+        Token::Number(n) => format!("number {}", n),
+        Token::Name(ref name) => format!("'{}'", name),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<&'a Token, String> {
+        let token = self.peek().ok_or_else(|| "unexpected end of condition".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(found) if *found == symbol => Ok(()),
+            token => Err(format!("expected '{}', found {}", symbol, describe(token))),
+        }
+    }
+
+    // a chain of binary operators from LEVELS[level] and tighter
+    fn expression(&mut self, level: usize) -> Result<Expr, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.expression(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(symbol)) => LEVELS[level].iter().find(|&&(s, _)| s == *symbol).map(|&(_, op)| op),
+                _ => None,
+            };
+            match op {
+                Some(op) => {
+                    self.position += 1;
+                    let right = self.expression(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                },
+                None => return Ok(left),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next()? {
+            Token::Number(n) => Ok(Expr::Number(*n)),
+            Token::Name(name) => register(name).map(Expr::Register)
+                .ok_or_else(|| format!("unknown register {}", name)),
+            Token::Symbol("!") => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Symbol("-") => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Token::Symbol("[") => {
+                let address = self.expression(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(address)))
+            },
+            Token::Symbol("(") => {
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            token => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+}
+
+fn register(name: &str) -> Option<Register> {
+    let register = match name.to_uppercase().as_str() {
+        "A" => Register::A,
+        "B" => Register::B,
+        "C" => Register::C,
+        "D" => Register::D,
+        "E" => Register::E,
+        "F" => Register::F,
+        "H" => Register::H,
+        "L" => Register::L,
+        "AF" => Register::AF,
+        "BC" => Register::BC,
+        "DE" => Register::DE,
+        "HL" => Register::HL,
+        "SP" => Register::SP,
+        "PC" => Register::PC,
+        "ZF" => Register::ZF,
+        "NF" => Register::NF,
+        "HF" => Register::HF,
+        "CF" => Register::CF,
+        "CYCLES" => Register::Cycles,
+        _ => return None,
+    };
+    Some(register)
+}
+
+fn pair(high: u8, low: u8) -> i64 {
+    ((high as i64) << 8) | low as i64
+}
+
+fn read_register(register: Register, machine: &Gameboy) -> i64 {
+    let cpu = &machine.cpu;
+    match register {
+        Register::A => cpu.a as i64,
+        Register::B => cpu.b as i64,
+        Register::C => cpu.c as i64,
+        Register::D => cpu.d as i64,
+        Register::E => cpu.e as i64,
+        Register::F => cpu.flags() as i64,
+        Register::H => cpu.h as i64,
+        Register::L => cpu.l as i64,
+        Register::AF => pair(cpu.a, cpu.flags()),
+        Register::BC => pair(cpu.b, cpu.c),
+        Register::DE => pair(cpu.d, cpu.e),
+        Register::HL => pair(cpu.h, cpu.l),
+        Register::SP => cpu.sp as i64,
+        Register::PC => cpu.pc as i64,
+        Register::ZF => cpu.Z as i64,
+        Register::NF => cpu.N as i64,
+        Register::HF => cpu.H as i64,
+        Register::CF => cpu.C as i64,
+        Register::Cycles => machine.cycles as i64,
+    }
+}
+
+fn evaluate(expr: &Expr, machine: &Gameboy) -> i64 {
+    match *expr {
+        Expr::Number(n) => n,
+        Expr::Register(register) => read_register(register, machine),
+        Expr::Memory(ref address) => {
+            let address = evaluate(address, machine) as u16;
+            machine.cpu.memory.read_value_u8(address as usize) as i64
+        },
+        Expr::Not(ref expr) => (evaluate(expr, machine) == 0) as i64,
+        Expr::Negate(ref expr) => evaluate(expr, machine).wrapping_neg(),
+        Expr::Binary(op, ref left, ref right) => {
+            let left = evaluate(left, machine);
+            // && and || do not look further than they have to
+            match op {
+                Op::And if left == 0 => return 0,
+                Op::Or if left != 0 => return 1,
+                _ => (),
+            }
+            let right = evaluate(right, machine);
+            match op {
+                Op::Or | Op::And => (right != 0) as i64,
+                Op::Equal => (left == right) as i64,
+                Op::NotEqual => (left != right) as i64,
+                Op::Less => (left < right) as i64,
+                Op::LessEqual => (left <= right) as i64,
+                Op::Greater => (left > right) as i64,
+                Op::GreaterEqual => (left >= right) as i64,
+                Op::BitOr => left | right,
+                Op::BitXor => left ^ right,
+                Op::BitAnd => left & right,
+                Op::Add => left.wrapping_add(right),
+                Op::Sub => left.wrapping_sub(right),
+            }
+        },
+    }
+}
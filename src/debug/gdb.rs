@@ -0,0 +1,447 @@
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic;
+
+use cpu::cpu::CPU;
+use cpu::gb::Gameboy;
+use cpu::watch::{Watch, Watchpoint};
+use debug::debug::{Breakpoint, Debug};
+
+// A GDB remote serial protocol server, so gdb and frontends that speak the
+// protocol can drive the emulator: `target remote localhost:2345`.
+//
+// Registers are six 16 bit pairs, each sent little endian, in the order gdb's
+// z80 target starts with:
+//
+//   0 af  1 bc  2 de  3 hl  4 sp  5 pc
+//
+// and the same layout is served as target.xml. Memory is read and written
+// raw, without the side effects of going through the bus, so writes to rom
+// patch it. Breakpoints (Z0, Z1) are the debugger's, watchpoints (Z2 write,
+// Z3 read, Z4 access) are the interconnect's. A ctrl-c from the client stops
+// a continue with SIGINT and an instruction the emulator cannot run with
+// SIGILL, everything else stops with SIGTRAP. There is no binary X packet,
+// gdb falls back to M for writes.
+
+pub const REGISTERS: [&str; 6] = ["af", "bc", "de", "hl", "sp", "pc"];
+
+const TARGET_XML: &str = concat!(
+    "<?xml version=\"1.0\"?>",
+    "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">",
+    "<target version=\"1.0\"><feature name=\"gb-rs.sm83\">",
+    "<reg name=\"af\" bitsize=\"16\" type=\"int\" regnum=\"0\"/>",
+    "<reg name=\"bc\" bitsize=\"16\" type=\"int\"/>",
+    "<reg name=\"de\" bitsize=\"16\" type=\"int\"/>",
+    "<reg name=\"hl\" bitsize=\"16\" type=\"int\"/>",
+    "<reg name=\"sp\" bitsize=\"16\" type=\"data_ptr\"/>",
+    "<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>",
+    "</feature></target>");
+
+// the largest packet the client may send, in bytes
+const PACKET_SIZE: usize = 0x4000;
+
+// how many instructions a continue runs between looks for a ctrl-c
+const POLL_INSTRUCTIONS: u32 = 4096;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// what a packet leaves the session to do
+enum Next {
+    Reply(String),
+    // reply, then end the session
+    Close(String),
+    // end it without a reply
+    Kill,
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    // bytes read but not used yet
+    input: Vec<u8>,
+    // acks are sent and expected until the client asks for no ack mode
+    ack: bool,
+}
+
+// wait for one client on address and serve it until it detaches
+pub fn serve<A: ToSocketAddrs>(machine: &mut Gameboy, debugger: &mut Debug, address: A) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(stream)?.run(machine, debugger)
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> io::Result<GdbStub> {
+        stream.set_nodelay(true)?;
+        Ok(GdbStub {
+            stream,
+            input: Vec::new(),
+            ack: true,
+        })
+    }
+
+    // answer packets until the client detaches, kills or hangs up
+    pub fn run(&mut self, machine: &mut Gameboy, debugger: &mut Debug) -> io::Result<()> {
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => return Ok(()),
+            };
+            match self.handle(&packet, machine, debugger)? {
+                Next::Reply(reply) => {
+                    self.send(&reply)?;
+                    // the OK is still acked, nothing after it
+                    if packet == "QStartNoAckMode" {
+                        self.ack = false;
+                    }
+                },
+                Next::Close(reply) => return self.send(&reply),
+                Next::Kill => return Ok(()),
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &str, machine: &mut Gameboy, debugger: &mut Debug) -> io::Result<Next> {
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => stop_reply(SIGTRAP),
+            "g" => REGISTERS.iter().enumerate().map(|(n, _)| word(register(&machine.cpu, n))).collect(),
+            "G" => match parse_words(arguments) {
+                Some(ref values) if values.len() == REGISTERS.len() => {
+                    for (n, &value) in values.iter().enumerate() {
+                        set_register(&mut machine.cpu, n, value);
+                    }
+                    ok()
+                },
+                _ => error(),
+            },
+            "p" => match usize::from_str_radix(arguments, 16) {
+                Ok(n) if n < REGISTERS.len() => word(register(&machine.cpu, n)),
+                _ => error(),
+            },
+            "P" => {
+                let mut parts = arguments.splitn(2, '=');
+                let n = parts.next().and_then(|n| usize::from_str_radix(n, 16).ok());
+                let value = parts.next().and_then(parse_words);
+                match (n, value) {
+                    (Some(n), Some(ref value)) if n < REGISTERS.len() && value.len() == 1 => {
+                        set_register(&mut machine.cpu, n, value[0]);
+                        ok()
+                    },
+                    _ => error(),
+                }
+            },
+            "m" => match parse_address_length(arguments) {
+                Some((address, length)) => (0..length)
+                    .map(|i| format!("{:02x}", machine.cpu.memory.peek(address.wrapping_add(i as u16))))
+                    .collect(),
+                None => error(),
+            },
+            "M" => {
+                let mut parts = arguments.splitn(2, ':');
+                let range = parts.next().and_then(parse_address_length);
+                let data = parts.next().and_then(parse_bytes);
+                match (range, data) {
+                    (Some((address, length)), Some(ref data)) if data.len() == length => {
+                        for (i, &byte) in data.iter().enumerate() {
+                            machine.cpu.memory.poke(address.wrapping_add(i as u16), byte);
+                        }
+                        ok()
+                    },
+                    _ => error(),
+                }
+            },
+            "c" | "s" => {
+                if !arguments.is_empty() {
+                    match u16::from_str_radix(arguments, 16) {
+                        Ok(address) => machine.cpu.pc = address,
+                        Err(_) => return Ok(Next::Reply(error())),
+                    }
+                }
+                self.resume(machine, debugger, command == "s")?
+            },
+            "Z" | "z" => self.breakpoint(arguments, command == "Z", machine, debugger),
+            "H" | "T" => ok(),
+            "D" => return Ok(Next::Close(ok())),
+            "k" => return Ok(Next::Kill),
+            _ => self.query(packet),
+        };
+        Ok(Next::Reply(reply))
+    }
+
+    // the q and v packets, an empty reply for any not supported
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE)
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            match parse_address_length(range) {
+                Some((offset, length)) => {
+                    let offset = (offset as usize).min(TARGET_XML.len());
+                    let end = (offset + length).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                    format!("{}{}", more, &TARGET_XML[offset..end])
+                },
+                None => error(),
+            }
+        } else if packet == "QStartNoAckMode" {
+            ok()
+        } else {
+            match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    // Z and z: type,address,kind
+    fn breakpoint(&mut self, arguments: &str, insert: bool, machine: &mut Gameboy, debugger: &mut Debug) -> String {
+        let fields: Vec<&str> = arguments.split(',').collect();
+        let (kind, address, length) = match (fields.first(), fields.get(1), fields.get(2)) {
+            (Some(kind), Some(address), Some(length)) => {
+                match (u16::from_str_radix(address, 16), u16::from_str_radix(length, 16)) {
+                    (Ok(address), Ok(length)) => (*kind, address, length.max(1)),
+                    _ => return error(),
+                }
+            },
+            _ => return error(),
+        };
+        let watch = match kind {
+            // software and hardware breakpoints are the same thing here
+            "0" | "1" => {
+                let breakpoints = &mut debugger.breakpoints;
+                if insert {
+                    breakpoints.push(Breakpoint::new(address));
+                } else if let Some(i) = breakpoints.iter().position(|b| b.location == address) {
+                    breakpoints.remove(i);
+                }
+                return ok();
+            },
+            "2" => Watch::Write,
+            "3" => Watch::Read,
+            "4" => Watch::Access,
+            _ => return String::new(),
+        };
+        let watchpoint = Watchpoint::new(address, address.saturating_add(length - 1), watch, None);
+        let watchpoints = &mut machine.cpu.memory.watchpoints;
+        if insert {
+            watchpoints.push(watchpoint);
+        } else if let Some(i) = watchpoints.iter().position(|&w| w == watchpoint) {
+            watchpoints.remove(i);
+        }
+        ok()
+    }
+
+    // run one instruction, or until a breakpoint, watchpoint or ctrl-c, and
+    // say why it stopped. The instruction at pc always runs so continuing
+    // from a breakpoint does not stop on it again
+    fn resume(&mut self, machine: &mut Gameboy, debugger: &mut Debug, step: bool) -> io::Result<String> {
+        let mut count = 0u32;
+        loop {
+            // a panic is an opcode that is not implemented, the session goes on
+            let stepped = panic::catch_unwind(panic::AssertUnwindSafe(|| debugger.step(machine)));
+            if stepped.is_err() {
+                return Ok(stop_reply(SIGILL));
+            }
+            if let Some(hit) = machine.cpu.memory.take_watch_hit() {
+                let kind = match (hit.write, machine.cpu.memory.watchpoints.get(hit.index).map(|w| w.watch)) {
+                    (_, Some(Watch::Access)) => "awatch",
+                    (true, _) => "watch",
+                    (false, _) => "rwatch",
+                };
+                return Ok(format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.address));
+            }
+            if step || debugger.check_breakpoints(machine) {
+                return Ok(stop_reply(SIGTRAP));
+            }
+            count += 1;
+            if count.is_multiple_of(POLL_INSTRUCTIONS) && self.interrupted()? {
+                return Ok(stop_reply(SIGINT));
+            }
+        }
+    }
+
+    // true if the client sent a ctrl-c while the machine was running, it
+    // may have come in with the packet that started it
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut buffer = [0; 256];
+        let read = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+        match read {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "gdb hung up")),
+            Ok(n) => self.input.extend_from_slice(&buffer[..n]),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(e) => return Err(e),
+        }
+        match self.input.iter().position(|&byte| byte == 0x03) {
+            Some(i) => {
+                self.input.remove(i);
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.input.is_empty() {
+            let mut buffer = [0; 1024];
+            let n = self.stream.read(&mut buffer)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.input.extend_from_slice(&buffer[..n]);
+        }
+        Ok(Some(self.input.remove(0)))
+    }
+
+    // the next packet's data, None once the client hangs up. Acks and a ctrl-c
+    // while stopped are skipped, a packet with a bad checksum is asked for again
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                Some(b'$') => (),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+            // the checksum covers the bytes as sent, escapes included. A } is
+            // followed by a byte xored with 0x20
+            let mut data = Vec::new();
+            let mut sent = Vec::new();
+            let mut escaped = false;
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => {
+                        sent.push(byte);
+                        if escaped {
+                            data.push(byte ^ 0x20);
+                            escaped = false;
+                        } else if byte == b'}' {
+                            escaped = true;
+                        } else {
+                            data.push(byte);
+                        }
+                    },
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0; 2];
+            for digit in checksum.iter_mut() {
+                match self.read_byte()? {
+                    Some(byte) => *digit = byte,
+                    None => return Ok(None),
+                }
+            }
+            let expected = std::str::from_utf8(&checksum).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if self.ack {
+                let good = expected == Some(sum(&sent));
+                self.stream.write_all(if good { b"+" } else { b"-" })?;
+                if !good {
+                    continue;
+                }
+            }
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, sum(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if !self.ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn sum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn ok() -> String {
+    "OK".to_string()
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+// a register as it goes over the wire, low byte first
+fn word(value: u16) -> String {
+    format!("{:02x}{:02x}", value as u8, value >> 8)
+}
+
+// little endian words, four hex digits each
+fn parse_words(hex: &str) -> Option<Vec<u16>> {
+    let bytes = parse_bytes(hex)?;
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(bytes.chunks(2).map(|pair| (pair[1] as u16) << 8 | pair[0] as u16).collect())
+}
+
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+// address,length in hex
+fn parse_address_length(text: &str) -> Option<(u16, usize)> {
+    let mut parts = text.splitn(2, ',');
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let length = usize::from_str_radix(parts.next()?, 16).ok()?;
+    Some((address, length.min(PACKET_SIZE / 2)))
+}
+
+// register n in the layout above
+fn register(cpu: &CPU, n: usize) -> u16 {
+    let pair = |high: u8, low: u8| (high as u16) << 8 | low as u16;
+    match n {
+        0 => pair(cpu.a, cpu.flags()),
+        1 => pair(cpu.b, cpu.c),
+        2 => pair(cpu.d, cpu.e),
+        3 => pair(cpu.h, cpu.l),
+        4 => cpu.sp,
+        _ => cpu.pc,
+    }
+}
+
+fn set_register(cpu: &mut CPU, n: usize, value: u16) {
+    let (high, low) = ((value >> 8) as u8, value as u8);
+    match n {
+        0 => {
+            cpu.a = high;
+            cpu.set_flags(low);
+        },
+        1 => {
+            cpu.b = high;
+            cpu.c = low;
+        },
+        2 => {
+            cpu.d = high;
+            cpu.e = low;
+        },
+        3 => {
+            cpu.h = high;
+            cpu.l = low;
+        },
+        4 => cpu.sp = value,
+        _ => cpu.pc = value,
+    }
+}
@@ -1 +1,9 @@
+pub mod asm;
 pub mod debug;
+pub mod disasm;
+pub mod expr;
+pub mod gdb;
+pub mod registers;
+pub mod stack;
+pub mod symbols;
+pub mod vram;
@@ -0,0 +1,162 @@
+// The bit fields of the hardware registers, decoded for the io command. The
+// names follow pan docs, shortened where they get long.
+
+pub struct Field {
+    // which bits, like 7 or 6-4
+    pub bits: &'static str,
+    pub name: &'static str,
+    pub value: String,
+}
+
+const INTERRUPTS: [&str; 5] = ["vblank", "stat", "timer", "serial", "joypad"];
+
+const SHADES: [&str; 4] = ["white", "light gray", "dark gray", "black"];
+
+// the fields of the register at address holding value, empty when it is just
+// a number
+pub fn fields(address: u16, value: u8) -> Vec<Field> {
+    let mut fields = Vec::new();
+    {
+        let mut field = |bits: &'static str, name: &'static str, text: String| {
+            fields.push(Field { bits, name, value: text });
+        };
+        let on = |bit: u8| if value & 1 << bit != 0 { "on" } else { "off" }.to_string();
+        match address {
+            // P1, a 0 selects or is pressed
+            0xFF00 => {
+                let selected = |bit: u8| if value & 1 << bit == 0 { "selected" } else { "-" }.to_string();
+                let pressed = |bit: u8| if value & 1 << bit == 0 { "pressed" } else { "-" }.to_string();
+                field("5", "buttons", selected(5));
+                field("4", "d-pad", selected(4));
+                field("3", "down/start", pressed(3));
+                field("2", "up/select", pressed(2));
+                field("1", "left/b", pressed(1));
+                field("0", "right/a", pressed(0));
+            },
+            // SC
+            0xFF02 => {
+                field("7", "transfer", on(7));
+                field("0", "clock", if value & 1 != 0 { "internal" } else { "external" }.to_string());
+            },
+            // TAC
+            0xFF07 => {
+                field("2", "timer", on(2));
+                let hz = [4096, 262144, 65536, 16384][bits(value, 1, 0) as usize];
+                field("1-0", "clock", format!("{} Hz", hz));
+            },
+            // IF and IE
+            0xFF0F | 0xFFFF => {
+                for (bit, name) in INTERRUPTS.iter().enumerate() {
+                    field(["0", "1", "2", "3", "4"][bit], name, on(bit as u8));
+                }
+            },
+            // NR10
+            0xFF10 => {
+                field("6-4", "sweep pace", bits(value, 6, 4).to_string());
+                field("3", "sweep", if value & 0x08 != 0 { "down" } else { "up" }.to_string());
+                field("2-0", "sweep step", bits(value, 2, 0).to_string());
+            },
+            // NR11 and NR21
+            0xFF11 | 0xFF16 => {
+                field("7-6", "duty", ["12.5%", "25%", "50%", "75%"][bits(value, 7, 6) as usize].to_string());
+                field("5-0", "length", bits(value, 5, 0).to_string());
+            },
+            // NR12, NR22 and NR42
+            0xFF12 | 0xFF17 | 0xFF21 => {
+                field("7-4", "volume", bits(value, 7, 4).to_string());
+                field("3", "envelope", if value & 0x08 != 0 { "up" } else { "down" }.to_string());
+                field("2-0", "envelope pace", bits(value, 2, 0).to_string());
+            },
+            // NR14, NR24 and NR34
+            0xFF14 | 0xFF19 | 0xFF1E => {
+                field("7", "trigger", on(7));
+                field("6", "length enable", on(6));
+                field("2-0", "period high", bits(value, 2, 0).to_string());
+            },
+            // NR30
+            0xFF1A => field("7", "dac", on(7)),
+            // NR32
+            0xFF1C => field("6-5", "output", ["mute", "100%", "50%", "25%"][bits(value, 6, 5) as usize].to_string()),
+            // NR41
+            0xFF20 => field("5-0", "length", bits(value, 5, 0).to_string()),
+            // NR43
+            0xFF22 => {
+                field("7-4", "clock shift", bits(value, 7, 4).to_string());
+                field("3", "lfsr width", if value & 0x08 != 0 { "7 bit" } else { "15 bit" }.to_string());
+                field("2-0", "clock divider", bits(value, 2, 0).to_string());
+            },
+            // NR44
+            0xFF23 => {
+                field("7", "trigger", on(7));
+                field("6", "length enable", on(6));
+            },
+            // NR50
+            0xFF24 => {
+                field("7", "vin left", on(7));
+                field("6-4", "left volume", bits(value, 6, 4).to_string());
+                field("3", "vin right", on(3));
+                field("2-0", "right volume", bits(value, 2, 0).to_string());
+            },
+            // NR51
+            0xFF25 => {
+                field("7-4", "left", channels(value >> 4));
+                field("3-0", "right", channels(value));
+            },
+            // NR52, the channel bits are read only
+            0xFF26 => {
+                field("7", "sound", on(7));
+                field("3-0", "playing", channels(value));
+            },
+            // LCDC
+            0xFF40 => {
+                field("7", "lcd", on(7));
+                field("6", "window map", map(value & 0x40 != 0));
+                field("5", "window", on(5));
+                field("4", "tiles", if value & 0x10 != 0 { "8000-8FFF" } else { "8800-97FF" }.to_string());
+                field("3", "bg map", map(value & 0x08 != 0));
+                field("2", "object size", if value & 0x04 != 0 { "8x16" } else { "8x8" }.to_string());
+                field("1", "objects", on(1));
+                field("0", "bg", on(0));
+            },
+            // STAT
+            0xFF41 => {
+                field("6", "lyc interrupt", on(6));
+                field("5", "oam interrupt", on(5));
+                field("4", "vblank interrupt", on(4));
+                field("3", "hblank interrupt", on(3));
+                field("2", "ly == lyc", if value & 0x04 != 0 { "yes" } else { "no" }.to_string());
+                let mode = bits(value, 1, 0);
+                field("1-0", "mode", format!("{} {}", mode, ["hblank", "vblank", "oam scan", "drawing"][mode as usize]));
+            },
+            // DMA
+            0xFF46 => field("7-0", "source", format!("{:04X}", (value as u16) << 8)),
+            // BGP, OBP0 and OBP1
+            0xFF47..=0xFF49 => {
+                for color in 0..4u8 {
+                    let shade = SHADES[bits(value, color * 2 + 1, color * 2) as usize];
+                    let name = ["color 0", "color 1", "color 2", "color 3"][color as usize];
+                    field(["1-0", "3-2", "5-4", "7-6"][color as usize], name, shade.to_string());
+                }
+            },
+            // BANK
+            0xFF50 => field("0", "boot rom", if value & 1 != 0 { "unmapped" } else { "mapped" }.to_string()),
+            _ => (),
+        }
+    }
+    fields
+}
+
+// bits high down to low of value, shifted down
+fn bits(value: u8, high: u8, low: u8) -> u8 {
+    (value >> low) & (0xFF >> (7 - (high - low)))
+}
+
+fn map(high: bool) -> String {
+    if high { "9C00-9FFF" } else { "9800-9BFF" }.to_string()
+}
+
+// the channels set in the low four bits, like 1 2 4
+fn channels(value: u8) -> String {
+    let on: Vec<String> = (0..4).filter(|bit| value & 1 << bit != 0).map(|bit| (bit + 1).to_string()).collect();
+    if on.is_empty() { "none".to_string() } else { on.join(" ") }
+}
@@ -0,0 +1,205 @@
+use cpu::interconnect::Interconnect;
+use cpu::mem;
+
+// A shadow call stack, kept by watching each instruction the debugger steps
+// rather than trusting what is on the game's stack. A call, rst or interrupt
+// that moves sp down two starts a frame, and a frame ends once sp climbs back
+// past its return address or a ret lands on it. A ret that lands anywhere
+// else means the game changed its stack by hand, which is noted.
+//
+// After a save state is loaded the frames are found again by looking for
+// return addresses on the game's stack, see rebuild.
+
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+// how far up the stack rebuild looks, in words
+const REBUILD_DEPTH: u16 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Call,
+    Rst,
+    Interrupt,
+    // found on the stack after a load
+    Recovered,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub kind: Kind,
+    // where the call or rst is
+    pub call_site: u16,
+    // where it went
+    pub function: u16,
+    pub return_address: u16,
+    // the bank mapped at the return address when the call was made
+    pub bank: u16,
+    // sp with the return address pushed
+    pub sp: u16,
+}
+
+// a ret that did not go where the frame it ended expected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mismatch {
+    pub ret: u16,
+    pub expected: u16,
+    pub landed: u16,
+}
+
+pub struct CallStack {
+    // outermost first
+    frames: Vec<Frame>,
+    // the last mismatched ret since the stack was cleared
+    pub mismatch: Option<Mismatch>,
+}
+
+// the pc, sp, opcode and bank an instruction started with
+#[derive(Clone, Copy, Debug)]
+pub struct Before {
+    pub pc: u16,
+    pub sp: u16,
+    pub opcode: u8,
+    pub bank: u16,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack {
+            frames: Vec::new(),
+            mismatch: None,
+        }
+    }
+
+    // update the stack for an instruction that went from before to pc and sp
+    pub fn track(&mut self, before: Before, memory: &Interconnect, pc: u16, sp: u16) {
+        if sp == before.sp.wrapping_sub(2) {
+            let call = call_length(before.opcode).map(|length| before.pc.wrapping_add(length));
+            let frame = match call {
+                Some(return_address) if pc != return_address => {
+                    let kind = if before.opcode & 0xC7 == 0xC7 { Kind::Rst } else { Kind::Call };
+                    Some((kind, return_address))
+                },
+                // an interrupt pushes wherever the cpu was going next
+                _ if INTERRUPT_VECTORS.contains(&pc) && pc != before.pc.wrapping_add(1) => {
+                    Some((Kind::Interrupt, peek_word(memory, sp)))
+                },
+                _ => None,
+            };
+            if let Some((kind, return_address)) = frame {
+                self.frames.push(Frame {
+                    kind,
+                    call_site: before.pc,
+                    function: pc,
+                    return_address,
+                    bank: before.bank,
+                    sp,
+                });
+                return;
+            }
+        }
+
+        // a ret that was taken
+        if is_return(before.opcode) && pc != before.pc.wrapping_add(1) {
+            if let Some(frame) = self.frames.last().cloned() {
+                if frame.return_address == pc {
+                    self.frames.pop();
+                } else {
+                    self.mismatch = Some(Mismatch {
+                        ret: before.pc,
+                        expected: frame.return_address,
+                        landed: pc,
+                    });
+                    // popping before the ret returns from more than one
+                    if let Some(outer) = self.frames.iter().rposition(|frame| frame.return_address == pc) {
+                        self.frames.truncate(outer);
+                    }
+                }
+            }
+        }
+        while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+            self.frames.pop();
+        }
+    }
+
+    // find the frames again from the return addresses on the game's stack,
+    // words pointing just past a call or rst in rom
+    pub fn rebuild(&mut self, memory: &Interconnect, sp: u16) {
+        self.clear();
+        let region = mem::region(sp);
+        let mut slot = sp;
+        for _ in 0..REBUILD_DEPTH {
+            if slot >= 0xFFFE || mem::region(slot + 1) != region {
+                break;
+            }
+            let address = peek_word(memory, slot);
+            if let Some((call_site, function)) = call_before(memory, address) {
+                self.frames.push(Frame {
+                    kind: Kind::Recovered,
+                    call_site,
+                    function,
+                    return_address: address,
+                    bank: memory.bank(call_site),
+                    sp: slot,
+                });
+            }
+            slot += 2;
+        }
+        self.frames.reverse();
+    }
+
+    // innermost first
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+
+    // the frame of the function running now
+    pub fn current(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatch = None;
+    }
+}
+
+// how long a call or rst is, None for anything else
+pub fn call_length(opcode: u8) -> Option<u16> {
+    match opcode {
+        0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => Some(3),
+        opcode if opcode & 0xC7 == 0xC7 => Some(1),
+        _ => None,
+    }
+}
+
+fn is_return(opcode: u8) -> bool {
+    matches!(opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9)
+}
+
+fn peek_word(memory: &Interconnect, address: u16) -> u16 {
+    (memory.peek(address.wrapping_add(1)) as u16) << 8 | memory.peek(address) as u16
+}
+
+// the call or rst in rom that returns to address, and where it goes
+fn call_before(memory: &Interconnect, address: u16) -> Option<(u16, u16)> {
+    if address < 3 || address as usize > mem::ROM_SIZE {
+        return None;
+    }
+    let site = address - 3;
+    if call_length(memory.peek(site)) == Some(3) {
+        return Some((site, peek_word(memory, site + 1)));
+    }
+    let rst = memory.peek(address - 1);
+    if rst & 0xC7 == 0xC7 {
+        return Some((address - 1, (rst & 0x38) as u16));
+    }
+    None
+}
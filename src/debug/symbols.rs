@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use cpu::mem;
+
+// Labels from the .sym files rgblink (-n) and no$gmb write, one per line:
+//
+//     ; comments and [sections] are skipped
+//     00:0150 Main
+//     01:4000 Main.loop
+//
+// Banks are numbered the way Interconnect::bank numbers them.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub bank: u16,
+    pub address: u16,
+    pub name: String,
+}
+
+pub struct Symbols {
+    // sorted by address
+    symbols: Vec<Symbol>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols {
+            symbols: Vec::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    // lines that are not symbols are left out
+    pub fn parse(text: &str) -> Symbols {
+        let mut symbols: Vec<Symbol> = text.lines().filter_map(parse_line).collect();
+        symbols.sort_by_key(|symbol| (symbol.address, symbol.bank));
+        let by_name = symbols.iter().enumerate()
+            .map(|(i, symbol)| (symbol.name.clone(), i))
+            .collect();
+        Symbols { symbols, by_name }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Symbols> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Ok(Symbols::parse(&text))
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.by_name.get(name).map(|&i| &self.symbols[i])
+    }
+
+    // the label right at an address
+    pub fn at(&self, bank: u16, address: u16) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.address == address && symbol.bank == bank)
+    }
+
+    // the closest label at or before an address in the same bank and region,
+    // with how far past it the address is
+    pub fn nearest(&self, bank: u16, address: u16) -> Option<(&Symbol, u16)> {
+        let region = mem::region(address);
+        self.symbols.iter()
+            .take_while(|symbol| symbol.address <= address)
+            .filter(|symbol| symbol.bank == bank && mem::region(symbol.address) == region)
+            .last()
+            .map(|symbol| (symbol, address - symbol.address))
+    }
+}
+
+fn parse_line(line: &str) -> Option<Symbol> {
+    let line = line.split(';').next().unwrap_or("").trim();
+    let mut parts = line.split_whitespace();
+    let location = parts.next()?;
+    let name = parts.next()?;
+    let mut location = location.splitn(2, ':');
+    let bank = u16::from_str_radix(location.next()?, 16).ok()?;
+    let address = u16::from_str_radix(location.next()?, 16).ok()?;
+    Some(Symbol {
+        bank,
+        address,
+        name: name.to_string(),
+    })
+}
@@ -0,0 +1,89 @@
+use cpu::gpu::{tile_map_pixel, tile_pixel, palette_shade, shade_rgb, SCREEN_WIDTH, SCREEN_HEIGHT,
+               LCD_CONTROL, SCROLL_X, SCROLL_Y, WINDOW_X, WINDOW_Y, BG_PALETTE,
+               BG_MAP, WINDOW_MAP, WINDOW_ON};
+use cpu::interconnect::Interconnect;
+
+// A picture of video ram for the viewer window: the 384 tiles at 0x8000,
+// 16 to a row, then the tile maps at 0x9800 and 0x9C00 side by side, all
+// shaded through BGP. The part of the background map the screen shows is
+// outlined in red and the part of the window map on screen in blue.
+//
+// The picture is RGB24, WIDTH pixels across.
+
+pub const TILES: usize = 384;
+pub const TILES_ACROSS: usize = 16;
+pub const MAP_SIZE: usize = 256;
+pub const GAP: usize = 8;
+
+pub const TILES_X: usize = 0;
+pub const MAPS_X: [usize; 2] = [TILES_ACROSS * 8 + GAP, TILES_ACROSS * 8 + GAP * 2 + MAP_SIZE];
+pub const WIDTH: usize = MAPS_X[1] + MAP_SIZE;
+pub const HEIGHT: usize = MAP_SIZE;
+
+pub const BACKGROUND: [u8; 3] = [0x40, 0x40, 0x60];
+pub const VIEWPORT: [u8; 3] = [0xFF, 0x00, 0x00];
+pub const WINDOW: [u8; 3] = [0x00, 0x00, 0xFF];
+
+pub fn render(interconnect: &Interconnect) -> Vec<u8> {
+    let memory = interconnect.memory();
+    let control = memory[LCD_CONTROL];
+    let palette = memory[BG_PALETTE];
+    let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for _ in 0..WIDTH * HEIGHT {
+        pixels.extend_from_slice(&BACKGROUND);
+    }
+
+    for tile in 0..TILES {
+        let left = TILES_X + (tile % TILES_ACROSS) * 8;
+        let top = (tile / TILES_ACROSS) * 8;
+        for y in 0..8 {
+            for x in 0..8 {
+                let colour = tile_pixel(memory, 0x8000 + tile * 16, x, y);
+                set(&mut pixels, left + x, top + y, shade_rgb(palette_shade(palette, colour)));
+            }
+        }
+    }
+
+    for (i, &left) in MAPS_X.iter().enumerate() {
+        let map = 0x9800 + i * 0x400;
+        for y in 0..MAP_SIZE {
+            for x in 0..MAP_SIZE {
+                let colour = tile_map_pixel(memory, control, map, x, y);
+                set(&mut pixels, left + x, y, shade_rgb(palette_shade(palette, colour)));
+            }
+        }
+    }
+
+    // the screen wraps around the background map
+    let background = MAPS_X[(control & BG_MAP != 0) as usize];
+    outline(&mut pixels, background, memory[SCROLL_X] as usize, memory[SCROLL_Y] as usize,
+            SCREEN_WIDTH, SCREEN_HEIGHT, VIEWPORT);
+
+    // the window is drawn from the top left of its map, as much of it as
+    // fits between WX-7,WY and the bottom right of the screen
+    let window_x = memory[WINDOW_X] as usize;
+    let window_y = memory[WINDOW_Y] as usize;
+    if control & WINDOW_ON != 0 && window_x <= 166 && window_y < SCREEN_HEIGHT {
+        let window = MAPS_X[(control & WINDOW_MAP != 0) as usize];
+        let width = (SCREEN_WIDTH + 7 - window_x).min(SCREEN_WIDTH);
+        outline(&mut pixels, window, 0, 0, width, SCREEN_HEIGHT - window_y, WINDOW);
+    }
+    pixels
+}
+
+fn set(pixels: &mut [u8], x: usize, y: usize, rgb: [u8; 3]) {
+    let i = (y * WIDTH + x) * 3;
+    pixels[i..i + 3].copy_from_slice(&rgb);
+}
+
+// a one pixel rectangle on the map drawn at left, wrapping at its edges
+fn outline(pixels: &mut [u8], left: usize, x: usize, y: usize, width: usize, height: usize, rgb: [u8; 3]) {
+    for dx in 0..width {
+        set(pixels, left + (x + dx) % MAP_SIZE, y, rgb);
+        set(pixels, left + (x + dx) % MAP_SIZE, (y + height - 1) % MAP_SIZE, rgb);
+    }
+    for dy in 0..height {
+        set(pixels, left + x, (y + dy) % MAP_SIZE, rgb);
+        set(pixels, left + (x + width - 1) % MAP_SIZE, (y + dy) % MAP_SIZE, rgb);
+    }
+}
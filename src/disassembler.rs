@@ -0,0 +1,52 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::exit;
+
+use clap::{Arg, App, ArgMatches, SubCommand};
+
+use gb_rs::debug::asm::Analysis;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("disasm")
+        .about("Writes a rom out as RGBDS source that assembles back to the same rom")
+        .arg(Arg::with_name("ROM")
+             .help("The rom to disassemble")
+             .required(true)
+             .index(1))
+        .arg(Arg::with_name("output")
+             .short("o")
+             .long("output")
+             .value_name("FILE")
+             .help("Where to write the source, defaults to the rom with an .asm extension")
+             .takes_value(true))
+}
+
+pub fn run(matches: &ArgMatches) -> ! {
+    let path = Path::new(matches.value_of("ROM").unwrap());
+    let rom = match fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Could not read rom {}, {}", path.display(), e);
+            exit(1);
+        }
+    };
+    let output = matches.value_of("output").map(Path::new)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| path.with_extension("asm"));
+
+    let analysis = Analysis::new(&rom);
+    // exit skips destructors, so flush by hand
+    let written = File::create(&output).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        analysis.write(&rom, &mut out)?;
+        out.flush()
+    });
+    if let Err(e) = written {
+        eprintln!("Could not write {}, {}", output.display(), e);
+        exit(1);
+    }
+    println!("Wrote {} instructions and {} labels to {}",
+             analysis.instructions(), analysis.labels(), output.display());
+    exit(0)
+}
@@ -0,0 +1,120 @@
+use cpu::gb::Gameboy;
+use movie::Movie;
+
+// a minute of frames, the limit when neither frames nor cycles are given
+pub const DEFAULT_FRAMES: u64 = 3600;
+
+// When a headless run should stop. Any limit that is set ends the run, the
+// first one reached wins
+pub struct RunConfig {
+    pub frames: Option<u64>,
+    pub cycles: Option<u64>,
+    pub until_pc: Option<u16>,
+    pub until_serial: Option<String>,
+    // stop on the LD B, B software breakpoint
+    pub until_breakpoint: bool,
+    // buttons to press, frame by frame from the start of the run
+    pub movie: Option<Movie>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    // the cpu was about to execute the instruction at until_pc
+    ReachedPc,
+    // the serial output contained until_serial
+    MatchedSerial,
+    // the cpu ran LD B, B
+    Breakpoint,
+    // ran out of frames or cycles first
+    Limit,
+}
+
+pub struct Run {
+    pub outcome: Outcome,
+    pub frames: u64,
+    pub cycles: u64,
+}
+
+impl RunConfig {
+    pub fn new() -> RunConfig {
+        RunConfig {
+            frames: None,
+            cycles: None,
+            until_pc: None,
+            until_serial: None,
+            until_breakpoint: false,
+            movie: None,
+        }
+    }
+
+    // true if the run only stops because of a pc, serial or breakpoint match
+    pub fn has_condition(&self) -> bool {
+        self.until_pc.is_some() || self.until_serial.is_some() || self.until_breakpoint
+    }
+}
+
+// Run the machine without a frontend until one of the limits is hit. With no
+// frame or cycle limit it stops after DEFAULT_FRAMES, so a rom that never
+// meets its condition still ends
+pub fn run(machine: &mut Gameboy, config: &RunConfig) -> Run {
+    let mut run = Run {
+        outcome: Outcome::Limit,
+        frames: 0,
+        cycles: 0,
+    };
+    let frame_limit = match (config.frames, config.cycles) {
+        (None, None) => Some(DEFAULT_FRAMES),
+        (frames, _) => frames,
+    };
+    let mut serial_length = machine.serial_output().len();
+    let mut frame_started = false;
+
+    loop {
+        if let Some(frames) = frame_limit {
+            if run.frames >= frames {
+                return run;
+            }
+        }
+        if let Some(cycles) = config.cycles {
+            if run.cycles >= cycles {
+                return run;
+            }
+        }
+        if config.until_pc == Some(machine.cpu.pc) {
+            run.outcome = Outcome::ReachedPc;
+            return run;
+        }
+        if !frame_started {
+            if let Some(ref movie) = config.movie {
+                machine.set_buttons(movie.buttons(run.frames));
+            }
+            frame_started = true;
+        }
+
+        let cycles = machine.step() as u32;
+        run.cycles += cycles as u64;
+        if machine.take_frame() {
+            run.frames += 1;
+            frame_started = false;
+        }
+        if machine.take_breakpoint() && config.until_breakpoint {
+            run.outcome = Outcome::Breakpoint;
+            return run;
+        }
+
+        let output = machine.serial_output();
+        if output.len() != serial_length {
+            serial_length = output.len();
+            if let Some(ref pattern) = config.until_serial {
+                if contains(output, pattern.as_bytes()) {
+                    run.outcome = Outcome::MatchedSerial;
+                    return run;
+                }
+            }
+        }
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
@@ -0,0 +1,20 @@
+// every piece of hardware is built with new(), and the cpu and debug modules
+// keep their main type in a file of the same name
+#![allow(clippy::new_without_default, clippy::module_inception)]
+
+#[macro_use]
+extern crate bitflags;
+extern crate byteorder;
+extern crate png;
+
+pub mod cpu;
+pub mod debug;
+pub mod headless;
+pub mod movie;
+pub mod rewind;
+pub mod screenshot;
+pub mod state;
+pub mod trace;
+
+pub use cpu::gb::Gameboy;
+pub use cpu::joypad::Buttons;
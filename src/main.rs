@@ -1,29 +1,37 @@
-extern crate bitflags;
-extern crate byteorder;
+extern crate gb_rs;
+#[cfg(feature = "sdl")]
+extern crate rustyline;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 extern crate clap;
 
-use std::io::prelude::*;
-use std::io::{stdin, stdout};
 use std::process::exit;
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
-mod cpu;
-mod debug;
+mod disassembler;
+mod runner;
+#[cfg(feature = "sdl")]
+mod sdl;
 
-use clap::{Arg, App, SubCommand};
+use clap::{Arg, App};
 
-use cpu::cpu::CPU;
-use cpu::interconnect::Interconnect;
-use cpu::gb::Gameboy;
-use debug::debug::{Debug, Actions};
-// use mem::MMU;
+use gb_rs::cpu::gb::Gameboy;
+use gb_rs::cpu::tcp_link::{TcpLink, TcpLinkConfig, TimeoutBehaviour};
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::gdb;
+use gb_rs::movie::{Movie, Recorder, Start};
+use gb_rs::state;
 
+// what the link modes do not support: they run without the debugger, save
+// states, movies or traces
+const LINK_CONFLICTS: [&str; 5] = ["debug", "state", "record", "play", "trace"];
 
 fn main() {
 
-    let matches = App::new("Gb-rs")
+    let app = App::new("Gb-rs")
         .version("0.1")
         .about("Gameboy emu in Rust")
         .arg(Arg::with_name("rom")
@@ -36,7 +44,66 @@ fn main() {
              .short("d")
              .long("debug")
              .help("Sets the debug value, if set it will start the debugger"))
-        .get_matches();
+        .arg(Arg::with_name("link")
+             .short("l")
+             .long("link")
+             .value_name("ROM")
+             .help("Runs a second gameboy with this rom, joined to the first by a link cable")
+             .takes_value(true)
+             .conflicts_with_all(&LINK_CONFLICTS))
+        .arg(Arg::with_name("listen")
+             .long("listen")
+             .value_name("ADDRESS")
+             .help("Waits for another gb-rs to plug into the link port over TCP, e.g. 127.0.0.1:5678")
+             .takes_value(true)
+             .conflicts_with_all(&LINK_CONFLICTS))
+        .arg(Arg::with_name("connect")
+             .long("connect")
+             .value_name("ADDRESS")
+             .help("Plugs the link port into another gb-rs listening over TCP")
+             .takes_value(true)
+             .conflicts_with("listen")
+             .conflicts_with_all(&LINK_CONFLICTS))
+        .arg(Arg::with_name("link-timeout")
+             .long("link-timeout")
+             .value_name("MS")
+             .help("How long to wait for the TCP link peer to connect or answer a transfer, defaults to 1000")
+             .takes_value(true))
+        .arg(Arg::with_name("link-wait")
+             .long("link-wait")
+             .help("Stall until the TCP link peer connects or answers instead of giving up after the timeout"))
+        .arg(Arg::with_name("state")
+             .long("state")
+             .value_name("FILE")
+             .help("Starts from this save state")
+             .takes_value(true))
+        .arg(Arg::with_name("record")
+             .long("record")
+             .value_name("FILE")
+             .help("Records the joypad, frame by frame, to this movie file")
+             .takes_value(true))
+        .arg(Arg::with_name("play")
+             .long("play")
+             .value_name("FILE")
+             .help("Plays back a movie recorded on the same rom instead of reading the keyboard")
+             .takes_value(true)
+             .conflicts_with_all(&["record", "state"]))
+        .arg(Arg::with_name("gdb")
+             .long("gdb")
+             .value_name("ADDRESS")
+             .help("Runs without a window, driven by a gdb client connecting to this address, e.g. 127.0.0.1:2345")
+             .takes_value(true)
+             .conflicts_with_all(&["record", "play", "link", "listen", "connect"]))
+        .subcommand(runner::subcommand())
+        .subcommand(disassembler::subcommand());
+    let matches = runner::trace_args(app).get_matches();
+
+    if let Some(matches) = matches.subcommand_matches("headless") {
+        runner::run(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("disasm") {
+        disassembler::run(matches);
+    }
 
     let boot_path = matches.value_of("rom").unwrap_or("./roms/BOOT.bin");
 
@@ -45,86 +112,124 @@ fn main() {
         Err(e) => panic!("Could not read boot file, {}", e)
     };
 
-    //load boot rom
-    let mut debug: bool = match matches.occurrences_of("debug") {
-        0 => false,
-        _ => true,
-    };
+    if let Some(link_path) = matches.value_of("link") {
+        let link_boot = match File::open(link_path) {
+            Ok(f) => f,
+            Err(e) => panic!("Could not read linked boot file, {}", e)
+        };
+        run_linked(Gameboy::new(boot), Gameboy::new(link_boot));
+    }
 
-    let mut machine = Gameboy::new(boot);
-    // let mut cpu = CPU::new(boot);
+    if matches.is_present("listen") || matches.is_present("connect") {
+        let mut config = TcpLinkConfig::new();
+        if let Some(timeout) = matches.value_of("link-timeout") {
+            let ms = u64::from_str(timeout).expect("Link timeout must be a number of milliseconds");
+            config.timeout = Duration::from_millis(ms);
+        }
+        if matches.is_present("link-wait") {
+            config.on_timeout = TimeoutBehaviour::Wait;
+        }
 
-    let sdl_context = sdl2::init().unwrap();
-    let video = sdl_context.video().unwrap();
+        let link = if let Some(address) = matches.value_of("listen") {
+            println!("Waiting for link peer on {}", address);
+            TcpLink::listen(Gameboy::new(boot), address, config)
+        } else {
+            TcpLink::connect(Gameboy::new(boot), matches.value_of("connect").unwrap(), config)
+        };
 
-    let _window = video.window("Gb-rs", 160, 144)
-        .position_centered()
-        .build()
-        .unwrap();
+        match link {
+            Ok(link) => run_networked(link),
+            Err(e) => panic!("Could not open link, {}", e),
+        }
+    }
 
-    let mut debugger = Debug::new();
-    if debug {
-        debugger.print_status(&machine.cpu);
+    let rom = fs::read(boot_path).expect("Could not read boot file");
+    let debug = matches.occurrences_of("debug") > 0;
+
+    if let Some(movie_path) = matches.value_of("play") {
+        let movie = match Movie::load_file(movie_path) {
+            Ok(movie) => movie,
+            Err(e) => panic!("Could not read movie, {}", e)
+        };
+        let mut machine = match movie.start(&rom) {
+            Ok(machine) => machine,
+            Err(e) => panic!("Could not play movie, {}", e)
+        };
+        runner::start_trace(&matches, &mut machine);
+        run(machine, Path::new(boot_path), debug, None, Some(movie));
     }
-    // cpu.print_boot();
-    
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    loop {
-
-        // check breakpoints
-        for event in event_pump.poll_event() {
-            use sdl2::event::Event;
-            use sdl2::keyboard::Keycode;
-
-            match event {
-                Event::KeyDown { keycode, .. } => match keycode {
-                    Some(key) => {
-                        match key {
-                            Keycode::Q => exit(0),
-                            Keycode::S => debugger.step(&mut machine),
-                            Keycode::P => debugger.print_status(&mut machine.cpu),
-                            Keycode::C => debug = !debug,
-                            _ => ()
-                        }
-                    },
-                    None => (),
-                },
-                _ => (),
-            }
+
+    let mut machine = Gameboy::from_bytes(&rom);
+    if let Some(state_path) = matches.value_of("state") {
+        if let Err(e) = state::load_file(&mut machine, state_path) {
+            panic!("Could not load save state, {}", e);
         }
-        
-        
-        if !debug {
-            // check breakpoints
-            if debugger.check_breakpoints(machine.cpu.pc) {
-                debug = !debug;
-            } else {
-                machine.step();
-                machine.cpu.update_timers();
-                machine.cpu.interrupts();
-            }
+    }
+    runner::start_trace(&matches, &mut machine);
+
+    if let Some(address) = matches.value_of("gdb") {
+        serve_gdb(machine, address);
+    }
+
+    let recorder = matches.value_of("record").map(|movie_path| {
+        let start = if matches.is_present("state") {
+            Start::State(state::save(&machine))
         } else {
-            'debug: loop {
-                print!("> ");
-                let _ = stdout().flush();
-                let mut input = String::new();
-                stdin().read_line(&mut input).expect("Input invalid");
-                match debugger.parse_input(input.trim(), &machine) {
-                    Actions::BREAK => {
-                        debug = !debug;
-                        break;
-                    },
-                    Actions::EXIT => {
-                        exit(0);
-                    },
-                    Actions::STEP => {
-                        machine.step();
-                    },
-                    Actions::NOOP => (),
-                };
-            }
-        }
+            Start::PowerOn
+        };
+        let file = File::create(movie_path).expect("Could not create movie file");
+        Recorder::new(file, &rom, start).expect("Could not write movie")
+    });
+
+    run(machine, Path::new(boot_path), debug, recorder, None);
+}
+
+// let a gdb client drive the machine until it detaches
+fn serve_gdb(mut machine: Gameboy, address: &str) -> ! {
+    println!("Waiting for gdb on {}", address);
+    let mut debugger = Debug::new();
+    let result = gdb::serve(&mut machine, &mut debugger, address);
+    runner::finish_trace(&mut machine);
+    match result {
+        Ok(()) => exit(0),
+        Err(e) => panic!("gdb connection failed, {}", e),
     }
-    
 }
 
+#[cfg(feature = "sdl")]
+fn run(machine: Gameboy, rom: &Path, debug: bool,
+       recorder: Option<Recorder<File>>, playback: Option<Movie>) -> ! {
+    sdl::run(machine, rom, debug, recorder, playback)
+}
+
+#[cfg(feature = "sdl")]
+fn run_linked(left: Gameboy, right: Gameboy) -> ! {
+    sdl::run_linked(left, right)
+}
+
+#[cfg(feature = "sdl")]
+fn run_networked(link: TcpLink) -> ! {
+    sdl::run_networked(link)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run(_machine: Gameboy, _rom: &Path, _debug: bool,
+       _recorder: Option<Recorder<File>>, _playback: Option<Movie>) -> ! {
+    no_frontend()
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_linked(_left: Gameboy, _right: Gameboy) -> ! {
+    no_frontend()
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_networked(_link: TcpLink) -> ! {
+    no_frontend()
+}
+
+#[cfg(not(feature = "sdl"))]
+fn no_frontend() -> ! {
+    eprintln!("gb-rs was built without a frontend, rebuild it with `--features sdl`");
+    exit(1)
+}
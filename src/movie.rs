@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::gb::Gameboy;
+use cpu::joypad::Buttons;
+use state;
+
+// An input movie is the joypad state of every frame, enough to replay a run
+// exactly since the emulator is deterministic:
+//
+//     "GBRM"     magic
+//     u16        format version
+//     u32        crc32 of the rom it was recorded on
+//     u8         0 to start from power on, 1 to start from a save state
+//     u32, ...   length and bytes of the save state, if there is one
+//     u8 ...     the buttons held during each frame, to the end of the file
+//
+// Frame 0 starts when the movie does and each frame ends where
+// Gameboy::take_frame says it does. Since frames run to the end of the file a
+// recording can be written as it goes and stay valid if it is cut short
+
+const MAGIC: &[u8; 4] = b"GBRM";
+pub const VERSION: u16 = 1;
+
+pub enum Start {
+    PowerOn,
+    // a save state from state::save
+    State(Vec<u8>),
+}
+
+pub struct Movie {
+    pub rom_crc: u32,
+    pub start: Start,
+    pub frames: Vec<Buttons>,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], start: Start) -> Movie {
+        Movie {
+            rom_crc: crc32(rom),
+            start,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn read<R: Read>(input: &mut R) -> io::Result<Movie> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic).map_err(|_| invalid("not a movie"))?;
+        if &magic != MAGIC {
+            return Err(invalid("not a movie"));
+        }
+        let version = input.read_u16::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(invalid(&format!("movie is version {}, expected {}", version, VERSION)));
+        }
+        let rom_crc = input.read_u32::<LittleEndian>()?;
+        let start = match input.read_u8()? {
+            0 => Start::PowerOn,
+            1 => {
+                let length = input.read_u32::<LittleEndian>()?;
+                let mut data = vec![0; length as usize];
+                input.read_exact(&mut data)?;
+                Start::State(data)
+            },
+            _ => return Err(invalid("movie has an unknown start")),
+        };
+
+        let mut frames = Vec::new();
+        input.read_to_end(&mut frames)?;
+        Ok(Movie {
+            rom_crc,
+            start,
+            frames: frames.into_iter().map(Buttons::from_bits_truncate).collect(),
+        })
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_header(out)?;
+        let frames: Vec<u8> = self.frames.iter().map(|buttons| buttons.bits()).collect();
+        out.write_all(&frames)
+    }
+
+    fn write_header<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_u16::<LittleEndian>(VERSION)?;
+        out.write_u32::<LittleEndian>(self.rom_crc)?;
+        match self.start {
+            Start::PowerOn => out.write_u8(0),
+            Start::State(ref data) => {
+                out.write_u8(1)?;
+                out.write_u32::<LittleEndian>(data.len() as u32)?;
+                out.write_all(data)
+            },
+        }
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> io::Result<Movie> {
+        Movie::read(&mut File::open(path)?)
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(&mut File::create(path)?)
+    }
+
+    // the machine the movie starts on, refused if the rom is not the one it
+    // was recorded on
+    pub fn start(&self, rom: &[u8]) -> io::Result<Gameboy> {
+        let crc = crc32(rom);
+        if crc != self.rom_crc {
+            return Err(invalid(&format!("movie was recorded on rom {:08X}, this rom is {:08X}",
+                                        self.rom_crc, crc)));
+        }
+        let mut machine = Gameboy::from_bytes(rom);
+        if let Start::State(ref data) = self.start {
+            state::load(&mut machine, data)?;
+        }
+        Ok(machine)
+    }
+
+    // the buttons held during a frame, nothing once the movie is over
+    pub fn buttons(&self, frame: u64) -> Buttons {
+        self.frames.get(frame as usize).cloned().unwrap_or_else(Buttons::empty)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+// writes a movie out one frame at a time
+pub struct Recorder<W: Write> {
+    out: W,
+    pub frames: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, rom: &[u8], start: Start) -> io::Result<Recorder<W>> {
+        Movie::new(rom, start).write_header(&mut out)?;
+        Ok(Recorder {
+            out,
+            frames: 0,
+        })
+    }
+
+    pub fn frame(&mut self, buttons: Buttons) -> io::Result<()> {
+        self.frames += 1;
+        self.out.write_u8(buttons.bits())?;
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// the standard crc32, as zip and the rom databases use it
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::io;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use cpu::gb::Gameboy;
+use state;
+
+// A ring buffer of save states to step the emulator backwards through.
+//
+// A snapshot is taken every few frames. Only the newest is kept whole, every
+// older one is stored as a delta against the snapshot that came after it, so
+// going back a step is one delta away and dropping the oldest step to stay in
+// the memory budget is just dropping its delta. Deltas are run length encoded
+// on top, most of one is zero counts and runs of the same byte.
+
+// frames between snapshots
+pub const INTERVAL: u32 = 5;
+
+// bytes kept for snapshots before the oldest are dropped
+pub const BUDGET: usize = 32 * 1024 * 1024;
+
+pub struct Rewind {
+    interval: u32,
+    budget: usize,
+    countdown: u32,
+    latest: Option<Vec<u8>>,
+    // oldest first, the last one rebuilds the snapshot before latest
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl Rewind {
+    pub fn new(interval: u32, budget: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            countdown: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    // call once per frame, snapshots the machine every interval frames
+    pub fn record(&mut self, machine: &Gameboy) {
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return;
+        }
+        self.countdown = self.interval - 1;
+        self.push(state::save(machine));
+    }
+
+    fn push(&mut self, snapshot: Vec<u8>) {
+        if let Some(previous) = self.latest.take() {
+            let delta = pack(&delta(&snapshot, &previous));
+            self.used -= previous.len();
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.used += snapshot.len();
+        self.latest = Some(snapshot);
+
+        while self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.used -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    // put the machine back to the newest snapshot and forget it, so the next
+    // call goes further back. False once there is nothing left, an error if
+    // the snapshot does not load, which leaves the machine and the snapshots
+    // as they were
+    pub fn rewind(&mut self, machine: &mut Gameboy) -> io::Result<bool> {
+        let snapshot = match self.latest.take() {
+            Some(snapshot) => snapshot,
+            None => return Ok(false),
+        };
+        if let Err(e) = state::load(machine, &snapshot) {
+            self.latest = Some(snapshot);
+            return Err(e);
+        }
+
+        self.used -= snapshot.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            let previous = apply(&snapshot, &unpack(&delta));
+            self.used -= delta.len();
+            self.used += previous.len();
+            self.latest = Some(previous);
+        }
+        // play forward a full interval before the next snapshot, the frame
+        // rewound to was the first of it
+        self.countdown = self.interval - 1;
+        Ok(true)
+    }
+
+    // snapshots that can still be rewound to
+    pub fn len(&self) -> usize {
+        self.latest.iter().count() + self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    // bytes used by the snapshots
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    // frames between snapshots
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.used = 0;
+        self.countdown = 0;
+    }
+}
+
+// what it takes to turn base into target: the target length, then runs of
+// (unchanged count, changed count, changed bytes), counts as u32
+fn delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; 4];
+    LittleEndian::write_u32(&mut out, target.len() as u32);
+
+    let same = |i: usize| i < base.len() && base[i] == target[i];
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        while i < target.len() && same(i) {
+            i += 1;
+        }
+        let unchanged = i - start;
+        let changed_start = i;
+        while i < target.len() && !same(i) {
+            i += 1;
+        }
+        let mut counts = [0; 8];
+        LittleEndian::write_u32(&mut counts[..4], unchanged as u32);
+        LittleEndian::write_u32(&mut counts[4..], (i - changed_start) as u32);
+        out.extend_from_slice(&counts);
+        out.extend_from_slice(&target[changed_start..i]);
+    }
+    out
+}
+
+fn apply(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let length = LittleEndian::read_u32(delta) as usize;
+    let mut out = Vec::with_capacity(length);
+    let mut rest = &delta[4..];
+    while !rest.is_empty() {
+        let unchanged = LittleEndian::read_u32(&rest[..4]) as usize;
+        let changed = LittleEndian::read_u32(&rest[4..8]) as usize;
+        let at = out.len();
+        out.extend_from_slice(&base[at..at + unchanged]);
+        out.extend_from_slice(&rest[8..8 + changed]);
+        rest = &rest[8 + changed..];
+    }
+    out
+}
+
+// packbits: a header byte of 0-127 is followed by that many plus one bytes to
+// copy, 129-255 by one byte to repeat 257 minus the header times
+fn pack(data: &[u8]) -> Vec<u8> {
+    let repeats = |i: usize| {
+        let mut end = i + 1;
+        while end < data.len() && end - i < 128 && data[end] == data[i] {
+            end += 1;
+        }
+        end - i
+    };
+
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = repeats(i);
+        if run >= 3 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < data.len() && i - start < 128 && repeats(i) < 3 {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out
+}
+
+fn unpack(packed: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < packed.len() {
+        let header = packed[i] as usize;
+        if header < 128 {
+            out.extend_from_slice(&packed[i + 1..i + 2 + header]);
+            i += 2 + header;
+        } else {
+            for _ in 0..257 - header {
+                out.push(packed[i + 1]);
+            }
+            i += 2;
+        }
+    }
+    out
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::panic;
+use std::process::exit;
+use std::str::FromStr;
+
+use clap::{Arg, App, ArgMatches, SubCommand};
+
+use gb_rs::cpu::gb::Gameboy;
+use gb_rs::headless;
+use gb_rs::headless::{RunConfig, Outcome};
+use gb_rs::movie::Movie;
+use gb_rs::screenshot;
+use gb_rs::trace::Trace;
+
+// exit codes of the headless runner
+const EXIT_OK: i32 = 0;
+const EXIT_LIMIT: i32 = 1;
+const EXIT_CRASH: i32 = 2;
+const EXIT_ERROR: i32 = 3;
+
+pub fn subcommand() -> App<'static, 'static> {
+    trace_args(SubCommand::with_name("headless"))
+        .about("Runs a rom without a window and reports how it ended")
+        .arg(Arg::with_name("ROM")
+             .help("The rom to run")
+             .required(true)
+             .index(1))
+        .arg(Arg::with_name("skip-boot")
+             .long("skip-boot")
+             .help("Starts the cartridge at 0x100 as if the boot rom had already run"))
+        .arg(Arg::with_name("frames")
+             .long("frames")
+             .value_name("N")
+             .help("Stops after this many frames, 3600 if neither this nor --cycles is given")
+             .takes_value(true))
+        .arg(Arg::with_name("cycles")
+             .long("cycles")
+             .value_name("N")
+             .help("Stops after this many m-cycles")
+             .takes_value(true))
+        .arg(Arg::with_name("until-pc")
+             .long("until-pc")
+             .value_name("ADDRESS")
+             .help("Stops when the cpu reaches this address, in hex")
+             .takes_value(true))
+        .arg(Arg::with_name("until-serial")
+             .long("until-serial")
+             .value_name("TEXT")
+             .help("Stops once the serial output contains this text")
+             .takes_value(true))
+        .arg(Arg::with_name("until-breakpoint")
+             .long("until-breakpoint")
+             .help("Stops when the cpu runs LD B, B, the breakpoint mooneye's test roms use"))
+        .arg(Arg::with_name("movie")
+             .long("movie")
+             .value_name("FILE")
+             .help("Plays back an input movie recorded on the same rom")
+             .takes_value(true)
+             .conflicts_with("skip-boot"))
+        .arg(Arg::with_name("screenshot")
+             .long("screenshot")
+             .value_name("FILE")
+             .help("Saves the screen to this png when the run stops")
+             .takes_value(true))
+        .after_help("Exits with 0 when the run stops on one of the --until options, or on \
+                     the frame or cycle limit if no condition was given. Exits with 1 if a \
+                     limit was hit before the condition, 2 if the emulator crashed and 3 if \
+                     the rom, movie, screenshot or trace could not be read or written, or \
+                     the movie was recorded on another rom.")
+}
+
+pub fn run(matches: &ArgMatches) -> ! {
+    let path = matches.value_of("ROM").unwrap();
+    let rom = match fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Could not read rom {}, {}", path, e);
+            exit(EXIT_ERROR);
+        }
+    };
+
+    let mut config = RunConfig::new();
+    let mut machine = match matches.value_of("movie") {
+        Some(movie_path) => {
+            let movie = Movie::load_file(movie_path).and_then(|movie| {
+                movie.start(&rom).map(|machine| (movie, machine))
+            });
+            match movie {
+                Ok((movie, machine)) => {
+                    config.movie = Some(movie);
+                    machine
+                },
+                Err(e) => {
+                    eprintln!("Could not play movie {}, {}", movie_path, e);
+                    exit(EXIT_ERROR);
+                }
+            }
+        },
+        None => Gameboy::from_bytes(&rom),
+    };
+
+    if matches.is_present("skip-boot") {
+        machine.skip_boot();
+    }
+    start_trace(matches, &mut machine);
+
+    config.frames = matches.value_of("frames").map(|n| parse_number(n, "frames"));
+    config.cycles = matches.value_of("cycles").map(|n| parse_number(n, "cycles"));
+    config.until_pc = matches.value_of("until-pc").map(|address| parse_address(address, "until-pc"));
+    config.until_serial = matches.value_of("until-serial").map(String::from);
+    config.until_breakpoint = matches.is_present("until-breakpoint");
+
+    // the machine outlives a panic so its state can still be dumped
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| headless::run(&mut machine, &config)));
+
+    let code = match result {
+        Ok(run) => {
+            println!("Stopped: {:?} after {} frames, {} cycles", run.outcome, run.frames, run.cycles);
+            match run.outcome {
+                Outcome::Limit if config.has_condition() => EXIT_LIMIT,
+                _ => EXIT_OK,
+            }
+        },
+        Err(_) => {
+            println!("Stopped: crashed");
+            EXIT_CRASH
+        },
+    };
+    finish_trace(&mut machine);
+    println!("Registers:\n{:?}", machine.cpu);
+    println!("Serial output:\n{}", String::from_utf8_lossy(machine.serial_output()));
+
+    if let Some(path) = matches.value_of("screenshot") {
+        if let Err(e) = screenshot::save_png(path, machine.framebuffer()) {
+            eprintln!("Could not write screenshot {}, {}", path, e);
+            exit(EXIT_ERROR);
+        }
+    }
+
+    exit(code)
+}
+
+fn parse_number(value: &str, name: &str) -> u64 {
+    match u64::from_str(value) {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("--{} must be a number, got {}", name, value);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+fn parse_address(value: &str, name: &str) -> u16 {
+    let digits = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    match u16::from_str_radix(digits, 16) {
+        Ok(address) => address,
+        Err(_) => {
+            eprintln!("--{} must be a hex address, got {}", name, value);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+// the --trace options, which the window takes as well
+pub fn trace_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(Arg::with_name("trace")
+             .long("trace")
+             .value_name("FILE")
+             .help("Logs every instruction to this file in the format gameboy-doctor reads")
+             .takes_value(true))
+        .arg(Arg::with_name("trace-pc")
+             .long("trace-pc")
+             .value_name("START-END")
+             .help("Only logs instructions in this range of addresses, in hex")
+             .takes_value(true)
+             .requires("trace"))
+        .arg(Arg::with_name("trace-bank")
+             .long("trace-bank")
+             .value_name("BANK")
+             .help("Only logs instructions in this rom or ram bank, in hex")
+             .takes_value(true)
+             .requires("trace"))
+        .arg(Arg::with_name("trace-limit")
+             .long("trace-limit")
+             .value_name("MB")
+             .help("Stops logging once the trace is this many megabytes")
+             .takes_value(true)
+             .requires("trace"))
+}
+
+// open the trace the options ask for, if any
+pub fn start_trace(matches: &ArgMatches, machine: &mut Gameboy) {
+    let path = match matches.value_of("trace") {
+        Some(path) => path,
+        None => return,
+    };
+    let mut trace = match Trace::create(path) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("Could not create trace {}, {}", path, e);
+            exit(EXIT_ERROR);
+        }
+    };
+    trace.range = matches.value_of("trace-pc").map(|range| {
+        let mut ends = range.splitn(2, '-');
+        let start = parse_address(ends.next().unwrap(), "trace-pc");
+        let end = ends.next().map_or(start, |end| parse_address(end, "trace-pc"));
+        (start, end)
+    });
+    trace.bank = matches.value_of("trace-bank").map(|bank| parse_address(bank, "trace-bank"));
+    trace.limit = matches.value_of("trace-limit").map(|mb| parse_number(mb, "trace-limit") << 20);
+    machine.trace = Some(trace);
+}
+
+// write out what is left of the trace before exiting
+pub fn finish_trace(machine: &mut Gameboy) {
+    if let Some(mut trace) = machine.trace.take() {
+        // a write that failed while running, or the last one now
+        let error = match trace.error.take() {
+            Some(e) => Some(e),
+            None => trace.flush().err(),
+        };
+        if let Some(e) = error {
+            eprintln!("Could not write trace, {}", e);
+            exit(EXIT_ERROR);
+        }
+        let full = if trace.full { ", stopped at the size limit" } else { "" };
+        println!("Traced {} instructions{}", trace.lines, full);
+    }
+}
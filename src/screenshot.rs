@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use png;
+
+use cpu::gpu::{SCREEN_WIDTH, SCREEN_HEIGHT, SHADES};
+
+// mismatched pixels are drawn in this colour on a diff image
+const DIFF_COLOUR: [u8; 3] = [0xFF, 0x00, 0x00];
+
+// the grey level of every pixel in a framebuffer
+pub fn grey_levels(framebuffer: &[u8]) -> Vec<u8> {
+    framebuffer.iter().map(|&shade| SHADES[shade as usize & 0b11]).collect()
+}
+
+// write a framebuffer out as an 8 bit greyscale png
+pub fn save_png<P: AsRef<Path>>(path: P, framebuffer: &[u8]) -> io::Result<()> {
+    write_png(path, png::ColorType::Grayscale, &grey_levels(framebuffer))
+}
+
+// read a screen sized png back as one grey level per pixel. Colour images are
+// reduced to their luma so references from other emulators compare too
+pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let file = File::open(path)?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(to_io)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(to_io)?;
+
+    if info.width as usize != SCREEN_WIDTH || info.height as usize != SCREEN_HEIGHT {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  format!("expected a {}x{} image, got {}x{}",
+                                          SCREEN_WIDTH, SCREEN_HEIGHT, info.width, info.height)));
+    }
+
+    let channels = info.color_type.samples();
+    let mut levels = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT);
+    for row in buffer.chunks(info.line_size).take(SCREEN_HEIGHT) {
+        for pixel in row.chunks(channels).take(SCREEN_WIDTH) {
+            levels.push(match info.color_type {
+                png::ColorType::Rgb | png::ColorType::Rgba => luma(pixel[0], pixel[1], pixel[2]),
+                _ => pixel[0],
+            });
+        }
+    }
+    Ok(levels)
+}
+
+// the number of pixels whose grey levels are further apart than tolerance
+pub fn count_differences(actual: &[u8], expected: &[u8], tolerance: u8) -> usize {
+    actual.iter().zip(expected)
+        .filter(|&(&a, &b)| a.abs_diff(b) > tolerance)
+        .count()
+}
+
+// write the actual image faded, with the pixels that are off by more than
+// tolerance highlighted
+pub fn save_diff_png<P: AsRef<Path>>(path: P, actual: &[u8], expected: &[u8], tolerance: u8) -> io::Result<()> {
+    let mut pixels = Vec::with_capacity(actual.len() * 3);
+    for (&a, &b) in actual.iter().zip(expected) {
+        if a.abs_diff(b) > tolerance {
+            pixels.extend_from_slice(&DIFF_COLOUR);
+        } else {
+            let faded = 0x80 + a / 2;
+            pixels.extend_from_slice(&[faded, faded, faded]);
+        }
+    }
+    write_png(path, png::ColorType::Rgb, &pixels)
+}
+
+fn write_png<P: AsRef<Path>>(path: P, colour: png::ColorType, pixels: &[u8]) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    encoder.set_color(colour);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(to_io)?;
+    writer.write_image_data(pixels).map_err(to_io)
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+fn to_io<E: ToString>(error: E) -> io::Error {
+    io::Error::other(error.to_string())
+}
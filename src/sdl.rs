@@ -0,0 +1,400 @@
+use std::fs::File;
+use std::path::Path;
+use std::process::exit;
+use std::thread;
+use std::time::Duration;
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use sdl2;
+use sdl2::EventPump;
+use sdl2::event::WindowEvent;
+use sdl2::VideoSubsystem;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use gb_rs::Buttons;
+use gb_rs::cpu::gb::Gameboy;
+use gb_rs::cpu::gpu::{self, SCREEN_WIDTH, SCREEN_HEIGHT};
+use gb_rs::cpu::link::LinkCable;
+use gb_rs::cpu::tcp_link::TcpLink;
+use gb_rs::debug::debug::{Debug, Actions};
+use gb_rs::debug::vram;
+use gb_rs::movie::{Movie, Recorder};
+use gb_rs::rewind::{self, Rewind};
+use gb_rs::state;
+
+use runner;
+
+type KeyMap = [(Scancode, Buttons); 8];
+
+const KEYS: KeyMap = [
+    (Scancode::Right, Buttons::RIGHT),
+    (Scancode::Left, Buttons::LEFT),
+    (Scancode::Up, Buttons::UP),
+    (Scancode::Down, Buttons::DOWN),
+    (Scancode::Z, Buttons::A),
+    (Scancode::X, Buttons::B),
+    (Scancode::Backspace, Buttons::SELECT),
+    (Scancode::Return, Buttons::START),
+];
+
+// player 2 on a link cable, on the other side of the keyboard
+const PLAYER_2_KEYS: KeyMap = [
+    (Scancode::D, Buttons::RIGHT),
+    (Scancode::A, Buttons::LEFT),
+    (Scancode::W, Buttons::UP),
+    (Scancode::S, Buttons::DOWN),
+    (Scancode::G, Buttons::A),
+    (Scancode::F, Buttons::B),
+    (Scancode::Tab, Buttons::SELECT),
+    (Scancode::Space, Buttons::START),
+];
+
+// F1-F10 load save state slots 1-10, with shift held they save
+const SLOT_KEYS: [Keycode; 10] = [
+    Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5,
+    Keycode::F6, Keycode::F7, Keycode::F8, Keycode::F9, Keycode::F10,
+];
+
+// held to rewind
+const REWIND_KEY: Scancode = Scancode::R;
+
+// 70224 clocks at 4.194304MHz
+const FRAME_MICROS: u64 = 16743;
+
+// a window showing one gameboy lcd, or any other picture at twice its size
+struct Screen {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    pixels: Vec<u8>,
+    width: usize,
+    id: u32,
+}
+
+impl Screen {
+    fn new(video: &VideoSubsystem, title: &str, position: Option<(i32, i32)>) -> Screen {
+        Screen::sized(video, title, position, SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+
+    fn sized(video: &VideoSubsystem, title: &str, position: Option<(i32, i32)>,
+             width: usize, height: usize) -> Screen {
+        let mut builder = video.window(title, width as u32 * 2, height as u32 * 2);
+        match position {
+            Some((x, y)) => builder.position(x, y),
+            None => builder.position_centered(),
+        };
+        let window = builder.build().unwrap();
+        let id = window.id();
+        let canvas = window.into_canvas().build().unwrap();
+        let texture = canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .unwrap();
+        Screen {
+            canvas,
+            texture,
+            pixels: vec![0; width * height * 3],
+            width,
+            id,
+        }
+    }
+
+    fn draw(&mut self, framebuffer: &[u8]) {
+        for (i, &shade) in framebuffer.iter().enumerate() {
+            self.pixels[i * 3..i * 3 + 3].copy_from_slice(&gpu::shade_rgb(shade));
+        }
+        self.present();
+    }
+
+    fn draw_rgb(&mut self, pixels: &[u8]) {
+        self.pixels.copy_from_slice(pixels);
+        self.present();
+    }
+
+    fn present(&mut self) {
+        self.texture.update(None, &self.pixels, self.width * 3).unwrap();
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, None).unwrap();
+        self.canvas.present();
+    }
+}
+
+// open or close the vram viewer to match the debugger, and draw it
+fn update_viewer(viewer: &mut Option<Screen>, video: &VideoSubsystem, debugger: &Debug, machine: &Gameboy) {
+    if !debugger.show_vram {
+        *viewer = None;
+        return;
+    }
+    let screen = viewer.get_or_insert_with(|| {
+        Screen::sized(video, "Gb-rs - VRAM", None, vram::WIDTH, vram::HEIGHT)
+    });
+    screen.draw_rgb(&vram::render(&machine.cpu.memory));
+}
+
+fn held_buttons(event_pump: &EventPump, keys: &KeyMap) -> Buttons {
+    let keyboard = event_pump.keyboard_state();
+    let mut buttons = Buttons::empty();
+    for &(scancode, button) in keys.iter() {
+        if keyboard.is_scancode_pressed(scancode) {
+            buttons |= button;
+        }
+    }
+    buttons
+}
+
+// set the buttons for the frame about to start, from the movie being played
+// or the keyboard
+fn start_frame(machine: &mut Gameboy, event_pump: &EventPump, frame: u64,
+               recorder: &mut Option<Recorder<File>>, playback: &Option<Movie>) {
+    let buttons = match *playback {
+        Some(ref movie) => {
+            if frame == movie.len() as u64 {
+                println!("Movie finished after {} frames", frame);
+            }
+            movie.buttons(frame)
+        },
+        None => held_buttons(event_pump, &KEYS),
+    };
+    if let Some(ref mut recorder) = *recorder {
+        recorder.frame(buttons).expect("Could not write movie");
+    }
+    machine.set_buttons(buttons);
+}
+
+fn state_slot(machine: &mut Gameboy, rom: &Path, slot: u8, save: bool) {
+    let path = state::slot_path(rom, slot);
+    let result = if save {
+        state::save_file(machine, &path)
+    } else {
+        state::load_file(machine, &path)
+    };
+    match result {
+        Ok(()) if save => println!("Saved slot {}", slot),
+        Ok(()) => println!("Loaded slot {}", slot),
+        Err(e) => println!("Could not use slot {} ({}), {}", slot, path.display(), e),
+    }
+}
+
+// write out the trace before leaving
+fn quit(machine: &mut Gameboy) -> ! {
+    runner::finish_trace(machine);
+    exit(0)
+}
+
+pub fn run(mut machine: Gameboy, rom: &Path, mut debug: bool,
+           mut recorder: Option<Recorder<File>>, playback: Option<Movie>) -> ! {
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+
+    let mut screen = Screen::new(&video, "Gb-rs", None);
+    let mut viewer: Option<Screen> = None;
+
+    let mut rewind = Rewind::new(rewind::INTERVAL, rewind::BUDGET);
+    let mut debugger = Debug::new();
+    debugger.set_rom(rom);
+    // the init files run before the first instruction, and may continue
+    match debugger.run_script(&mut machine) {
+        Actions::BREAK => debug = false,
+        Actions::EXIT => quit(&mut machine),
+        _ => (),
+    }
+    if debug {
+        debugger.print_status(&machine.cpu);
+    }
+    let mut editor = DefaultEditor::new().expect("Could not open the debugger prompt");
+
+    // jumping around in time would break the movie
+    let movie_active = recorder.is_some() || playback.is_some();
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut frame = 0;
+    start_frame(&mut machine, &event_pump, frame, &mut recorder, &playback);
+    loop {
+
+        // check breakpoints
+        while let Some(event) = event_pump.poll_event() {
+            use sdl2::event::Event;
+
+            match event {
+                Event::Quit { .. } => quit(&mut machine),
+                // closing the viewer only hides it, closing the lcd quits
+                Event::Window { window_id, win_event: WindowEvent::Close, .. } => {
+                    if viewer.as_ref().is_some_and(|viewer| viewer.id == window_id) {
+                        debugger.show_vram = false;
+                        viewer = None;
+                    } else {
+                        quit(&mut machine);
+                    }
+                },
+                Event::KeyDown { keycode: Some(key), keymod, .. } => match key {
+                    Keycode::Q => quit(&mut machine),
+                    Keycode::S => {
+                        debugger.step_and_watch(&mut machine);
+                    },
+                    Keycode::P => debugger.print_status(&machine.cpu),
+                    Keycode::C => debug = !debug,
+                    Keycode::V => {
+                        debugger.show_vram = !debugger.show_vram;
+                        update_viewer(&mut viewer, &video, &debugger, &machine);
+                    },
+                    _ => {
+                        if let Some(slot) = SLOT_KEYS.iter().position(|&k| k == key) {
+                            let save = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                            if save || !movie_active {
+                                state_slot(&mut machine, rom, slot as u8 + 1, save);
+                                if !save {
+                                    debugger.rebuild_stack(&machine);
+                                }
+                            } else {
+                                println!("Can't load a state while a movie is recording or playing");
+                            }
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        if !debug {
+            // step back a snapshot at a time, as fast as they were taken
+            if !movie_active && event_pump.keyboard_state().is_scancode_pressed(REWIND_KEY) {
+                match rewind.rewind(&mut machine) {
+                    Ok(true) => {
+                        debugger.rebuild_stack(&machine);
+                        screen.draw(machine.framebuffer());
+                        update_viewer(&mut viewer, &video, &debugger, &machine);
+                    },
+                    Ok(false) => (),
+                    Err(e) => {
+                        println!("Could not rewind, {}", e);
+                        rewind.clear();
+                    },
+                }
+                thread::sleep(Duration::from_micros(FRAME_MICROS * rewind.interval() as u64));
+                continue;
+            }
+
+            // check breakpoints
+            if debugger.check_breakpoints(&machine) {
+                debug = !debug;
+            } else {
+                if debugger.step_and_watch(&mut machine) {
+                    debug = true;
+                }
+                machine.cpu.update_timers();
+            }
+        } else {
+            loop {
+                // what is left of a script runs before anything is read
+                let action = match debugger.run_script(&mut machine) {
+                    Actions::NOOP => match editor.readline("> ") {
+                        Ok(line) => {
+                            if !line.trim().is_empty() {
+                                let _ = editor.add_history_entry(line.as_str());
+                            }
+                            debugger.parse_typed(&line, &mut machine)
+                        },
+                        Err(ReadlineError::Interrupted) => Actions::NOOP,
+                        // ctrl-d or stdin closed
+                        Err(_) => Actions::EXIT,
+                    },
+                    action => action,
+                };
+                match action {
+                    Actions::BREAK => {
+                        debug = !debug;
+                        break;
+                    },
+                    Actions::EXIT => quit(&mut machine),
+                    Actions::STEP => {
+                        debugger.step_and_watch(&mut machine);
+                    },
+                    Actions::NOOP => (),
+                };
+                update_viewer(&mut viewer, &video, &debugger, &machine);
+            }
+        }
+
+        // the debugger may have stepped through several frames
+        let mut finished = false;
+        while machine.take_frame() {
+            frame += 1;
+            start_frame(&mut machine, &event_pump, frame, &mut recorder, &playback);
+            rewind.record(&machine);
+            finished = true;
+        }
+        if finished {
+            screen.draw(machine.framebuffer());
+            update_viewer(&mut viewer, &video, &debugger, &machine);
+        }
+    }
+}
+
+pub fn run_linked(left: Gameboy, right: Gameboy) -> ! {
+    let mut cable = LinkCable::new(left, right);
+
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+
+    // open the two screens side by side
+    let mut left_screen = Screen::new(&video, "Gb-rs - Player 1", Some((100, 100)));
+    let mut right_screen = Screen::new(&video, "Gb-rs - Player 2",
+                                       Some((100 + SCREEN_WIDTH as i32 * 2 + 20, 100)));
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    loop {
+        while let Some(event) = event_pump.poll_event() {
+            use sdl2::event::Event;
+
+            match event {
+                Event::Quit { .. } => exit(0),
+                Event::KeyDown { keycode: Some(Keycode::Q), .. } => exit(0),
+                _ => (),
+            }
+        }
+
+        cable.step();
+
+        // each player has their own keys
+        if cable.left.take_frame() {
+            cable.left.set_buttons(held_buttons(&event_pump, &KEYS));
+            left_screen.draw(cable.left.framebuffer());
+        }
+        if cable.right.take_frame() {
+            cable.right.set_buttons(held_buttons(&event_pump, &PLAYER_2_KEYS));
+            right_screen.draw(cable.right.framebuffer());
+        }
+    }
+}
+
+pub fn run_networked(mut link: TcpLink) -> ! {
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+
+    let mut screen = Screen::new(&video, "Gb-rs - Link", None);
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    loop {
+        while let Some(event) = event_pump.poll_event() {
+            use sdl2::event::Event;
+
+            match event {
+                Event::Quit { .. } => exit(0),
+                Event::KeyDown { keycode: Some(Keycode::Q), .. } => exit(0),
+                _ => (),
+            }
+        }
+
+        link.step();
+        if let Some(e) = link.take_error() {
+            println!("Link peer disconnected, {}", e);
+        }
+
+        if link.gameboy.take_frame() {
+            link.gameboy.set_buttons(held_buttons(&event_pump, &KEYS));
+            screen.draw(link.gameboy.framebuffer());
+        }
+    }
+}
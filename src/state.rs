@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use cpu::gb::Gameboy;
+
+// A save state is a small header followed by the whole machine:
+//
+//     "GBRS"     magic
+//     u16        format version
+//     u32        crc32 of the rom it was saved on
+//     u64        m-cycles since power on
+//     u32        m-cycles into the current frame
+//     cpu        registers, ime and clocks
+//     memory     0x8000-0xFFFF, cartridge ram included. The rom is not saved,
+//                states only load on the rom they were saved on
+//     hardware   serial, joypad, ppu, apu and oam dma internals
+//
+// Everything is little endian. Bump VERSION whenever the layout changes,
+// states from other versions are refused rather than loaded wrong
+
+const MAGIC: &[u8; 4] = b"GBRS";
+pub const VERSION: u16 = 4;
+const HEADER_SIZE: usize = 10;
+
+// numbered slots, bound to F1-F10 in the sdl frontend
+pub const SLOTS: u8 = 10;
+
+pub fn save(machine: &Gameboy) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.write_u16::<LittleEndian>(VERSION).unwrap();
+    out.write_u32::<LittleEndian>(machine.rom_crc).unwrap();
+    machine.save_state(&mut out).unwrap();
+    out
+}
+
+// restore a state saved by save() into the machine, so whatever is not part
+// of the state, such as the trace, the watchpoints and whether a link cable is
+// plugged in, stays as it is. A bad state leaves the machine as it was
+pub fn load(machine: &mut Gameboy, data: &[u8]) -> io::Result<()> {
+    let mut input = data;
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic).map_err(|_| invalid("not a save state"))?;
+    if &magic != MAGIC {
+        return Err(invalid("not a save state"));
+    }
+    let version = input.read_u16::<LittleEndian>()?;
+    if version != VERSION {
+        return Err(invalid(&format!("save state is version {}, expected {}", version, VERSION)));
+    }
+    let crc = input.read_u32::<LittleEndian>().map_err(|_| invalid("save state is truncated"))?;
+    if crc != machine.rom_crc {
+        return Err(invalid(&format!("save state is for the rom with crc32 {:08X}, this one is {:08X}",
+                                    crc, machine.rom_crc)));
+    }
+
+    let before = save(machine);
+    let result = match machine.load_state(&mut input) {
+        Err(_) => Err(invalid("save state is truncated")),
+        Ok(()) if !input.is_empty() => Err(invalid("save state has trailing data")),
+        Ok(()) => Ok(()),
+    };
+    if result.is_err() {
+        machine.load_state(&mut &before[HEADER_SIZE..]).expect("Could not put the machine back");
+    }
+    result
+}
+
+pub fn save_file<P: AsRef<Path>>(machine: &Gameboy, path: P) -> io::Result<()> {
+    File::create(path)?.write_all(&save(machine))
+}
+
+pub fn load_file<P: AsRef<Path>>(machine: &mut Gameboy, path: P) -> io::Result<()> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    load(machine, &data)
+}
+
+// slots live next to the rom, game.gb saves slot 3 to game.ss3
+pub fn slot_path(rom: &Path, slot: u8) -> PathBuf {
+    rom.with_extension(format!("ss{}", slot))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
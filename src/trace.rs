@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use cpu::cpu::CPU;
+
+// An execution trace, a line for every instruction before it runs in the
+// format gameboy-doctor and most other emulators' trace logs use:
+//
+//   A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//
+// so two traces of the same rom can be diffed line by line. Lines can be
+// kept to a range of pc and a bank, and the trace stops once it has written
+// limit bytes.
+
+pub struct Trace {
+    out: Box<dyn Write + Send>,
+    // only instructions with pc in start..=end
+    pub range: Option<(u16, u16)>,
+    // only instructions in this bank, see Interconnect::bank
+    pub bank: Option<u16>,
+    // stop after this many bytes
+    pub limit: Option<u64>,
+    pub lines: u64,
+    written: u64,
+    // the limit was hit, nothing more is written
+    pub full: bool,
+    // the write that failed, nothing more is written after it. Left for the
+    // frontend to report
+    pub error: Option<io::Error>,
+}
+
+impl Trace {
+    pub fn new<W: Write + Send + 'static>(out: W) -> Trace {
+        Trace {
+            out: Box::new(out),
+            range: None,
+            bank: None,
+            limit: None,
+            lines: 0,
+            written: 0,
+            full: false,
+            error: None,
+        }
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Trace> {
+        Ok(Trace::new(BufWriter::new(File::create(path)?)))
+    }
+
+    // write the line for the instruction the cpu is about to run, if it
+    // passes the filters
+    pub fn log(&mut self, cpu: &CPU) -> io::Result<()> {
+        if self.full || self.error.is_some() {
+            return Ok(());
+        }
+        let pc = cpu.pc;
+        if self.range.is_some_and(|(start, end)| pc < start || pc > end)
+            || self.bank.is_some_and(|bank| bank != cpu.memory.bank(pc)) {
+            return Ok(());
+        }
+        let line = line(cpu);
+        if self.limit.is_some_and(|limit| self.written + line.len() as u64 + 1 > limit) {
+            self.full = true;
+            return Ok(());
+        }
+        writeln!(self.out, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        self.lines += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// the trace line for the cpu as it is now
+pub fn line(cpu: &CPU) -> String {
+    let memory = &cpu.memory;
+    let pc = cpu.pc;
+    format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            cpu.a, cpu.flags(), cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l, cpu.sp, pc,
+            memory.peek(pc), memory.peek(pc.wrapping_add(1)),
+            memory.peek(pc.wrapping_add(2)), memory.peek(pc.wrapping_add(3)))
+}
@@ -0,0 +1,238 @@
+// Whole rom disassembly on a hand made 64KB rom with a call, a data table and
+// a bank switch. The listing is put back together by a small assembler built
+// on the decoder, which has to get the rom back byte for byte. That only shows
+// the disassembler agrees with itself, so the listing is also checked against
+// asm_banked.asm, and when rgbasm and rgblink are installed they have to build
+// the same rom from it.
+
+extern crate gb_rs;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use gb_rs::debug::asm::Analysis;
+use gb_rs::debug::disasm;
+
+const BANK_SIZE: usize = 0x4000;
+
+fn banked_rom() -> Vec<u8> {
+    let mut rom = vec![0xFF; 4 * BANK_SIZE];
+    // nop; jp 0x150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x16A].copy_from_slice(&[
+        0x31, 0xFE, 0xFF,       // ld sp, $FFFE
+        0xCD, 0x00, 0x02,       // call $0200
+        0x3E, 0x02,             // ld a, $02
+        0xEA, 0x00, 0x20,       // ld [$2000], a
+        0xCD, 0x00, 0x40,       // call $4000, in bank 2
+        0xEA, 0x80, 0xFF,       // ld [$FF80], a
+        0xF8, 0xFE,             // ld hl, sp - 2
+        0xE8, 0xFE,             // add sp, -2
+        0x10, 0x00,             // stop
+        0x18, 0xE7,             // jr $0150
+        0xD3,                   // never reached
+    ]);
+    // sums a table at 0x300 up to a 0xFF
+    rom[0x200..0x209].copy_from_slice(&[
+        0x21, 0x00, 0x03,       // ld hl, $0300
+        0x2A,                   // ld a, [hl+]
+        0xFE, 0xFF,             // cp $FF
+        0xC8,                   // ret z
+        0x18, 0xFA,             // jr $0203
+    ]);
+    rom[0x300..0x304].copy_from_slice(&[0x01, 0x02, 0x03, 0xFF]);
+    // bank 1 is never mapped in by the code
+    rom[BANK_SIZE..BANK_SIZE + 4].copy_from_slice(&[0xC9, 0xC9, 0xC9, 0xC9]);
+    // ld b, 5; dec b; jr nz, $4002; ret
+    rom[2 * BANK_SIZE..2 * BANK_SIZE + 6].copy_from_slice(&[0x06, 0x05, 0x05, 0x20, 0xFD, 0xC9]);
+    rom
+}
+
+// $XXXX and label names become @ so an instruction can be matched before the
+// labels have addresses
+fn normalize(text: &str, labels: &HashSet<String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(first) = rest.chars().next() {
+        let word = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(rest.len()).max(first.len_utf8());
+        let token = &rest[..word];
+        let address = token.len() == 5 && token.starts_with('$')
+            && token[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if address || labels.contains(token) {
+            out.push('@');
+        } else {
+            out.push_str(token);
+        }
+        rest = &rest[word..];
+    }
+    out
+}
+
+// the numbers in an instruction, as the operand bytes they might encode
+fn operand_guesses(text: &str, address: u16) -> Vec<[u8; 2]> {
+    let mut guesses = vec![[0, 0]];
+    for token in text.split(&[' ', ',', '[', ']'][..]) {
+        let value = if let Some(hex) = token.strip_prefix('$') {
+            u16::from_str_radix(hex, 16).ok()
+        } else {
+            token.parse::<i16>().ok().map(|n| n as u16)
+        };
+        if let Some(value) = value {
+            guesses.push([value as u8, (value >> 8) as u8]);
+            guesses.push([(value as u8).wrapping_neg(), 0]);
+            // jr counts from the end of the instruction
+            guesses.push([value.wrapping_sub(address.wrapping_add(2)) as u8, 0]);
+        }
+    }
+    guesses
+}
+
+// the bytes an instruction line assembles to at address. Given label names,
+// only the shape has to match since all that is wanted is the length
+fn assemble_instruction(text: &str, address: u16, labels: &HashSet<String>) -> Vec<u8> {
+    let wanted = normalize(text, labels);
+    for guess in operand_guesses(text, address) {
+        for opcode in 0..=0xFFu8 {
+            for bytes in [[opcode, guess[0], guess[1]], [0xCB, opcode, 0]].iter() {
+                let instruction = disasm::decode(address, |a| bytes[a.wrapping_sub(address) as usize % 3]);
+                if instruction.text() == text || (!labels.is_empty() && normalize(&instruction.text(), labels) == wanted) {
+                    return bytes[..instruction.length as usize].to_vec();
+                }
+            }
+        }
+    }
+    panic!("could not assemble {}", text);
+}
+
+fn byte(text: &str) -> u8 {
+    u8::from_str_radix(text.trim().trim_start_matches('$'), 16).unwrap()
+}
+
+// rgbasm's job, for the handful of directives the listing uses
+fn assemble(source: &str) -> Vec<u8> {
+    let names: HashSet<String> = source.lines()
+        .filter_map(|line| line.strip_suffix(':'))
+        .map(String::from)
+        .collect();
+    let mut addresses: HashMap<String, u16> = HashMap::new();
+    let mut rom = Vec::new();
+    // the second pass fills in the labels the first one found
+    for pass in 0..2 {
+        rom.clear();
+        for line in source.lines() {
+            let line = line.split(';').next().unwrap().trim();
+            let address = if rom.len() < BANK_SIZE { rom.len() } else { BANK_SIZE + rom.len() % BANK_SIZE } as u16;
+            if line.is_empty() {
+                continue;
+            } else if line.starts_with("SECTION") {
+                let bank = line.split('$').nth(1).unwrap()[..3].to_string();
+                assert_eq!(rom.len(), usize::from_str_radix(&bank, 16).unwrap() * BANK_SIZE);
+            } else if let Some(name) = line.strip_suffix(':') {
+                addresses.insert(name.to_string(), address);
+            } else if let Some(data) = line.strip_prefix("db ") {
+                rom.extend(data.split(',').map(byte));
+            } else if let Some(fill) = line.strip_prefix("ds ") {
+                let mut fill = fill.split(',');
+                let count: usize = fill.next().unwrap().trim().parse().unwrap();
+                let value = byte(fill.next().unwrap());
+                rom.extend(std::iter::repeat_n(value, count));
+            } else if pass == 0 {
+                rom.extend(assemble_instruction(line, address, &names));
+            } else {
+                let mut text = line.to_string();
+                for (name, target) in &addresses {
+                    let named = text.split(&[' ', ','][..]).any(|word| word == name);
+                    if named {
+                        text = text.replace(name.as_str(), &format!("${:04X}", target));
+                    }
+                }
+                rom.extend(assemble_instruction(&text, address, &HashSet::new()));
+            }
+        }
+    }
+    rom
+}
+
+fn listing(rom: &[u8]) -> (Analysis, String) {
+    let analysis = Analysis::new(rom);
+    let mut out = Vec::new();
+    analysis.write(rom, &mut out).unwrap();
+    (analysis, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn reassembles() {
+    let rom = banked_rom();
+    let (_, source) = listing(&rom);
+    let assembled = assemble(&source);
+    assert_eq!(assembled.len(), rom.len());
+    let first_difference = assembled.iter().zip(rom.iter()).position(|(a, b)| a != b);
+    assert_eq!(first_difference, None, "{}", source);
+}
+
+#[test]
+fn matches_fixture() {
+    let (_, source) = listing(&banked_rom());
+    assert_eq!(source, include_str!("asm_banked.asm"));
+}
+
+// run one of the rgbds tools, false if it is not installed
+fn rgbds(tool: &str, args: &[&str]) -> bool {
+    match Command::new(tool).args(args).status() {
+        Ok(status) => {
+            assert!(status.success(), "{} {:?} failed", tool, args);
+            true
+        },
+        Err(_) => {
+            eprintln!("Skipping, {} not found", tool);
+            false
+        },
+    }
+}
+
+#[test]
+fn rgbds_round_trip() {
+    let (_, source) = listing(&banked_rom());
+    let dir = env::temp_dir().join(format!("gb-rs-asm-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+    fs::write(path("banked.asm"), source).unwrap();
+
+    let built = rgbds("rgbasm", &["-o", &path("banked.o"), &path("banked.asm")])
+        && rgbds("rgblink", &["-p", "0xFF", "-o", &path("banked.gb"), &path("banked.o")]);
+    let rom = if built { fs::read(path("banked.gb")).ok() } else { None };
+    fs::remove_dir_all(&dir).unwrap();
+    if let Some(rom) = rom {
+        assert!(rom == banked_rom(), "rgbds built a different rom");
+    }
+}
+
+#[test]
+fn separates_code_and_data() {
+    let rom = banked_rom();
+    let (analysis, source) = listing(&rom);
+    assert!(analysis.instruction(0x200).is_some());
+    assert!(analysis.instruction(0x300).is_none(), "the table is data");
+    assert!(analysis.instruction(0x169).is_none(), "nothing runs past jr");
+    assert!(analysis.instruction(BANK_SIZE).is_none(), "bank 1 is never mapped");
+    assert!(analysis.instruction(2 * BANK_SIZE + 3).is_some(), "bank 2 is switched in");
+    assert!(source.contains("\tdb $01, $02, $03\n"), "{}", source);
+    assert!(source.contains("\tdb $EA, $80, $FF ; ld [$FF80], a\n"), "{}", source);
+}
+
+#[test]
+fn labels() {
+    let rom = banked_rom();
+    let (analysis, source) = listing(&rom);
+    assert_eq!(analysis.label(0x100), Some("Entry"));
+    assert_eq!(analysis.label(0x200), Some("Call_000_0200"));
+    assert_eq!(analysis.label(0x203), Some("Jump_000_0203"));
+    assert_eq!(analysis.label(2 * BANK_SIZE), Some("Call_002_4000"));
+    assert_eq!(analysis.label(2 * BANK_SIZE + 2), Some("Jump_002_4002"));
+    assert!(source.contains("\tcall Call_002_4000\n"));
+    assert!(source.contains("\tjr nz, Jump_002_4002\n"));
+    assert!(source.contains("SECTION \"ROM Bank $002\", ROMX[$4000], BANK[$2]\n"));
+}
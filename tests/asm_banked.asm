@@ -0,0 +1,338 @@
+; Disassembled by gb-rs from a rom with crc32 235EF950
+
+SECTION "ROM Bank $000", ROM0[$0000]
+
+RST_00:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_08:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_10:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_18:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_20:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_28:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_30:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+RST_38:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+VBlankInterrupt:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+StatInterrupt:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+TimerInterrupt:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+SerialInterrupt:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+JoypadInterrupt:
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+	rst $0038
+
+Entry:
+	nop
+	jp Jump_000_0150
+
+	ds 76, $FF
+
+Jump_000_0150:
+	ld sp, $FFFE
+	call Call_000_0200
+	ld a, $02
+	ld [$2000], a
+	call Call_002_4000
+	db $EA, $80, $FF ; ld [$FF80], a
+	ld hl, sp - 2
+	add sp, -2
+	db $10, $00 ; stop
+	jr Jump_000_0150
+
+	db $D3
+	ds 150, $FF
+
+Call_000_0200:
+	ld hl, $0300
+
+Jump_000_0203:
+	ld a, [hl+]
+	cp $FF
+	ret z
+	jr Jump_000_0203
+
+	ds 247, $FF
+	db $01, $02, $03
+	ds 15613, $FF
+
+SECTION "ROM Bank $001", ROMX[$4000], BANK[$1]
+	db $C9, $C9, $C9, $C9
+	ds 16380, $FF
+
+SECTION "ROM Bank $002", ROMX[$4000], BANK[$2]
+
+Call_002_4000:
+	ld b, $05
+
+Jump_002_4002:
+	dec b
+	jr nz, Jump_002_4002
+	ret
+
+	ds 16378, $FF
+
+SECTION "ROM Bank $003", ROMX[$4000], BANK[$3]
+	ds 16384, $FF
@@ -0,0 +1,110 @@
+// The shadow call stack behind bt: frames for calls and interrupts, rets that
+// do not go where the stack expects and finding the frames again after a save
+// state is loaded, on common::calls_rom.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::stack::{Before, CallStack, Kind};
+use gb_rs::Gameboy;
+
+// run until pc gets to an address, stepping through the debugger
+fn run_to(debugger: &mut Debug, machine: &mut Gameboy, address: u16) {
+    for _ in 0..1000 {
+        if machine.cpu.pc == address {
+            return;
+        }
+        debugger.step(machine);
+    }
+    panic!("never got to 0x{:04X}", address);
+}
+
+fn return_addresses(stack: &CallStack) -> Vec<u16> {
+    stack.frames().map(|frame| frame.return_address).collect()
+}
+
+#[test]
+fn frames() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    run_to(&mut debugger, &mut machine, 0x181);
+    assert_eq!(return_addresses(&debugger.stack), vec![0x175, 0x153]);
+    let inner = debugger.stack.current().unwrap();
+    assert_eq!((inner.kind, inner.call_site, inner.function, inner.bank), (Kind::Call, 0x172, 0x180, 0));
+    assert!(debugger.stack.mismatch.is_none());
+    debugger.parse_input("bt", &mut machine);
+}
+
+#[test]
+fn interrupts() {
+    let machine = common::machine(&common::calls_rom());
+    let memory = &machine.cpu.memory;
+    let mut stack = CallStack::new();
+    // nothing the cpu runs here pushes, so an interrupt is made up: sp drops
+    // two and pc is at a vector without a call in sight
+    let before = Before { pc: 0x0104, sp: 0x0000, opcode: 0x00, bank: 0 };
+    stack.track(before, memory, 0x40, 0xFFFE);
+    let frame = *stack.current().unwrap();
+    assert_eq!((frame.kind, frame.call_site, frame.function), (Kind::Interrupt, 0x0104, 0x40));
+    // reti
+    let before = Before { pc: 0x41, sp: 0xFFFE, opcode: 0xD9, bank: 0 };
+    stack.track(before, memory, frame.return_address, 0x0000);
+    assert!(stack.is_empty());
+    assert!(stack.mismatch.is_none());
+
+    // a push is not an interrupt even at a vector
+    let before = Before { pc: 0x3F, sp: 0x0000, opcode: 0xC5, bank: 0 };
+    stack.track(before, memory, 0x40, 0xFFFE);
+    assert!(stack.is_empty());
+}
+
+#[test]
+fn mismatched_return() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    run_to(&mut debugger, &mut machine, 0x181);
+    // return straight to the outer caller, as if the game had popped first
+    debugger.parse_input("poke FFFA 53", &mut machine);
+    debugger.step(&mut machine);
+    assert_eq!(machine.cpu.pc, 0x153);
+    let mismatch = debugger.stack.mismatch.unwrap();
+    assert_eq!((mismatch.ret, mismatch.expected, mismatch.landed), (0x181, 0x175, 0x153));
+    assert!(debugger.stack.is_empty(), "both frames are gone");
+    debugger.parse_input("bt", &mut machine);
+}
+
+#[test]
+fn survives_loading_a_state() {
+    let path = env::temp_dir().join(format!("gb-rs-backtrace-{}.ss", std::process::id()));
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    run_to(&mut debugger, &mut machine, 0x181);
+    let frames: Vec<_> = debugger.stack.frames().cloned().collect();
+    debugger.parse_input(&format!("save {}", path.display()), &mut machine);
+
+    run_to(&mut debugger, &mut machine, 0x153);
+    assert!(debugger.stack.is_empty());
+    debugger.parse_input(&format!("load {}", path.display()), &mut machine);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(machine.cpu.pc, 0x181);
+    assert_eq!(return_addresses(&debugger.stack), vec![0x175, 0x153]);
+    for (found, seen) in debugger.stack.frames().zip(frames.iter()) {
+        assert_eq!(found.kind, Kind::Recovered);
+        assert_eq!((found.call_site, found.function, found.sp), (seen.call_site, seen.function, seen.sp));
+    }
+    // and finish works from them
+    debugger.parse_input("finish", &mut machine);
+    for _ in 0..100 {
+        if debugger.check_breakpoints(&machine) {
+            break;
+        }
+        debugger.step(&mut machine);
+    }
+    assert_eq!(machine.cpu.pc, 0x175);
+}
@@ -0,0 +1,92 @@
+// Blargg's test roms print their results to the serial port, ending with
+// "Passed" or "Failed". Each individual rom counts as one sub-test.
+//
+// Like the mooneye suites these fail on regressions, roms listed in
+// blargg_passing.txt that stop passing. Run with --nocapture to see the table.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::path::Path;
+
+use common::Verdict;
+
+// generous, the slowest cpu_instrs rom finishes in under a minute of
+// emulated time
+const FRAME_LIMIT: u32 = 60 * 60;
+
+const PASSING: &str = include_str!("blargg_passing.txt");
+
+const CPU_INSTRS: [&str; 11] = [
+    "01-special.gb",
+    "02-interrupts.gb",
+    "03-op sp,hl.gb",
+    "04-op r,imm.gb",
+    "05-op rp.gb",
+    "06-ld r,r.gb",
+    "07-jr,jp,call,ret,rst.gb",
+    "08-misc instrs.gb",
+    "09-op r,r.gb",
+    "10-bit ops.gb",
+    "11-op a,(hl).gb",
+];
+
+const MEM_TIMING: [&str; 3] = [
+    "01-read_timing.gb",
+    "02-write_timing.gb",
+    "03-modify_timing.gb",
+];
+
+fn run_rom(path: &Path) -> Option<Verdict> {
+    let mut machine = common::load(path)?;
+
+    Some(common::guard(move || {
+        for _ in 0..FRAME_LIMIT {
+            machine.run_frame();
+            let output = String::from_utf8_lossy(machine.serial_output()).into_owned();
+            if output.contains("Passed") {
+                return Verdict::Passed;
+            }
+            if output.contains("Failed") {
+                return Verdict::Failed(output.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+        }
+        Verdict::TimedOut
+    }))
+}
+
+fn run_suite(suite: &str, dir: &str, roms: &[&str]) {
+    let dir = match common::suite_dir(&format!("blargg/{}", dir)) {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let results: Vec<_> = roms.iter()
+        .filter_map(|rom| run_rom(&dir.join(rom)).map(|verdict| (format!("{}/{}", suite, rom), verdict)))
+        .collect();
+    if !results.is_empty() {
+        common::print_table(suite, &results);
+        common::check_regressions(suite, &results, PASSING);
+    }
+}
+
+#[test]
+fn cpu_instrs() {
+    run_suite("cpu_instrs", "cpu_instrs/individual", &CPU_INSTRS);
+}
+
+#[test]
+fn instr_timing() {
+    run_suite("instr_timing", "instr_timing", &["instr_timing.gb"]);
+}
+
+#[test]
+fn mem_timing() {
+    run_suite("mem_timing", "mem_timing/individual", &MEM_TIMING);
+}
+
+#[test]
+fn halt_bug() {
+    run_suite("halt_bug", "", &["halt_bug.gb"]);
+}
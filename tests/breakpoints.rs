@@ -0,0 +1,91 @@
+// Breakpoint conditions and counts on common::counter_rom, which loops over
+// 0x150: inc b; ld a, b; ld (0xC000), a; jr -7
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::expr::Condition;
+use gb_rs::Gameboy;
+
+// runs until the debugger stops the machine
+fn run(debugger: &mut Debug, machine: &mut Gameboy) {
+    for _ in 0..100000 {
+        if debugger.check_breakpoints(machine) {
+            return;
+        }
+        machine.step();
+    }
+    panic!("no breakpoint stopped the machine");
+}
+
+fn evaluate(source: &str, machine: &Gameboy) -> i64 {
+    Condition::parse(source).unwrap().evaluate(machine)
+}
+
+#[test]
+fn expressions() {
+    let mut machine = common::machine(&common::counter_rom());
+    machine.cpu.memory.load_value_u8(0xC123, 0x42);
+    machine.cpu.h = 0xC1;
+    machine.cpu.l = 0x23;
+
+    assert_eq!(evaluate("HL", &machine), 0xC123);
+    assert_eq!(evaluate("[HL] == $42", &machine), 1);
+    assert_eq!(evaluate("[hl + 1]", &machine), 0);
+    assert_eq!(evaluate("1 + 2 == 3 && !0", &machine), 1);
+    assert_eq!(evaluate("1 || 0 && 0", &machine), 1);
+    assert_eq!(evaluate("(1 || 0) && 0", &machine), 0);
+    assert_eq!(evaluate("H & 0xF0 | 1", &machine), 0xC1);
+    assert_eq!(evaluate("-1 < 0", &machine), 1);
+    assert_eq!(evaluate("PC == 0x100 && CYCLES == 0", &machine), 1);
+
+    for bad in ["", "A ==", "Q == 1", "[HL", "0xZZ", "A = 1", "1 2"].iter() {
+        assert!(Condition::parse(bad).is_err(), "{} should not parse", bad);
+    }
+}
+
+#[test]
+fn conditional_breakpoint() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    debugger.parse_input("b 0x0150 if B == 0x3 && [0xC000] == 3", &mut machine);
+
+    run(&mut debugger, &mut machine);
+    assert_eq!((machine.cpu.pc, machine.cpu.b), (0x150, 3));
+    assert_eq!(debugger.breakpoints[0].hits, 1);
+
+    // continuing does not stop on the same instruction again
+    debugger.parse_input("cond 0", &mut machine);
+    run(&mut debugger, &mut machine);
+    assert_eq!((machine.cpu.pc, machine.cpu.b), (0x150, 4));
+}
+
+#[test]
+fn ignore_counts() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    debugger.parse_input("b 152", &mut machine);
+    debugger.parse_input("ignore 0 5", &mut machine);
+
+    run(&mut debugger, &mut machine);
+    assert_eq!(machine.cpu.b, 6);
+    assert_eq!(debugger.breakpoints[0].hits, 6);
+    assert_eq!(debugger.breakpoints[0].ignore, 0);
+}
+
+#[test]
+fn temporary_breakpoint() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    debugger.parse_input("tb 0x0151", &mut machine);
+    debugger.parse_input("b 0x0150 if B == 2", &mut machine);
+
+    run(&mut debugger, &mut machine);
+    assert_eq!((machine.cpu.pc, machine.cpu.b), (0x151, 1));
+    assert_eq!(debugger.breakpoints.len(), 1);
+
+    run(&mut debugger, &mut machine);
+    assert_eq!((machine.cpu.pc, machine.cpu.b), (0x150, 2));
+}
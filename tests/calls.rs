@@ -0,0 +1,45 @@
+// call and ret carry the whole return address through the stack.
+
+extern crate gb_rs;
+
+mod common;
+
+
+// 0x150: call 0x4123; call 0x4200; inc b; jr -2
+// 0x4123: ret nz; ret
+// 0x4200: ret nz
+fn far_call_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x159].copy_from_slice(&[0xCD, 0x23, 0x41, 0xCD, 0x00, 0x42, 0x04, 0x18, 0xFE]);
+    rom[0x4123..0x4125].copy_from_slice(&[0xC0, 0xC9]);
+    rom[0x4200] = 0xC0;
+    rom
+}
+
+#[test]
+fn call_and_ret_nz() {
+    let mut machine = common::machine(&far_call_rom());
+    // nop; jp 0x150; call
+    for _ in 0..3 {
+        machine.step();
+    }
+    assert_eq!((machine.cpu.pc, machine.cpu.sp), (0x4123, 0xFFFC));
+    assert_eq!((machine.cpu.memory.peek(0xFFFD), machine.cpu.memory.peek(0xFFFC)), (0x01, 0x53));
+
+    // z is set after the boot rom, so the first ret nz falls through
+    assert_eq!(machine.cpu.flags() & 0x80, 0x80);
+    machine.step();
+    assert_eq!((machine.cpu.pc, machine.cpu.sp), (0x4124, 0xFFFC));
+
+    machine.step();
+    assert_eq!((machine.cpu.pc, machine.cpu.sp), (0x153, 0xFFFE));
+
+    machine.step();
+    assert_eq!(machine.cpu.pc, 0x4200);
+    machine.cpu.set_flags(0x00);
+    machine.step();
+    assert_eq!((machine.cpu.pc, machine.cpu.sp), (0x156, 0xFFFE));
+    machine.step();
+    assert_eq!(machine.cpu.b, 0x01);
+}
@@ -0,0 +1,163 @@
+// Helpers shared by the tests: small hand made roms, and finding and running
+// the test rom suites.
+//
+// The suites' roms are not part of the repository. Point GB_RS_TEST_ROMS at a
+// copy of the game-boy-test-roms collection
+// (https://github.com/c-sp/game-boy-test-roms), laid out as
+//
+//     blargg/cpu_instrs/individual/01-special.gb
+//     blargg/instr_timing/instr_timing.gb
+//     ...
+//
+// It defaults to roms/tests in the repository. A suite whose directory is
+// missing is skipped rather than failed.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use gb_rs::Gameboy;
+
+pub const ROMS_VAR: &str = "GB_RS_TEST_ROMS";
+
+// a gameboy at the cartridge entry point, as the boot rom leaves it
+pub fn machine(rom: &[u8]) -> Gameboy {
+    let mut machine = Gameboy::from_bytes(rom);
+    machine.skip_boot();
+    machine
+}
+
+// 0x100: jr -2, for tests that drive the hardware themselves
+pub fn idle_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    rom
+}
+
+// 0x100: nop; jp 0x150, 0x150: inc b; inc b; jr 0x150
+pub fn loop_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x154].copy_from_slice(&[0x04, 0x04, 0x18, 0xFC]);
+    rom
+}
+
+// a rom that counts in b and keeps storing it to 0xC000, for tests that only
+// need a machine doing something.
+// 0x100: jp 0x150, 0x150: inc b; ld a, b; ld (0xC000), a; jr -7
+pub fn counter_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x157].copy_from_slice(&[0x04, 0x78, 0xEA, 0x00, 0xC0, 0x18, 0xF9]);
+    rom
+}
+
+// nested calls for the debugger's call stack, next and finish.
+// 0x100: jp 0x150
+// 0x150: call 0x170; inc b; call 0x170; jr 0x150
+// 0x170: ld a, 5; call 0x180; ret
+// 0x180: inc b; ret
+pub fn calls_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x159].copy_from_slice(&[0xCD, 0x70, 0x01, 0x04, 0xCD, 0x70, 0x01, 0x18, 0xF7]);
+    rom[0x170..0x176].copy_from_slice(&[0x3E, 0x05, 0xCD, 0x80, 0x01, 0xC9]);
+    rom[0x180..0x182].copy_from_slice(&[0x04, 0xC9]);
+    rom
+}
+
+// the directory of one suite, None if it is not there
+pub fn suite_dir(suite: &str) -> Option<PathBuf> {
+    let root = match env::var_os(ROMS_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("roms").join("tests"),
+    };
+    let dir = root.join(suite);
+    if dir.is_dir() {
+        Some(dir)
+    } else {
+        eprintln!("Skipping {}, {} not found (set {})", suite, dir.display(), ROMS_VAR);
+        None
+    }
+}
+
+// a gameboy started at the cartridge entry point, None if the rom is missing
+pub fn load(path: &Path) -> Option<Gameboy> {
+    let mut rom = Vec::new();
+    match File::open(path) {
+        Ok(mut f) => f.read_to_end(&mut rom).expect("Could not read rom"),
+        Err(_) => {
+            eprintln!("Skipping {}, not found", path.display());
+            return None;
+        }
+    };
+    Some(machine(&rom))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Passed,
+    Failed(String),
+    // the emulator panicked, usually on an opcode that is not implemented yet
+    Crashed(String),
+    TimedOut,
+}
+
+// run a test and turn a panic inside the emulator into a crash verdict
+pub fn guard<F: FnOnce() -> Verdict>(test: F) -> Verdict {
+    match panic::catch_unwind(panic::AssertUnwindSafe(test)) {
+        Ok(verdict) => verdict,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("panic")
+            };
+            Verdict::Crashed(message)
+        }
+    }
+}
+
+// print one line per rom and fail if any of them did not pass
+pub fn report(suite: &str, results: &[(String, Verdict)]) {
+    let passed = print_table(suite, results);
+    assert!(passed == results.len(), "{}: {} of {} roms failed", suite, results.len() - passed, results.len());
+}
+
+// print one line per rom, returning how many passed
+pub fn print_table(suite: &str, results: &[(String, Verdict)]) -> usize {
+    println!("{}", suite);
+    for (name, verdict) in results {
+        let status = match verdict {
+            Verdict::Passed => String::from("pass"),
+            Verdict::Failed(why) => format!("FAIL  {}", why),
+            Verdict::Crashed(why) => format!("CRASH {}", why),
+            Verdict::TimedOut => String::from("TIMEOUT"),
+        };
+        println!("  {:<40} {}", name, status);
+    }
+
+    let passed = results.iter().filter(|&(_, verdict)| *verdict == Verdict::Passed).count();
+    println!("  {}/{} passed", passed, results.len());
+    passed
+}
+
+// fail if any result listed in a known passing file, one name per line with
+// # comments, no longer passes
+pub fn check_regressions(suite: &str, results: &[(String, Verdict)], passing: &str) {
+    let expected: Vec<&str> = passing.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let regressions: Vec<&str> = results.iter()
+        .filter(|&(name, verdict)| *verdict != Verdict::Passed && expected.contains(&name.as_str()))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert!(regressions.is_empty(), "{}: these used to pass and now fail: {:?}", suite, regressions);
+}
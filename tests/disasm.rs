@@ -0,0 +1,112 @@
+// The disassembler on hand written bytes, checked against the instruction
+// lengths and rgbds syntax of the opcode tables.
+
+extern crate gb_rs;
+
+use gb_rs::debug::disasm::{self, Operand};
+
+fn decode(bytes: &[u8], address: u16) -> disasm::Instruction {
+    disasm::decode(address, |a| bytes.get(a.wrapping_sub(address) as usize).cloned().unwrap_or(0))
+}
+
+fn text(bytes: &[u8]) -> String {
+    decode(bytes, 0x150).text()
+}
+
+// lengths of the base opcodes, a row per high nibble
+const LENGTHS: [&str; 16] = [
+    "1311112131111121",
+    "2311112121111121",
+    "2311112121111121",
+    "2311112121111121",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1111111111111111",
+    "1133312111323321",
+    "1131312111313121",
+    "2111112121311121",
+    "2111112121311121",
+];
+
+#[test]
+fn lengths() {
+    for opcode in 0..=0xFFu8 {
+        let row = LENGTHS[(opcode >> 4) as usize].as_bytes();
+        let expected = (row[(opcode & 0xF) as usize] - b'0') as u16;
+        let instruction = decode(&[opcode, 0x12, 0x34], 0x150);
+        assert_eq!(instruction.length, expected, "opcode 0x{:02X} is {}", opcode, instruction.text());
+        assert!(!instruction.text().is_empty());
+    }
+}
+
+#[test]
+fn base_opcodes() {
+    assert_eq!(text(&[0x00]), "nop");
+    assert_eq!(text(&[0x01, 0x34, 0x12]), "ld bc, $1234");
+    assert_eq!(text(&[0x08, 0x00, 0xC0]), "ld [$C000], sp");
+    assert_eq!(text(&[0x10, 0x00]), "stop");
+    assert_eq!(text(&[0x22]), "ld [hl+], a");
+    assert_eq!(text(&[0x3A]), "ld a, [hl-]");
+    assert_eq!(text(&[0x36, 0x7F]), "ld [hl], $7F");
+    assert_eq!(text(&[0x40]), "ld b, b");
+    assert_eq!(text(&[0x76]), "halt");
+    assert_eq!(text(&[0x7E]), "ld a, [hl]");
+    assert_eq!(text(&[0x88]), "adc a, b");
+    assert_eq!(text(&[0x96]), "sub [hl]");
+    assert_eq!(text(&[0xBF]), "cp a");
+    assert_eq!(text(&[0xE0, 0x40]), "ldh [$FF40], a");
+    assert_eq!(text(&[0xF2]), "ldh a, [c]");
+    assert_eq!(text(&[0xE8, 0xFE]), "add sp, -2");
+    assert_eq!(text(&[0xF8, 0x05]), "ld hl, sp + 5");
+    assert_eq!(text(&[0xEA, 0x00, 0xC0]), "ld [$C000], a");
+    assert_eq!(text(&[0xF1]), "pop af");
+    assert_eq!(text(&[0xE9]), "jp hl");
+    assert_eq!(text(&[0xFE, 0x90]), "cp $90");
+    assert_eq!(text(&[0xD3]), "db $D3");
+    assert!(!disasm::is_valid(0xD3));
+}
+
+#[test]
+fn prefixed_opcodes() {
+    assert_eq!(text(&[0xCB, 0x00]), "rlc b");
+    assert_eq!(text(&[0xCB, 0x37]), "swap a");
+    assert_eq!(text(&[0xCB, 0x3E]), "srl [hl]");
+    assert_eq!(text(&[0xCB, 0x7C]), "bit 7, h");
+    assert_eq!(text(&[0xCB, 0x87]), "res 0, a");
+    assert_eq!(text(&[0xCB, 0xFE]), "set 7, [hl]");
+    for opcode in 0..=0xFFu8 {
+        assert_eq!(decode(&[0xCB, opcode], 0).length, 2);
+    }
+}
+
+#[test]
+fn jump_targets() {
+    // jr back over itself and forward past the next instruction
+    let back = decode(&[0x18, 0xF9], 0x157);
+    assert_eq!(back.operand, Some(Operand::Jump(0x152)));
+    assert_eq!(back.text(), "jr $0152");
+    assert!(back.ends_block());
+    assert_eq!(decode(&[0x20, 0x02], 0x150).text(), "jr nz, $0154");
+    assert_eq!(decode(&[0xC3, 0x50, 0x01], 0x100).operand, Some(Operand::Jump(0x150)));
+    assert_eq!(text(&[0xDC, 0x00, 0x40]), "call c, $4000");
+    assert_eq!(text(&[0xFF]), "rst $0038");
+    assert!(!decode(&[0xCD, 0x00, 0x40], 0x150).ends_block());
+}
+
+#[test]
+fn named_operands() {
+    let call = decode(&[0xCD, 0x00, 0x40], 0x150);
+    let named = call.text_with(|operand| match operand {
+        Operand::Jump(0x4000) => Some("FarCode".to_string()),
+        _ => None,
+    });
+    assert_eq!(named, "call FarCode");
+    let load = decode(&[0xFA, 0x00, 0xC0], 0x150);
+    assert_eq!(load.operand, Some(Operand::Memory(0xC000)));
+    assert_eq!(load.text_with(|_| Some("wCounter".to_string())), "ld a, [wCounter]");
+}
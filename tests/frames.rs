@@ -0,0 +1,55 @@
+// Where frames end: every frame step finishes is handed out by take_frame,
+// and run_frame always returns because no instruction runs without cycles.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::panic;
+
+use gb_rs::cpu::gpu::CYCLES_PER_FRAME;
+
+#[test]
+fn frames_are_not_merged() {
+    let mut machine = common::machine(&common::idle_rom());
+    while machine.cycles < 3 * CYCLES_PER_FRAME as u64 + 10 {
+        machine.step();
+    }
+    for _ in 0..3 {
+        assert!(machine.take_frame());
+    }
+    assert!(!machine.take_frame());
+}
+
+#[test]
+fn lcd_off() {
+    let mut machine = common::machine(&common::idle_rom());
+    machine.cpu.memory.poke(0xFF40, 0x00);
+    let cycles = machine.run_frame();
+    assert!((CYCLES_PER_FRAME..CYCLES_PER_FRAME + 4).contains(&cycles), "{}", cycles);
+}
+
+#[test]
+fn nops() {
+    // one m-cycle each, so a frame of them ends on time
+    let mut machine = common::machine(&vec![0; 0x8000]);
+    let cycles = machine.run_frame();
+    assert!(cycles <= CYCLES_PER_FRAME, "{}", cycles);
+    assert_eq!(machine.cpu.pc, 0x100 + cycles as u16);
+    assert!(!machine.take_frame());
+}
+
+#[test]
+fn every_instruction_takes_cycles() {
+    // the opcodes the cpu does not know panic, the rest must tick
+    let opcodes = (0..=0xFF).map(|opcode| [opcode, 0])
+        .chain((0..=0xFF).map(|opcode| [0xCB, opcode]));
+    for bytes in opcodes {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&bytes);
+        let result = panic::catch_unwind(|| common::machine(&rom).step());
+        if let Ok(cycles) = result {
+            assert!(cycles > 0, "{:02X?} took no cycles", bytes);
+        }
+    }
+}
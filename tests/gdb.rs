@@ -0,0 +1,175 @@
+// The gdb remote protocol stub, driven over a local socket by a small client.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::gdb::GdbStub;
+use gb_rs::Gameboy;
+
+// 0x100: nop; jp 0x150
+// 0x150: inc b; ld [$C000], a; jr 0x150
+fn store_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x156].copy_from_slice(&[0x04, 0xEA, 0x00, 0xC0, 0x18, 0xF9]);
+    rom
+}
+
+struct Client {
+    stream: TcpStream,
+    ack: bool,
+}
+
+impl Client {
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    // send a packet and return the reply
+    fn ask(&mut self, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+        if self.ack {
+            assert_eq!(self.byte(), b'+');
+        }
+        self.reply()
+    }
+
+    fn reply(&mut self) -> String {
+        assert_eq!(self.byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum = [self.byte(), self.byte()];
+        let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        assert_eq!(u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(), sum);
+        if self.ack {
+            self.stream.write_all(b"+").unwrap();
+        }
+        String::from_utf8(data).unwrap()
+    }
+}
+
+// serve a machine on a local port, returning the client and the server
+// thread, which hands the machine back when the session ends
+fn session() -> (Client, thread::JoinHandle<Gameboy>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut machine = common::machine(&store_rom());
+        let mut debugger = Debug::new();
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(stream).unwrap().run(&mut machine, &mut debugger).unwrap();
+        machine
+    });
+    let stream = TcpStream::connect(address).unwrap();
+    stream.set_nodelay(true).unwrap();
+    (Client { stream, ack: true }, server)
+}
+
+#[test]
+fn registers_and_memory() {
+    let (mut client, server) = session();
+    assert!(client.ask("qSupported:multiprocess+").contains("qXfer:features:read+"));
+    assert_eq!(client.ask("?"), "S05");
+    // af bc de hl sp pc as the boot rom leaves them, low bytes first
+    assert_eq!(client.ask("g"), "b0011300d8004d01feff0001");
+    assert_eq!(client.ask("p3"), "4d01");
+    assert_eq!(client.ask("P1=3412"), "OK");
+    assert_eq!(client.ask("p1"), "3412");
+    assert_eq!(client.ask("p9"), "E01");
+    assert_eq!(client.ask("Gf000000000000000fcff5001"), "OK");
+    assert_eq!(client.ask("g"), "f000000000000000fcff5001");
+
+    assert_eq!(client.ask("m150,6"), "04ea00c018f9");
+    assert_eq!(client.ask("Mc000,3:abcdef"), "OK");
+    assert_eq!(client.ask("mc000,3"), "abcdef");
+    assert_eq!(client.ask("Mc000,3:ab"), "E01");
+
+    let xml = client.ask("qXfer:features:read:target.xml:0,1000");
+    assert!(xml.starts_with('l') && xml.contains("name=\"pc\""), "{}", xml);
+    assert_eq!(client.ask("vMustReplyEmpty"), "");
+    assert_eq!(client.ask("D"), "OK");
+
+    let machine = server.join().unwrap();
+    assert_eq!((machine.cpu.a, machine.cpu.flags(), machine.cpu.sp, machine.cpu.pc), (0x00, 0xF0, 0xFFFC, 0x150));
+    assert_eq!(machine.cpu.memory.peek(0xC001), 0xCD);
+}
+
+#[test]
+fn breakpoints_and_steps() {
+    let (mut client, server) = session();
+    assert_eq!(client.ask("Z0,151,1"), "OK");
+    assert_eq!(client.ask("c"), "S05");
+    assert_eq!(client.ask("p5"), "5101");
+    // continuing runs the loop round to the breakpoint again
+    assert_eq!(client.ask("c"), "S05");
+    assert_eq!(client.ask("p5"), "5101");
+    assert_eq!(client.ask("p1"), "1302", "b went up once more");
+    assert_eq!(client.ask("z0,151,1"), "OK");
+    assert_eq!(client.ask("s"), "S05");
+    assert_eq!(client.ask("p5"), "5401");
+    assert_eq!(client.ask("s150"), "S05");
+    assert_eq!(client.ask("p5"), "5101");
+
+    assert_eq!(client.ask("Z2,c000,1"), "OK");
+    assert_eq!(client.ask("c"), "T05watch:c000;");
+    assert_eq!(client.ask("p5"), "5401");
+    assert_eq!(client.ask("z2,c000,1"), "OK");
+    assert_eq!(client.ask("Z4,c000,1"), "OK");
+    assert_eq!(client.ask("c"), "T05awatch:c000;");
+    // kill has no reply
+    write!(client.stream, "$k#6b").unwrap();
+    let machine = server.join().unwrap();
+    assert_eq!(machine.cpu.memory.watchpoints.len(), 1);
+}
+
+#[test]
+fn interrupt_and_no_ack_mode() {
+    let (mut client, server) = session();
+    assert_eq!(client.ask("QStartNoAckMode"), "OK");
+    client.ack = false;
+    assert_eq!(client.ask("p5"), "0001");
+    write!(client.stream, "$c#63").unwrap();
+    // ctrl-c
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.ask("D"), "OK");
+    let machine = server.join().unwrap();
+    assert!(machine.cycles > 0, "it ran");
+}
+
+#[test]
+fn escaped_bytes() {
+    let (mut client, server) = session();
+    // p3 with the 3 escaped
+    assert_eq!(client.ask("p}\x13"), "4d01");
+    assert_eq!(client.ask("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn unimplemented_opcode() {
+    let (mut client, server) = session();
+    // 0xD3 is not an instruction, the stub replies instead of going down
+    assert_eq!(client.ask("M150,1:d3"), "OK");
+    assert_eq!(client.ask("c"), "S04");
+    assert_eq!(client.ask("s"), "S04");
+    assert_eq!(client.ask("M150,1:04"), "OK");
+    assert_eq!(client.ask("s"), "S05");
+    assert_eq!(client.ask("D"), "OK");
+    let machine = server.join().unwrap();
+    assert_eq!(machine.cpu.b, 0x01);
+}
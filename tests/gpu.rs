@@ -0,0 +1,47 @@
+// The lcd draws every line of a frame, the first one included.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::cpu::gpu::{SCREEN_WIDTH, LCD_CONTROL, BG_PALETTE};
+use gb_rs::Gameboy;
+
+fn idle_machine() -> Gameboy {
+    let mut machine = common::machine(&common::idle_rom());
+    // colours as they are, and tile 0, which fills the map, gets a first row
+    // of colour 3
+    let memory = &mut machine.cpu.memory;
+    memory.poke(BG_PALETTE as u16, 0b11100100);
+    memory.poke(0x8000, 0xFF);
+    memory.poke(0x8001, 0xFF);
+    machine
+}
+
+fn row(machine: &Gameboy, y: usize) -> &[u8] {
+    &machine.framebuffer()[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]
+}
+
+#[test]
+fn first_line() {
+    let mut machine = idle_machine();
+    machine.run_frame();
+    assert!(row(&machine, 0).iter().all(|&shade| shade == 3), "{:?}", row(&machine, 0));
+    assert!(row(&machine, 1).iter().all(|&shade| shade == 0));
+    assert!(row(&machine, 8).iter().all(|&shade| shade == 3));
+}
+
+#[test]
+fn first_line_after_turning_the_lcd_back_on() {
+    let mut machine = idle_machine();
+    machine.run_frame();
+    let control = machine.cpu.memory.peek(LCD_CONTROL as u16);
+    machine.cpu.memory.poke(LCD_CONTROL as u16, control & 0x7F);
+    machine.step();
+    assert_eq!(machine.cpu.memory.peek(0xFF41) & 0b11, 0, "mode 0 while off");
+
+    machine.cpu.memory.poke(0x8001, 0x00);
+    machine.cpu.memory.poke(LCD_CONTROL as u16, control);
+    machine.run_frame();
+    assert!(row(&machine, 0).iter().all(|&shade| shade == 1), "{:?}", row(&machine, 0));
+}
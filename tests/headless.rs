@@ -0,0 +1,114 @@
+// Headless runs: every limit and condition in the library, and the exit
+// codes and argument parsing of `gb-rs headless`.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use gb_rs::headless::{self, Outcome, RunConfig, DEFAULT_FRAMES};
+
+// 0x100: ld a, 'B'; ldh [SB], a; ld a, 0x81; ldh [SC], a; jr -2
+fn serial_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x10A].copy_from_slice(&[0x3E, b'B', 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]);
+    rom
+}
+
+// 0x100: nop; nop; ld b, b; jr -2
+fn breakpoint_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x105].copy_from_slice(&[0x00, 0x00, 0x40, 0x18, 0xFE]);
+    rom
+}
+
+#[test]
+fn frame_and_cycle_limits() {
+    let mut config = RunConfig::new();
+    config.frames = Some(2);
+    let run = headless::run(&mut common::machine(&common::counter_rom()), &config);
+    assert_eq!((run.outcome, run.frames), (Outcome::Limit, 2));
+
+    let mut config = RunConfig::new();
+    config.cycles = Some(1000);
+    let run = headless::run(&mut common::machine(&common::counter_rom()), &config);
+    assert_eq!(run.outcome, Outcome::Limit);
+    assert!(run.cycles >= 1000 && run.cycles < 1010, "{}", run.cycles);
+}
+
+#[test]
+fn default_limit() {
+    // nothing ever matches, the run still ends
+    let mut config = RunConfig::new();
+    config.until_pc = Some(0x4000);
+    let run = headless::run(&mut common::machine(&common::counter_rom()), &config);
+    assert_eq!((run.outcome, run.frames), (Outcome::Limit, DEFAULT_FRAMES));
+}
+
+#[test]
+fn conditions() {
+    let mut config = RunConfig::new();
+    config.frames = Some(10);
+    config.until_pc = Some(0x155);
+    let mut counter = common::machine(&common::counter_rom());
+    assert_eq!(headless::run(&mut counter, &config).outcome, Outcome::ReachedPc);
+    assert_eq!(counter.cpu.pc, 0x155);
+
+    let mut config = RunConfig::new();
+    config.frames = Some(10);
+    config.until_serial = Some("B".to_string());
+    let run = headless::run(&mut common::machine(&serial_rom()), &config);
+    assert_eq!((run.outcome, run.frames), (Outcome::MatchedSerial, 0));
+
+    let mut config = RunConfig::new();
+    config.frames = Some(10);
+    config.until_breakpoint = true;
+    let mut breakpoint = common::machine(&breakpoint_rom());
+    assert_eq!(headless::run(&mut breakpoint, &config).outcome, Outcome::Breakpoint);
+    assert_eq!(breakpoint.cpu.pc, 0x103);
+}
+
+fn write_rom(name: &str, rom: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("gb-rs-headless-{}-{}.gb", name, std::process::id()));
+    fs::write(&path, rom).unwrap();
+    path
+}
+
+// the exit code of `gb-rs headless ROM --skip-boot ARGS`
+fn exit_code(rom: &PathBuf, args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_gb-rs"))
+        .arg("headless")
+        .arg(rom)
+        .arg("--skip-boot")
+        .args(args)
+        .output()
+        .unwrap();
+    output.status.code().unwrap()
+}
+
+#[test]
+fn exit_codes() {
+    let counter = write_rom("counter", &common::counter_rom());
+    assert_eq!(exit_code(&counter, &["--frames", "1"]), 0, "a limit with no condition");
+    assert_eq!(exit_code(&counter, &["--frames", "10", "--until-pc", "155"]), 0);
+    assert_eq!(exit_code(&counter, &["--frames", "10", "--until-pc", "0x155"]), 0);
+    assert_eq!(exit_code(&counter, &["--frames", "10", "--until-pc", "0X155"]), 0);
+    assert_eq!(exit_code(&counter, &["--frames", "1", "--until-pc", "4000"]), 1, "the limit came first");
+    assert_eq!(exit_code(&counter, &["--frames", "1", "--until-pc", "0xZZ"]), 3);
+    assert_eq!(exit_code(&counter, &["--frames", "ten"]), 3);
+
+    // 0xD3 is not an instruction
+    let mut crash = vec![0; 0x8000];
+    crash[0x100] = 0xD3;
+    let crash = write_rom("crash", &crash);
+    assert_eq!(exit_code(&crash, &["--frames", "1"]), 2);
+
+    let missing = env::temp_dir().join("gb-rs-headless-missing.gb");
+    assert_eq!(exit_code(&missing, &["--frames", "1"]), 3);
+    fs::remove_file(counter).unwrap();
+    fs::remove_file(crash).unwrap();
+}
@@ -0,0 +1,80 @@
+// Two machines on the in-process link cable, and the serial port's clock.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::cpu::link::LinkCable;
+use gb_rs::cpu::serial::Serial;
+use gb_rs::Gameboy;
+
+// 0x100: ld a, data; ldh [SB], a; ld a, control; ldh [SC], a; jr -2
+fn transfer_rom(data: u8, control: u8) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x10A].copy_from_slice(&[0x3E, data, 0xE0, 0x01, 0x3E, control, 0xE0, 0x02, 0x18, 0xFE]);
+    rom
+}
+
+fn serial_interrupt(machine: &Gameboy) -> bool {
+    machine.cpu.memory.peek(0xFF0F) & 0b00001000 != 0
+}
+
+#[test]
+fn master_and_slave_swap_bytes() {
+    // the left side clocks, the right waits on the external clock
+    let mut cable = LinkCable::new(common::machine(&transfer_rom(0x42, 0x81)), common::machine(&transfer_rom(0x99, 0x80)));
+    assert!(!serial_interrupt(&cable.left) && !serial_interrupt(&cable.right));
+    while cable.left.cycles < 900 {
+        cable.step();
+    }
+    assert_eq!(cable.left.cpu.memory.peek(0xFF01), 0x42, "not all 8 bits are out yet");
+    assert!(!serial_interrupt(&cable.left));
+
+    while cable.left.cycles < 1200 {
+        cable.step();
+    }
+    let (left, right) = cable.disconnect();
+    assert_eq!(left.cpu.memory.peek(0xFF01), 0x99);
+    assert_eq!(right.cpu.memory.peek(0xFF01), 0x42);
+    for machine in [&left, &right].iter() {
+        assert_eq!(machine.cpu.memory.peek(0xFF02) & 0x80, 0, "transfer finished");
+        assert!(serial_interrupt(machine));
+    }
+    assert_eq!(left.cpu.memory.serial.output(), &[0x42]);
+}
+
+#[test]
+fn unplugged_master_reads_ff() {
+    let mut machine = common::machine(&transfer_rom(0x42, 0x81));
+    while machine.cycles < 1200 {
+        machine.step();
+    }
+    assert_eq!(machine.cpu.memory.peek(0xFF01), 0xFF);
+    assert!(serial_interrupt(&machine));
+}
+
+#[test]
+fn clock_keeps_overflow() {
+    let mut serial = Serial::new();
+    serial.write_control(0x81);
+    serial.step(1000);
+    assert!(serial.transferring());
+    // 6 cycles past the 1024 a byte takes
+    serial.step(30);
+    assert!(!serial.transferring());
+
+    serial.write_control(0x81);
+    serial.step(1017);
+    assert!(serial.transferring());
+    serial.step(1);
+    assert!(!serial.transferring(), "the next byte is 6 cycles shorter");
+
+    // one cut short starts over
+    serial.write_control(0x81);
+    serial.step(500);
+    serial.write_control(0x81);
+    serial.step(1023);
+    assert!(serial.transferring());
+    serial.step(1);
+    assert!(!serial.transferring());
+}
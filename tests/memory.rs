@@ -0,0 +1,63 @@
+// Memory and register editing from the debugger, where set goes through the
+// bus with its side effects and poke does not.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::cpu::gpu::OAM;
+use gb_rs::debug::debug::Debug;
+use gb_rs::Gameboy;
+
+fn run(debugger: &mut Debug, machine: &mut Gameboy, commands: &[&str]) {
+    for command in commands {
+        debugger.parse_input(command, machine);
+    }
+}
+
+#[test]
+fn set_and_fill() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    run(&mut debugger, &mut machine, &["set C000 12", "poke $C001 34", "fill C002-C004 AA",
+                                       "pfill C005 3 bb", "set C008 123", "fill C009 0 1"]);
+    let memory = &machine.cpu.memory;
+    let bytes: Vec<u8> = (0xC000..0xC00A).map(|address| memory.peek(address)).collect();
+    assert_eq!(bytes, [0x12, 0x34, 0xAA, 0xAA, 0xAA, 0xBB, 0xBB, 0xBB, 0x00, 0x00]);
+}
+
+#[test]
+fn poke_skips_side_effects() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    run(&mut debugger, &mut machine, &["fill C100-C19F 77", "set LY 33"]);
+    assert_eq!(machine.cpu.memory.peek(0xFF44), 0, "LY ignores writes");
+    run(&mut debugger, &mut machine, &["poke ff44 33", "poke DMA C1"]);
+    assert_eq!(machine.cpu.memory.peek(0xFF44), 0x33);
+    for _ in 0..100 {
+        machine.step();
+    }
+    assert_eq!(machine.cpu.memory.peek(OAM as u16), 0, "poking DMA starts no dma");
+
+    run(&mut debugger, &mut machine, &["set DMA C1"]);
+    for _ in 0..100 {
+        machine.step();
+    }
+    assert_eq!(machine.cpu.memory.peek(OAM as u16), 0x77);
+}
+
+#[test]
+fn registers() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    run(&mut debugger, &mut machine, &["r a 0x12", "r HL 1234", "r sp DFF0", "r f F0", "r b 100", "r q 1"]);
+    let cpu = &machine.cpu;
+    assert_eq!((cpu.a, cpu.h, cpu.l, cpu.sp), (0x12, 0x12, 0x34, 0xDFF0));
+    assert_eq!(cpu.flags(), 0xF0);
+    assert_eq!(cpu.b, 0);
+
+    // moving pc somewhere with a breakpoint does not stop there straight away
+    run(&mut debugger, &mut machine, &["b 152", "r pc 0x152"]);
+    assert!(!debugger.check_breakpoints(&machine));
+    assert_eq!(machine.cpu.pc, 0x152);
+}
@@ -0,0 +1,135 @@
+// Mooneye's test roms finish by running LD B, B. A pass leaves the fibonacci
+// numbers 3, 5, 8, 13, 21, 34 in B, C, D, E, H and L, a failure leaves 0x42
+// in all of them.
+//
+// Most of these roms do not pass yet, so rather than failing on every rom
+// the suites fail on regressions: any rom listed in mooneye_passing.txt that
+// stops passing. Run with --nocapture to see the table, and add roms to the
+// list as they start to pass. result_registers checks the verdict itself on
+// small roms that end the same way, so something runs without the suite.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use gb_rs::Gameboy;
+
+use common::Verdict;
+
+const FRAME_LIMIT: u32 = 60 * 20;
+
+const PASSED: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const FAILED: [u8; 6] = [0x42; 6];
+
+const PASSING: &str = include_str!("mooneye_passing.txt");
+
+fn run_rom(path: &Path) -> Option<Verdict> {
+    common::load(path).map(run_machine)
+}
+
+fn run_machine(mut machine: Gameboy) -> Verdict {
+    common::guard(move || {
+        for _ in 0..FRAME_LIMIT {
+            machine.run_frame();
+            if machine.take_breakpoint() {
+                let cpu = &machine.cpu;
+                let registers = [cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l];
+                return if registers == PASSED {
+                    Verdict::Passed
+                } else if registers == FAILED {
+                    Verdict::Failed(String::from("failure registers"))
+                } else {
+                    Verdict::Failed(format!("B C D E H L = {:?}", registers))
+                };
+            }
+        }
+        Verdict::TimedOut
+    })
+}
+
+// 0x100: ld b, b0; ld c, c0; ld d, d0; ld e, e0; ld hl, h0 l0; ld b, b; jr -2
+fn result_rom(registers: [u8; 6]) -> Vec<u8> {
+    let [b, c, d, e, h, l] = registers;
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x10E].copy_from_slice(&[0x06, b, 0x0E, c, 0x16, d, 0x1E, e, 0x21, l, h, 0x40, 0x18, 0xFE]);
+    rom
+}
+
+// roms named for other models, such as foo-cgb.gb or foo-S.gb, are left out.
+// Names without a model suffix run everywhere
+fn runs_on_dmg(name: &str) -> bool {
+    let stem = name.trim_end_matches(".gb");
+    match stem.rfind('-') {
+        Some(dash) => {
+            let models = &stem[dash + 1..];
+            models.contains("dmgABC") || models.starts_with('G')
+        },
+        None => true,
+    }
+}
+
+fn run_suite(suite: &str) {
+    let dir = match common::suite_dir(&format!("mooneye-test-suite/{}", suite)) {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let mut roms: Vec<String> = fs::read_dir(&dir).expect("Could not list roms")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".gb") && runs_on_dmg(name))
+        .collect();
+    roms.sort();
+
+    let results: Vec<_> = roms.iter()
+        .filter_map(|rom| run_rom(&dir.join(rom)).map(|verdict| (format!("{}/{}", suite, rom), verdict)))
+        .collect();
+    common::print_table(suite, &results);
+    common::check_regressions(suite, &results, PASSING);
+}
+
+#[test]
+fn timer() {
+    run_suite("acceptance/timer");
+}
+
+#[test]
+fn interrupts() {
+    run_suite("acceptance/interrupts");
+}
+
+#[test]
+fn oam_dma() {
+    run_suite("acceptance/oam_dma");
+}
+
+#[test]
+fn ppu() {
+    run_suite("acceptance/ppu");
+}
+
+#[test]
+fn mbc1() {
+    run_suite("emulator-only/mbc1");
+}
+
+#[test]
+fn mbc2() {
+    run_suite("emulator-only/mbc2");
+}
+
+#[test]
+fn mbc5() {
+    run_suite("emulator-only/mbc5");
+}
+
+#[test]
+fn result_registers() {
+    assert_eq!(run_machine(common::machine(&result_rom(PASSED))), Verdict::Passed);
+    assert_eq!(run_machine(common::machine(&result_rom(FAILED))), Verdict::Failed(String::from("failure registers")));
+    assert_eq!(run_machine(common::machine(&result_rom([3, 5, 8, 13, 21, 0]))),
+               Verdict::Failed(String::from("B C D E H L = [3, 5, 8, 13, 21, 0]")));
+}
@@ -0,0 +1,82 @@
+// Input movies recorded and played back on a tiny rom that copies the joypad
+// register to ram, no test roms needed.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::headless::{self, RunConfig};
+use gb_rs::movie::{Movie, Recorder, Start};
+use gb_rs::{state, Buttons, Gameboy};
+
+// 0x100: jp 0x150, 0x150: ldh a, (0x00); ld (0xC000), a; inc b; jr -8
+fn joypad_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x158].copy_from_slice(&[0xF0, 0x00, 0xEA, 0x00, 0xC0, 0x04, 0x18, 0xF8]);
+    rom
+}
+
+fn inputs() -> Vec<Buttons> {
+    (0..40u8).map(|frame| Buttons::from_bits_truncate(frame.wrapping_mul(37))).collect()
+}
+
+// records inputs() the way a frontend would, returning the movie and the
+// final state
+fn record(rom: &[u8], start: Start) -> (Vec<u8>, Vec<u8>) {
+    let mut machine = match start {
+        Start::PowerOn => Gameboy::from_bytes(rom),
+        Start::State(ref data) => {
+            let mut machine = Gameboy::from_bytes(rom);
+            state::load(&mut machine, data).unwrap();
+            machine
+        },
+    };
+    let mut recorder = Recorder::new(Vec::new(), rom, start).unwrap();
+    for buttons in inputs() {
+        recorder.frame(buttons).unwrap();
+        machine.set_buttons(buttons);
+        machine.run_frame();
+    }
+    (recorder.into_inner(), state::save(&machine))
+}
+
+fn play(rom: &[u8], movie: &[u8]) -> Vec<u8> {
+    let movie = Movie::read(&mut &movie[..]).unwrap();
+    let mut machine = movie.start(rom).unwrap();
+    let mut config = RunConfig::new();
+    config.frames = Some(movie.len() as u64);
+    config.movie = Some(movie);
+    headless::run(&mut machine, &config);
+    state::save(&machine)
+}
+
+#[test]
+fn plays_back_from_power_on() {
+    let rom = joypad_rom();
+    let (movie, recorded) = record(&rom, Start::PowerOn);
+    assert_eq!(Movie::read(&mut &movie[..]).unwrap().frames, inputs());
+    assert_eq!(play(&rom, &movie), recorded);
+    assert_eq!(play(&rom, &movie), recorded);
+}
+
+#[test]
+fn plays_back_from_a_save_state() {
+    let rom = joypad_rom();
+    let mut machine = common::machine(&rom);
+    for _ in 0..3 {
+        machine.run_frame();
+    }
+    let (movie, recorded) = record(&rom, Start::State(state::save(&machine)));
+    assert_eq!(play(&rom, &movie), recorded);
+}
+
+#[test]
+fn refuses_another_rom() {
+    let rom = joypad_rom();
+    let (movie, _) = record(&rom, Start::PowerOn);
+    let movie = Movie::read(&mut &movie[..]).unwrap();
+
+    assert!(movie.start(&common::counter_rom()).is_err());
+    assert!(Movie::read(&mut &b"GBRS"[..]).is_err());
+}
@@ -0,0 +1,65 @@
+// Hardware register fields as the io command decodes them.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::registers;
+
+fn decoded(address: u16, value: u8) -> Vec<(&'static str, &'static str, String)> {
+    registers::fields(address, value).into_iter().map(|field| (field.bits, field.name, field.value)).collect()
+}
+
+fn values(address: u16, value: u8) -> Vec<String> {
+    decoded(address, value).into_iter().map(|(_, name, value)| format!("{} {}", name, value)).collect()
+}
+
+#[test]
+fn lcd() {
+    assert_eq!(values(0xFF40, 0x91), vec![
+        "lcd on", "window map 9800-9BFF", "window off", "tiles 8000-8FFF",
+        "bg map 9800-9BFF", "object size 8x8", "objects off", "bg on",
+    ]);
+    assert_eq!(values(0xFF40, 0x6C)[1..5], ["window map 9C00-9FFF", "window on", "tiles 8800-97FF", "bg map 9C00-9FFF"]);
+    assert_eq!(values(0xFF41, 0x45), vec![
+        "lyc interrupt on", "oam interrupt off", "vblank interrupt off", "hblank interrupt off",
+        "ly == lyc yes", "mode 1 vblank",
+    ]);
+    assert_eq!(values(0xFF47, 0xE4), vec!["color 0 white", "color 1 light gray", "color 2 dark gray", "color 3 black"]);
+    assert_eq!(values(0xFF46, 0xC1), vec!["source C100"]);
+    assert!(decoded(0xFF44, 0x90).is_empty(), "LY is just a number");
+}
+
+#[test]
+fn bits() {
+    let tac = decoded(0xFF07, 0x05);
+    assert_eq!(tac[0], ("2", "timer", "on".to_string()));
+    assert_eq!(tac[1], ("1-0", "clock", "262144 Hz".to_string()));
+    assert_eq!(values(0xFFFF, 0x05), vec!["vblank on", "stat off", "timer on", "serial off", "joypad off"]);
+    assert_eq!(values(0xFF00, 0x2E)[..3], ["buttons -", "d-pad selected", "down/start -"]);
+    assert_eq!(values(0xFF00, 0x2E)[5], "right/a pressed");
+    assert_eq!(values(0xFF25, 0xF3), vec!["left 1 2 3 4", "right 1 2"]);
+    assert_eq!(values(0xFF26, 0x80), vec!["sound on", "playing none"]);
+    assert_eq!(values(0xFF10, 0x2B), vec!["sweep pace 2", "sweep down", "sweep step 3"]);
+    assert_eq!(values(0xFF1C, 0x40), vec!["output 50%"]);
+}
+
+#[test]
+fn command() {
+    let machine = common::machine(&vec![0; 0x8000]);
+    let debugger = Debug::new();
+
+    let all = debugger.io(&[], &machine);
+    let lcdc: Vec<_> = all.iter().filter(|line| line.starts_with("LCDC ")).collect();
+    assert_eq!(lcdc.len(), 1);
+    assert!(lcdc[0].starts_with("LCDC  FF40  91  lcd on, "), "{}", lcdc[0]);
+
+    let lcdc = debugger.io(&["lcdc"], &machine);
+    assert_eq!(lcdc[0], "LCDC FF40 = 0x91 (145)");
+    assert_eq!(lcdc[1], "  7    lcd               on");
+    assert_eq!(lcdc.len(), 9);
+
+    assert_eq!(debugger.io(&["nothing"], &machine), vec!["Usage: io [register name]"]);
+    assert_eq!(debugger.io(&["lcdc", "extra"], &machine), vec!["Usage: io [register name]"]);
+}
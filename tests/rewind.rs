@@ -0,0 +1,100 @@
+// The rewind buffer on common::counter_rom, no test roms needed.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::rewind::Rewind;
+use gb_rs::state;
+
+#[test]
+fn steps_back_through_snapshots() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut rewind = Rewind::new(3, usize::MAX);
+
+    // snapshots on frames 0, 3, 6 and 9
+    let mut snapshots = Vec::new();
+    for frame in 0..10 {
+        if frame % 3 == 0 {
+            snapshots.push(state::save(&machine));
+        }
+        rewind.record(&machine);
+        machine.run_frame();
+    }
+    assert_eq!(rewind.len(), 4);
+
+    for expected in snapshots.iter().rev() {
+        assert!(rewind.rewind(&mut machine).unwrap());
+        assert_eq!(&state::save(&machine), expected);
+    }
+    assert!(rewind.is_empty());
+    assert!(!rewind.rewind(&mut machine).unwrap());
+    assert_eq!(rewind.memory_used(), 0);
+}
+
+#[test]
+fn stays_within_budget() {
+    let mut machine = common::machine(&common::counter_rom());
+    let size = state::save(&machine).len();
+    let budget = size * 2;
+    let mut rewind = Rewind::new(1, budget);
+
+    for _ in 0..100 {
+        rewind.record(&machine);
+        machine.run_frame();
+        assert!(rewind.memory_used() <= budget);
+    }
+    // deltas between frames are far smaller than whole states
+    assert!(rewind.len() > 10);
+
+    let mut last = machine.cycles;
+    while rewind.rewind(&mut machine).unwrap() {
+        assert!(machine.cycles < last);
+        last = machine.cycles;
+    }
+}
+
+#[test]
+fn snapshots_again_an_interval_after_rewinding() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut rewind = Rewind::new(3, usize::MAX);
+    for _ in 0..10 {
+        rewind.record(&machine);
+        machine.run_frame();
+    }
+    // back to frame 9, the frontend records after each frame it runs
+    assert!(rewind.rewind(&mut machine).unwrap());
+    let mut frame_12 = Vec::new();
+    for _ in 0..3 {
+        machine.run_frame();
+        frame_12 = state::save(&machine);
+        rewind.record(&machine);
+    }
+    assert_eq!(rewind.len(), 4);
+    assert!(rewind.rewind(&mut machine).unwrap());
+    assert_eq!(state::save(&machine), frame_12);
+}
+
+#[test]
+fn deltas_are_compressed() {
+    let mut machine = common::machine(&common::counter_rom());
+    let size = state::save(&machine).len();
+    let mut rewind = Rewind::new(1, usize::MAX);
+    rewind.record(&machine);
+
+    // 4KB of work ram filled with the same byte
+    let mut snapshots = vec![state::save(&machine)];
+    for value in 1..4 {
+        for address in 0xC000..0xD000 {
+            machine.cpu.memory.poke(address, value);
+        }
+        snapshots.push(state::save(&machine));
+        rewind.record(&machine);
+    }
+    assert!(rewind.memory_used() < size + 3 * 200, "{} bytes", rewind.memory_used());
+
+    for expected in snapshots.iter().rev() {
+        assert!(rewind.rewind(&mut machine).unwrap());
+        assert_eq!(&state::save(&machine), expected);
+    }
+}
@@ -0,0 +1,92 @@
+// Visual test roms are checked by running them for a number of frames and
+// comparing the screen against a reference png. On a mismatch the actual
+// screen and a diff with the wrong pixels in red are written to
+// target/tmp/screenshots for review.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gb_rs::screenshot;
+
+use common::Verdict;
+
+// how close a screen has to be to its reference. level is how far apart two
+// grey levels can be and still count as the same, pixels is how many pixels
+// can still differ after that
+struct Tolerance {
+    level: u8,
+    pixels: usize,
+}
+
+const EXACT: Tolerance = Tolerance { level: 0, pixels: 0 };
+
+struct Screenshot {
+    rom: &'static str,
+    reference: &'static str,
+    frames: u32,
+    tolerance: Tolerance,
+}
+
+const DMG_ACID2: Screenshot = Screenshot {
+    rom: "dmg-acid2.gb",
+    reference: "dmg-acid2-dmg.png",
+    frames: 60,
+    tolerance: EXACT,
+};
+
+fn output_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("screenshots");
+    fs::create_dir_all(&dir).expect("Could not create screenshot directory");
+    dir
+}
+
+fn check(dir: &Path, test: &Screenshot) -> Option<Verdict> {
+    let mut machine = common::load(&dir.join(test.rom))?;
+    let expected = match screenshot::load_png(dir.join(test.reference)) {
+        Ok(expected) => expected,
+        Err(e) => return Some(Verdict::Failed(format!("could not read {}, {}", test.reference, e))),
+    };
+
+    Some(common::guard(move || {
+        for _ in 0..test.frames {
+            machine.run_frame();
+        }
+
+        let actual = screenshot::grey_levels(machine.framebuffer());
+        let differences = screenshot::count_differences(&actual, &expected, test.tolerance.level);
+        if differences <= test.tolerance.pixels {
+            return Verdict::Passed;
+        }
+
+        let name = test.rom.trim_end_matches(".gb");
+        let diff = output_dir().join(format!("{}-diff.png", name));
+        screenshot::save_png(output_dir().join(format!("{}-actual.png", name)), machine.framebuffer())
+            .expect("Could not write screenshot");
+        screenshot::save_diff_png(&diff, &actual, &expected, test.tolerance.level)
+            .expect("Could not write diff");
+        Verdict::Failed(format!("{} pixels differ, see {}", differences, diff.display()))
+    }))
+}
+
+fn run_suite(suite: &str, tests: &[Screenshot]) {
+    let dir = match common::suite_dir(suite) {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let results: Vec<_> = tests.iter()
+        .filter_map(|test| check(&dir, test).map(|verdict| (test.rom.to_string(), verdict)))
+        .collect();
+    if !results.is_empty() {
+        common::report(suite, &results);
+    }
+}
+
+#[test]
+fn dmg_acid2() {
+    run_suite("dmg-acid2", &[DMG_ACID2]);
+}
@@ -0,0 +1,112 @@
+// Debugger scripts: the .gbrsinit file next to the rom, source, and an empty
+// line repeating the last command.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use gb_rs::debug::debug::{Actions, Debug, INIT_FILE};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("gb-rs-script-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn is_continue(action: Actions) -> bool {
+    matches!(action, Actions::BREAK)
+}
+
+#[test]
+fn init_file() {
+    let dir = scratch_dir("init");
+    let rom = dir.join("game.gb");
+    fs::write(&rom, common::loop_rom()).unwrap();
+    fs::write(dir.join(INIT_FILE), "# shared setup\nb 150\nw c000\n\ns\nc\nb 152\n").unwrap();
+
+    let mut machine = common::machine(&common::loop_rom());
+    let mut debugger = Debug::new();
+    debugger.set_rom(&rom);
+    assert!(debugger.breakpoints.is_empty(), "nothing runs until asked");
+    assert!(is_continue(debugger.run_script(&mut machine)), "c hands the machine back");
+    assert_eq!(debugger.breakpoints.len(), 1);
+    assert_eq!(machine.cpu.memory.watchpoints.len(), 1);
+    assert_eq!(machine.cpu.pc, 0x101, "s ran");
+
+    // the rest runs the next time the debugger stops
+    assert!(matches!(debugger.run_script(&mut machine), Actions::NOOP));
+    assert_eq!(debugger.breakpoints.iter().map(|b| b.location).collect::<Vec<_>>(), vec![0x150, 0x152]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source() {
+    let dir = scratch_dir("source");
+    let inner = dir.join("inner.gdb");
+    let outer = dir.join("outer.gdb");
+    fs::write(&inner, "s\ns\n").unwrap();
+    fs::write(&outer, format!("b 153\nsource {}\ns\n", inner.display())).unwrap();
+
+    let mut machine = common::machine(&common::loop_rom());
+    let mut debugger = Debug::new();
+    let action = debugger.parse_input(&format!("source {}", outer.display()), &mut machine);
+    assert!(matches!(action, Actions::NOOP));
+    assert_eq!(machine.cpu.pc, 0x151, "three steps from 0x100");
+    assert_eq!(debugger.breakpoints.len(), 1);
+
+    assert!(matches!(debugger.parse_input("source missing.gdb", &mut machine), Actions::NOOP));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_itself() {
+    let dir = scratch_dir("itself");
+    let script = dir.join("again.gdb");
+    fs::write(&script, format!("s\nsource {}\n", script.display())).unwrap();
+
+    let mut machine = common::machine(&common::loop_rom());
+    let mut debugger = Debug::new();
+    let command = format!("source {}", script.display());
+    assert!(matches!(debugger.parse_input(&command, &mut machine), Actions::NOOP));
+    let cycles = machine.cycles;
+    assert!(cycles > 0);
+    // the depth is back to nothing once it stops
+    assert!(matches!(debugger.parse_input(&command, &mut machine), Actions::NOOP));
+    assert!(machine.cycles > cycles);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn script_steps_check_watchpoints() {
+    let dir = scratch_dir("watch");
+    let script = dir.join("steps.gdb");
+    // jp, inc b, ld a, b and then the write to 0xC000
+    fs::write(&script, "w c000\ns\ns\ns\ns\ns\n").unwrap();
+
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    debugger.parse_input(&format!("source {}", script.display()), &mut machine);
+    assert_eq!(machine.cpu.pc, 0x155);
+    assert!(machine.cpu.memory.take_watch_hit().is_none(), "the step reported it");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn empty_line_repeats() {
+    let mut machine = common::machine(&common::loop_rom());
+    let mut debugger = Debug::new();
+    assert!(matches!(debugger.parse_typed("", &mut machine), Actions::NOOP), "nothing to repeat yet");
+    assert!(matches!(debugger.parse_typed("b 150", &mut machine), Actions::NOOP));
+    assert!(matches!(debugger.parse_typed("  ", &mut machine), Actions::NOOP));
+    assert_eq!(debugger.breakpoints.len(), 2);
+
+    assert!(matches!(debugger.parse_typed("s", &mut machine), Actions::STEP));
+    assert!(matches!(debugger.parse_typed("", &mut machine), Actions::STEP));
+    // script lines do not count as typed
+    assert!(matches!(debugger.parse_input("c", &mut machine), Actions::BREAK));
+    assert!(matches!(debugger.parse_typed("", &mut machine), Actions::STEP));
+}
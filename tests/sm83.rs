@@ -0,0 +1,234 @@
+// Runs the cpu one instruction at a time against the SM83 single step test
+// vectors (https://github.com/SingleStepTests/sm83), found under sm83/v1 in
+// the test rom directory. Every vector sets up registers and a few bytes of
+// ram on a flat bus, runs one instruction and checks the registers, ram, the
+// m-cycles it took and what the cpu did on the bus in each of them.
+//
+// Each json file covers one opcode. Like the mooneye suites this fails only
+// on regressions, opcodes listed in sm83_passing.txt that stop passing. A
+// couple of vectors are kept inline and always run.
+
+extern crate gb_rs;
+extern crate serde_json;
+
+mod common;
+
+use std::fs::{self, File};
+use std::path::Path;
+
+use serde_json::Value;
+
+use gb_rs::cpu::bus::Bus;
+use gb_rs::cpu::cpu::CPU;
+
+use common::Verdict;
+
+const PASSING: &str = include_str!("sm83_passing.txt");
+
+// a few vectors in the same format, so the harness checks something when the
+// suite is not there: nop, ld c, a, and inc b clearing n and keeping c
+const INLINE: &str = r#"[
+    {
+        "name": "00 inline",
+        "initial": {"pc": 256, "sp": 65534, "a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": 176, "h": 6, "l": 7,
+                    "ime": 0, "ie": 0, "ram": [[256, 0]]},
+        "final": {"pc": 257, "sp": 65534, "a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": 176, "h": 6, "l": 7,
+                  "ime": 0, "ram": [[256, 0]]},
+        "cycles": [[256, 0, "r-m"]]
+    },
+    {
+        "name": "4f inline",
+        "initial": {"pc": 256, "sp": 65534, "a": 9, "b": 0, "c": 3, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                    "ime": 0, "ie": 0, "ram": [[256, 79]]},
+        "final": {"pc": 257, "sp": 65534, "a": 9, "b": 0, "c": 9, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                  "ime": 0, "ram": [[256, 79]]},
+        "cycles": [[256, 79, "r-m"]]
+    },
+    {
+        "name": "04 inline",
+        "initial": {"pc": 49152, "sp": 65534, "a": 0, "b": 16, "c": 0, "d": 0, "e": 0, "f": 80, "h": 0, "l": 0,
+                    "ime": 0, "ie": 0, "ram": [[49152, 4]]},
+        "final": {"pc": 49153, "sp": 65534, "a": 0, "b": 17, "c": 0, "d": 0, "e": 0, "f": 16, "h": 0, "l": 0,
+                  "ime": 0, "ram": [[49152, 4]]},
+        "cycles": [[49152, 4, "r-m"]]
+    }
+]"#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+// 64KB of plain ram that remembers every access, grouped by m-cycle
+struct FlatBus {
+    memory: Vec<u8>,
+    cycles: Vec<Vec<Access>>,
+    current: Vec<Access>,
+}
+
+impl FlatBus {
+    fn new() -> FlatBus {
+        FlatBus {
+            memory: vec![0; 0x10000],
+            cycles: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.memory[address as usize];
+        self.current.push(Access::Read(address, value));
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+        self.current.push(Access::Write(address, value));
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            self.cycles.push(self.current.split_off(0));
+        }
+    }
+}
+
+fn field(state: &Value, name: &str) -> u16 {
+    state[name].as_u64().unwrap_or_else(|| panic!("missing {}", name)) as u16
+}
+
+fn set_up(state: &Value) -> CPU<FlatBus> {
+    let mut cpu = CPU::with_bus(FlatBus::new());
+    cpu.pc = field(state, "pc");
+    cpu.sp = field(state, "sp");
+    cpu.a = field(state, "a") as u8;
+    cpu.b = field(state, "b") as u8;
+    cpu.c = field(state, "c") as u8;
+    cpu.d = field(state, "d") as u8;
+    cpu.e = field(state, "e") as u8;
+    cpu.set_flags(field(state, "f") as u8);
+    cpu.h = field(state, "h") as u8;
+    cpu.l = field(state, "l") as u8;
+    cpu.ime = field(state, "ime") as u8;
+    cpu.memory.memory[0xFFFF] = field(state, "ie") as u8;
+    for entry in state["ram"].as_array().expect("missing ram") {
+        cpu.memory.memory[entry[0].as_u64().unwrap() as usize] = entry[1].as_u64().unwrap() as u8;
+    }
+    cpu
+}
+
+// the first difference between the cpu and the expected final state
+fn compare(cpu: &CPU<FlatBus>, state: &Value) -> Option<String> {
+    let registers = [
+        ("pc", cpu.pc),
+        ("sp", cpu.sp),
+        ("a", cpu.a as u16),
+        ("b", cpu.b as u16),
+        ("c", cpu.c as u16),
+        ("d", cpu.d as u16),
+        ("e", cpu.e as u16),
+        ("f", cpu.flags() as u16),
+        ("h", cpu.h as u16),
+        ("l", cpu.l as u16),
+        ("ime", cpu.ime as u16),
+    ];
+    for &(name, actual) in registers.iter() {
+        let expected = field(state, name);
+        if actual != expected {
+            return Some(format!("{} is {:#X}, expected {:#X}", name, actual, expected));
+        }
+    }
+
+    for entry in state["ram"].as_array().expect("missing ram") {
+        let address = entry[0].as_u64().unwrap() as usize;
+        let expected = entry[1].as_u64().unwrap() as u8;
+        if cpu.memory.memory[address] != expected {
+            return Some(format!("[{:#06X}] is {:#X}, expected {:#X}", address, cpu.memory.memory[address], expected));
+        }
+    }
+    None
+}
+
+// the vectors list one entry per m-cycle, null or without r/w pins when the
+// bus is idle
+fn expected_cycles(cycles: &Value) -> Vec<Vec<Access>> {
+    cycles.as_array().expect("missing cycles").iter()
+        .map(|cycle| {
+            let address = cycle[0].as_u64();
+            let value = cycle[1].as_u64();
+            let pins = cycle[2].as_str().unwrap_or("");
+            match (address, value) {
+                (Some(address), Some(value)) if pins.contains('r') => vec![Access::Read(address as u16, value as u8)],
+                (Some(address), Some(value)) if pins.contains('w') => vec![Access::Write(address as u16, value as u8)],
+                _ => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+fn run_vector(test: &Value) -> Option<String> {
+    let mut cpu = set_up(&test["initial"]);
+    let cycles = cpu.cycle() as usize;
+
+    if let Some(difference) = compare(&cpu, &test["final"]) {
+        return Some(difference);
+    }
+    let expected = expected_cycles(&test["cycles"]);
+    if cycles != expected.len() {
+        return Some(format!("took {} m-cycles, expected {}", cycles, expected.len()));
+    }
+    // only what happened inside a ticked m-cycle counts
+    if !cpu.memory.current.is_empty() {
+        return Some(format!("bus activity {:?} after the last m-cycle", cpu.memory.current));
+    }
+    if cpu.memory.cycles != expected {
+        return Some(format!("bus activity {:?}, expected {:?}", cpu.memory.cycles, expected));
+    }
+    None
+}
+
+fn run_file(path: &Path) -> Verdict {
+    let tests: Value = serde_json::from_reader(File::open(path).expect("Could not open test file"))
+        .expect("Could not parse test file");
+
+    common::guard(move || {
+        for test in tests.as_array().expect("expected a list of tests") {
+            if let Some(difference) = run_vector(test) {
+                return Verdict::Failed(format!("{}: {}", test["name"].as_str().unwrap_or("?"), difference));
+            }
+        }
+        Verdict::Passed
+    })
+}
+
+#[test]
+fn inline_vectors() {
+    let tests: Value = serde_json::from_str(INLINE).expect("Could not parse the inline vectors");
+    for test in tests.as_array().unwrap() {
+        assert_eq!(run_vector(test), None, "{}", test["name"]);
+    }
+}
+
+#[test]
+fn single_step() {
+    let dir = match common::suite_dir("sm83/v1") {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let mut files: Vec<String> = fs::read_dir(&dir).expect("Could not list tests")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    files.sort();
+
+    let results: Vec<_> = files.iter()
+        .map(|file| (file.trim_end_matches(".json").to_string(), run_file(&dir.join(file))))
+        .collect();
+    common::print_table("sm83", &results);
+    common::check_regressions("sm83", &results, PASSING);
+}
@@ -0,0 +1,110 @@
+// Save states round trip without any test roms, on common::counter_rom.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use gb_rs::cpu::watch::{Watch, Watchpoint};
+use gb_rs::debug::debug::Debug;
+use gb_rs::{state, Gameboy};
+
+fn machine() -> Gameboy {
+    let mut machine = common::machine(&common::counter_rom());
+    for _ in 0..2 {
+        machine.run_frame();
+    }
+    machine
+}
+
+#[test]
+fn round_trip() {
+    let mut machine = machine();
+    let saved = state::save(&machine);
+    let (pc, a, cycles) = (machine.cpu.pc, machine.cpu.a, machine.cycles);
+
+    machine.run_frame();
+    assert_ne!(machine.cycles, cycles);
+
+    state::load(&mut machine, &saved).unwrap();
+    assert_eq!((machine.cpu.pc, machine.cpu.a, machine.cycles), (pc, a, cycles));
+    assert_eq!(state::save(&machine), saved);
+}
+
+#[test]
+fn rejects_bad_states() {
+    let mut machine = machine();
+    let saved = state::save(&machine);
+    let cycles = machine.cycles;
+
+    let mut magic = saved.clone();
+    magic[0] = b'X';
+    let mut version = saved.clone();
+    version[4] = version[4].wrapping_add(1);
+    let mut trailing = saved.clone();
+    trailing.push(0);
+
+    for data in [&magic[..], &version[..], &saved[..saved.len() - 1], &trailing[..]].iter() {
+        machine.run_frame();
+        let before = state::save(&machine);
+        assert!(state::load(&mut machine, data).is_err());
+        assert_eq!(state::save(&machine), before);
+    }
+    assert_ne!(machine.cycles, cycles);
+}
+
+#[test]
+fn rejects_other_roms() {
+    let saved = state::save(&machine());
+    let mut rom = common::counter_rom();
+    rom[0x7FFF] ^= 0xFF;
+    let mut other = common::machine(&rom);
+    let before = state::save(&other);
+    let error = state::load(&mut other, &saved).err().unwrap();
+    assert!(error.to_string().contains("crc32"), "{}", error);
+    assert_eq!(state::save(&other), before);
+}
+
+#[test]
+fn leaves_the_rom_alone() {
+    let mut machine = machine();
+    let saved = state::save(&machine);
+    machine.cpu.memory.poke(0x7FFF, 0x42);
+    state::load(&mut machine, &saved).unwrap();
+    assert_eq!(machine.cpu.memory.peek(0x7FFF), 0x42);
+}
+
+#[test]
+fn keeps_what_is_not_saved() {
+    let mut machine = machine();
+    let saved = state::save(&machine);
+    machine.cpu.memory.serial.linked = true;
+    machine.cpu.memory.watchpoints.push(Watchpoint::new(0xC000, 0xC000, Watch::Write, None));
+
+    state::load(&mut machine, &saved).unwrap();
+    assert!(machine.cpu.memory.serial.linked);
+    assert_eq!(machine.cpu.memory.watchpoints.len(), 1);
+}
+
+#[test]
+fn debugger_slots() {
+    let mut machine = machine();
+    let mut debugger = Debug::new();
+    // no rom to put slot 3 next to, and no file called 3 instead
+    debugger.parse_input("save 3", &mut machine);
+    debugger.parse_input("save 11", &mut machine);
+    assert!(!Path::new("3").exists() && !Path::new("11").exists());
+
+    let rom = env::temp_dir().join(format!("gb-rs-slots-{}.gb", std::process::id()));
+    debugger.rom_path = Some(rom.clone());
+    let pc = machine.cpu.pc;
+    debugger.parse_input("save 3", &mut machine);
+    machine.run_frame();
+    assert_ne!(machine.cpu.pc, pc);
+    debugger.parse_input("load 3", &mut machine);
+    assert_eq!(machine.cpu.pc, pc);
+    fs::remove_file(state::slot_path(&rom, 3)).unwrap();
+}
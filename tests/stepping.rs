@@ -0,0 +1,83 @@
+// next, finish, until and step N on common::calls_rom.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::debug::debug::{Actions, Debug};
+use gb_rs::Gameboy;
+
+// what the frontend does with an action, returning once the debugger stops
+fn act(debugger: &mut Debug, machine: &mut Gameboy, command: &str) {
+    match debugger.parse_input(command, machine) {
+        Actions::STEP => debugger.step(machine),
+        Actions::BREAK => {
+            for _ in 0..10000 {
+                if debugger.check_breakpoints(machine) {
+                    return;
+                }
+                debugger.step(machine);
+            }
+            panic!("{} never stopped", command);
+        },
+        _ => (),
+    }
+}
+
+#[test]
+fn step_count() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    act(&mut debugger, &mut machine, "s 3");
+    assert_eq!(machine.cpu.pc, 0x170);
+    act(&mut debugger, &mut machine, "step");
+    assert_eq!(machine.cpu.pc, 0x172);
+    assert_eq!(debugger.stack.len(), 1);
+}
+
+#[test]
+fn next_steps_over_calls() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    act(&mut debugger, &mut machine, "until 150");
+    assert_eq!(machine.cpu.pc, 0x150);
+    act(&mut debugger, &mut machine, "n");
+    assert_eq!(machine.cpu.pc, 0x153);
+    assert_eq!(machine.cpu.b, 1);
+    assert!(debugger.stack.is_empty());
+    // anything but a call is a single step
+    act(&mut debugger, &mut machine, "next");
+    assert_eq!(machine.cpu.pc, 0x154);
+}
+
+#[test]
+fn finish_returns_a_frame_at_a_time() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    act(&mut debugger, &mut machine, "until 180");
+    assert_eq!(debugger.stack.len(), 2);
+    assert_eq!(debugger.stack.current().unwrap().function, 0x180);
+    act(&mut debugger, &mut machine, "finish");
+    assert_eq!(machine.cpu.pc, 0x175);
+    assert_eq!(debugger.stack.len(), 1);
+    act(&mut debugger, &mut machine, "finish");
+    assert_eq!(machine.cpu.pc, 0x153);
+    assert!(debugger.stack.is_empty());
+    act(&mut debugger, &mut machine, "finish");
+    assert_eq!(machine.cpu.pc, 0x153);
+}
+
+#[test]
+fn breakpoints_cancel_next() {
+    let mut machine = common::machine(&common::calls_rom());
+    let mut debugger = Debug::new();
+    act(&mut debugger, &mut machine, "until 150");
+    act(&mut debugger, &mut machine, "b 180");
+    act(&mut debugger, &mut machine, "n");
+    assert_eq!(machine.cpu.pc, 0x180);
+    // the next stop is the second call reaching the breakpoint, not the
+    // return address next was after
+    act(&mut debugger, &mut machine, "c");
+    assert_eq!(machine.cpu.pc, 0x180);
+    assert_eq!(machine.cpu.b, 2);
+}
@@ -0,0 +1,73 @@
+// Symbol files and bank:address locations in the debugger, on
+// common::counter_rom whose loop is at 0x150 in bank 0.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::debug::debug::Debug;
+use gb_rs::debug::symbols::Symbols;
+use gb_rs::Gameboy;
+
+const SYM: &str = "; File generated by rgblink
+[labels]
+00:0100 EntryPoint
+00:0150 Main
+00:0152 Main.store
+01:4000 FarCode
+00:C000 wCounter ; comments after a label
+not a symbol
+";
+
+// true if the debugger stops the machine within a few thousand instructions
+fn stops(debugger: &mut Debug, machine: &mut Gameboy) -> bool {
+    for _ in 0..5000 {
+        if debugger.check_breakpoints(machine) {
+            return true;
+        }
+        machine.step();
+    }
+    false
+}
+
+#[test]
+fn parses_sym_files() {
+    let symbols = Symbols::parse(SYM);
+    assert_eq!(symbols.len(), 5);
+
+    let main = symbols.lookup("Main.store").unwrap();
+    assert_eq!((main.bank, main.address), (0, 0x152));
+    assert_eq!(symbols.at(1, 0x4000).unwrap().name, "FarCode");
+    assert!(symbols.at(0, 0x4000).is_none());
+
+    let (symbol, offset) = symbols.nearest(0, 0x151).unwrap();
+    assert_eq!((symbol.name.as_str(), offset), ("Main", 1));
+    // labels in another region are not near
+    assert!(symbols.nearest(0, 0x8000).is_none());
+    assert_eq!(symbols.nearest(0, 0xC003).unwrap().0.name, "wCounter");
+}
+
+#[test]
+fn breakpoints_on_labels() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+    debugger.symbols = Symbols::parse(SYM);
+
+    debugger.parse_input("b Main.store", &mut machine);
+    assert!(stops(&mut debugger, &mut machine));
+    assert_eq!(machine.cpu.pc, 0x152);
+    assert_eq!(debugger.describe(&machine.cpu.memory, 0x153), "0x0153 (Main.store+0x1)");
+}
+
+#[test]
+fn breakpoints_in_banks() {
+    let mut machine = common::machine(&common::counter_rom());
+    let mut debugger = Debug::new();
+
+    debugger.parse_input("b 01:0150", &mut machine);
+    assert!(!stops(&mut debugger, &mut machine));
+
+    debugger.parse_input("b 00:0151", &mut machine);
+    assert!(stops(&mut debugger, &mut machine));
+    assert_eq!(machine.cpu.pc, 0x151);
+}
@@ -0,0 +1,153 @@
+// The TCP link cable over 127.0.0.1, against another link and against a
+// scripted peer that answers late.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gb_rs::cpu::tcp_link::{TcpLink, TcpLinkConfig};
+
+fn start_transfer(link: &mut TcpLink, data: u8, control: u8) {
+    let memory = &mut link.gameboy.cpu.memory;
+    memory.load_value_u8(0xFF01, data);
+    memory.load_value_u8(0xFF02, control);
+}
+
+fn transferring(link: &TcpLink) -> bool {
+    link.gameboy.cpu.memory.peek(0xFF02) & 0x80 != 0
+}
+
+// run until the transfer is over, or give up after a few seconds
+fn finish_transfer(link: &mut TcpLink) -> u8 {
+    let give_up = Instant::now() + Duration::from_secs(5);
+    while transferring(link) {
+        assert!(Instant::now() < give_up, "the transfer never finished");
+        link.step();
+    }
+    link.gameboy.cpu.memory.peek(0xFF01)
+}
+
+fn config(millis: u64) -> TcpLinkConfig {
+    let mut config = TcpLinkConfig::new();
+    config.timeout = Duration::from_millis(millis);
+    config
+}
+
+fn read_frame(stream: &mut TcpStream) -> [u8; 3] {
+    let mut frame = [0; 3];
+    stream.read_exact(&mut frame).unwrap();
+    frame
+}
+
+#[test]
+fn transfer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let slave = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut link = TcpLink::from_stream(common::machine(&common::idle_rom()), stream, config(2000)).unwrap();
+        start_transfer(&mut link, 0x99, 0x80);
+        finish_transfer(&mut link)
+    });
+
+    let mut master = TcpLink::connect(common::machine(&common::idle_rom()), address, config(2000)).unwrap();
+    start_transfer(&mut master, 0x42, 0x81);
+    assert_eq!(finish_transfer(&mut master), 0x99);
+    assert_eq!(slave.join().unwrap(), 0x42);
+    assert!(master.gameboy.cpu.memory.peek(0xFF0F) & 0b00001000 != 0, "serial interrupt");
+    assert!(master.connected());
+}
+
+#[test]
+fn timeout_and_late_reply() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (timed_out, wait) = mpsc::channel();
+    let peer = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_eq!(read_frame(&mut stream), [0x00, 0, 2]);
+        stream.write_all(&[0x00, 0, 2]).unwrap();
+
+        // answer the first transfer only once the master has given up on it
+        let first = read_frame(&mut stream);
+        wait.recv().unwrap();
+        stream.write_all(&[0x02, first[1], 0x11]).unwrap();
+        let second = read_frame(&mut stream);
+        stream.write_all(&[0x02, second[1], 0x22]).unwrap();
+        (first, second)
+    });
+
+    let mut master = TcpLink::connect(common::machine(&common::idle_rom()), address, config(100)).unwrap();
+    start_transfer(&mut master, 0x42, 0x81);
+    assert_eq!(finish_transfer(&mut master), 0xFF, "no reply in time reads as nothing plugged in");
+    assert!(master.connected(), "a slow peer is not dropped");
+    timed_out.send(()).unwrap();
+    // let the late reply arrive before the next transfer goes out
+    thread::sleep(Duration::from_millis(50));
+
+    start_transfer(&mut master, 0x43, 0x81);
+    assert_eq!(finish_transfer(&mut master), 0x22, "the late reply is not taken for this one");
+    let (first, second) = peer.join().unwrap();
+    assert_eq!((first[0], first[2]), (0x01, 0x42));
+    assert_eq!((second[0], second[2]), (0x01, 0x43));
+    assert_ne!(first[1], second[1]);
+    assert!(master.take_error().is_none());
+}
+
+#[test]
+fn hung_up_peer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let peer = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_frame(&mut stream);
+        stream.write_all(&[0x00, 0, 2]).unwrap();
+    });
+
+    let mut master = TcpLink::connect(common::machine(&common::idle_rom()), address, config(1000)).unwrap();
+    peer.join().unwrap();
+    start_transfer(&mut master, 0x42, 0x81);
+    assert_eq!(finish_transfer(&mut master), 0xFF);
+    assert!(!master.connected());
+    assert!(master.take_error().is_some());
+    assert!(master.take_error().is_none(), "handed over once");
+}
+
+#[test]
+fn listen_times_out() {
+    let started = Instant::now();
+    let error = TcpLink::listen(common::machine(&common::idle_rom()), "127.0.0.1:0", config(100)).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn listen_and_connect() {
+    // borrow a free port for the listening link
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let slave = thread::spawn(move || {
+        let mut link = TcpLink::listen(common::machine(&common::idle_rom()), address, config(2000)).unwrap();
+        start_transfer(&mut link, 0x99, 0x80);
+        finish_transfer(&mut link)
+    });
+
+    let give_up = Instant::now() + Duration::from_secs(2);
+    let mut master = loop {
+        match TcpLink::connect(common::machine(&common::idle_rom()), address, config(2000)) {
+            Ok(link) => break link,
+            Err(e) => {
+                assert!(Instant::now() < give_up, "could not connect: {}", e);
+                thread::sleep(Duration::from_millis(10));
+            },
+        }
+    };
+    start_transfer(&mut master, 0x42, 0x81);
+    assert_eq!(finish_transfer(&mut master), 0x99);
+    assert_eq!(slave.join().unwrap(), 0x42);
+}
@@ -0,0 +1,126 @@
+// Execution traces in the gameboy-doctor format, with their filters and size
+// limit.
+
+extern crate gb_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use gb_rs::trace::{self, Trace};
+use gb_rs::{state, Gameboy};
+
+fn trace_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("gb-rs-trace-{}-{}.log", name, std::process::id()))
+}
+
+// run a machine for some instructions with a trace set up by setup, and
+// return the lines it wrote
+fn traced<F: Fn(&mut Trace)>(name: &str, steps: usize, setup: F) -> Vec<String> {
+    let path = trace_path(name);
+    let mut machine = common::machine(&common::loop_rom());
+    let mut trace = Trace::create(&path).unwrap();
+    setup(&mut trace);
+    machine.trace = Some(trace);
+    for _ in 0..steps {
+        machine.step();
+    }
+    machine.trace.take().unwrap().flush().unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    text.lines().map(String::from).collect()
+}
+
+#[test]
+fn format() {
+    let machine = common::machine(&common::loop_rom());
+    // the first line of every gameboy-doctor log
+    assert_eq!(trace::line(&machine.cpu),
+               "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01");
+
+    let lines = traced("format", 5, |_| ());
+    assert_eq!(lines.len(), 5);
+    assert!(lines[2].ends_with("PC:0150 PCMEM:04,04,18,FC"), "{}", lines[2]);
+    assert!(lines[3].starts_with("A:01 F:B0 B:01 "), "{}", lines[3]);
+}
+
+#[test]
+fn filters() {
+    let lines = traced("range", 20, |trace| trace.range = Some((0x151, 0x152)));
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|line| line.contains("PC:0151") || line.contains("PC:0152")));
+
+    let lines = traced("bank", 20, |trace| trace.bank = Some(1));
+    assert!(lines.is_empty(), "nothing runs in bank 1");
+    let lines = traced("bank0", 20, |trace| trace.bank = Some(0));
+    assert_eq!(lines.len(), 20);
+}
+
+#[test]
+fn limit() {
+    let line_length = trace::line(&Gameboy::from_bytes(&common::loop_rom()).cpu).len() as u64 + 1;
+    let path = trace_path("limit");
+    let mut machine = Gameboy::from_bytes(&common::loop_rom());
+    let mut trace = Trace::create(&path).unwrap();
+    trace.limit = Some(line_length * 3 + 10);
+    machine.trace = Some(trace);
+    for _ in 0..20 {
+        machine.step();
+    }
+    let mut trace = machine.trace.take().unwrap();
+    trace.flush().unwrap();
+    assert!(trace.full);
+    assert_eq!(trace.lines, 3);
+    assert_eq!(fs::metadata(&path).unwrap().len(), line_length * 3);
+    fs::remove_file(&path).unwrap();
+}
+
+// fails every write past 100 bytes, room for one line
+struct FullDisk {
+    written: usize,
+}
+
+impl Write for FullDisk {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.written + data.len() > 100 {
+            return Err(io::Error::other("disk full"));
+        }
+        self.written += data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_errors_are_kept() {
+    let mut machine = common::machine(&common::loop_rom());
+    machine.trace = Some(Trace::new(FullDisk { written: 0 }));
+    for _ in 0..5 {
+        machine.step();
+    }
+    let trace = machine.trace.as_mut().expect("the trace is kept for the frontend");
+    assert_eq!(trace.lines, 1);
+    assert_eq!(trace.error.take().unwrap().to_string(), "disk full");
+}
+
+#[test]
+fn survives_loading_a_state() {
+    let mut machine = common::machine(&common::loop_rom());
+    machine.trace = Some(Trace::new(Vec::new()));
+    for _ in 0..3 {
+        machine.step();
+    }
+    let saved = state::save(&machine);
+    machine.step();
+    state::load(&mut machine, &saved).unwrap();
+    for _ in 0..3 {
+        machine.step();
+    }
+    assert_eq!(machine.trace.as_ref().expect("still tracing").lines, 7);
+}
@@ -0,0 +1,82 @@
+// The vram viewer's picture: tiles, both maps and the screen and window
+// outlined on them.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::cpu::gpu::shade_rgb;
+use gb_rs::debug::vram::{self, render, BACKGROUND, HEIGHT, MAPS_X, VIEWPORT, WIDTH, WINDOW};
+use gb_rs::Gameboy;
+
+fn machine() -> Gameboy {
+    let mut machine = common::machine(&vec![0; 0x8000]);
+    // tile 1 has a black top row and the right half of its fourth row dark
+    // grey, through a palette that keeps colours as they are
+    let memory = &mut machine.cpu.memory;
+    memory.poke(0xFF47, 0b11100100);
+    memory.poke(0x8010, 0xFF);
+    memory.poke(0x8011, 0xFF);
+    memory.poke(0x8016, 0x00);
+    memory.poke(0x8017, 0x0F);
+    machine
+}
+
+fn pixel(pixels: &[u8], x: usize, y: usize) -> [u8; 3] {
+    let i = (y * WIDTH + x) * 3;
+    [pixels[i], pixels[i + 1], pixels[i + 2]]
+}
+
+#[test]
+fn tiles() {
+    let pixels = render(&machine().cpu.memory);
+    assert_eq!(pixels.len(), WIDTH * HEIGHT * 3);
+    assert_eq!(pixel(&pixels, 0, 0), shade_rgb(0), "tile 0 is blank");
+    assert_eq!(pixel(&pixels, 8, 0), shade_rgb(3));
+    assert_eq!(pixel(&pixels, 12, 3), shade_rgb(2));
+    assert_eq!(pixel(&pixels, 11, 3), shade_rgb(0));
+    assert_eq!(pixel(&pixels, 0, 24 * 8), BACKGROUND, "384 tiles, 16 to a row");
+    assert_eq!(pixel(&pixels, 16 * 8, 0), BACKGROUND, "a gap before the maps");
+}
+
+#[test]
+fn maps_and_viewport() {
+    let mut machine = machine();
+    {
+        let memory = &mut machine.cpu.memory;
+        memory.poke(0x9821, 1);
+        memory.poke(0x9C00 + 32 * 10 + 3, 1);
+        memory.poke(0xFF43, 250);
+        memory.poke(0xFF42, 200);
+    }
+    let pixels = render(&machine.cpu.memory);
+    let (bg, other) = (MAPS_X[0], MAPS_X[1]);
+    assert_eq!(pixel(&pixels, bg + 12, 11), shade_rgb(2), "tile 1 at row 1, column 1");
+    assert_eq!(pixel(&pixels, other + 24, 80), shade_rgb(3));
+    assert_eq!(pixel(&pixels, other + 24, 81), shade_rgb(0));
+
+    // 160x144 from 250,200, wrapping round to 153,87
+    for &(x, y) in [(250, 200), (250, 210), (0, 200), (153, 200), (153, 87), (250, 87), (250, 0), (100, 200)].iter() {
+        assert_eq!(pixel(&pixels, bg + x, y), VIEWPORT, "{},{}", x, y);
+    }
+    assert_ne!(pixel(&pixels, bg + 100, 100), VIEWPORT);
+    assert_ne!(pixel(&pixels, bg + 154, 200), VIEWPORT);
+    assert!(!pixels.chunks(3).any(|rgb| rgb == WINDOW), "the window is off");
+
+    // the window on, using the other map, from 80,100 on the screen
+    {
+        let memory = &mut machine.cpu.memory;
+        let control = memory.peek(0xFF40);
+        memory.poke(0xFF40, control | 0b01100000);
+        memory.poke(0xFF4B, 87);
+        memory.poke(0xFF4A, 100);
+    }
+    let pixels = render(&machine.cpu.memory);
+    for &(x, y) in [(0, 0), (79, 0), (0, 43), (79, 43), (40, 43)].iter() {
+        assert_eq!(pixel(&pixels, other + x, y), WINDOW, "{},{}", x, y);
+    }
+    assert_ne!(pixel(&pixels, other + 80, 0), WINDOW);
+    assert_ne!(pixel(&pixels, other, 44), WINDOW);
+    assert_eq!(pixel(&pixels, other + 24, 80), shade_rgb(3));
+    assert_eq!(vram::WIDTH, other + 256);
+}
@@ -0,0 +1,63 @@
+// Watchpoints checked on the interconnect, on tiny roms so no test roms are
+// needed.
+
+extern crate gb_rs;
+
+mod common;
+
+use gb_rs::cpu::watch::{Watch, Watchpoint, WatchHit};
+use gb_rs::Gameboy;
+
+fn machine(rom: &[u8], watchpoint: Watchpoint) -> Gameboy {
+    let mut machine = common::machine(rom);
+    machine.cpu.memory.watchpoints.push(watchpoint);
+    machine
+}
+
+// steps until a watchpoint is hit, returning it with the pc of the
+// instruction that hit it
+fn run_until_hit(machine: &mut Gameboy) -> (u16, WatchHit) {
+    for _ in 0..100000 {
+        let pc = machine.cpu.pc;
+        machine.step();
+        if let Some(hit) = machine.cpu.memory.take_watch_hit() {
+            return (pc, hit);
+        }
+    }
+    panic!("watchpoint never hit");
+}
+
+#[test]
+fn write_with_value() {
+    // the counter rom stores b to 0xC000 from 0x152
+    let mut machine = machine(&common::counter_rom(), Watchpoint::new(0xC000, 0xC000, Watch::Write, Some(0x05)));
+    let (pc, hit) = run_until_hit(&mut machine);
+    assert_eq!(pc, 0x152);
+    assert_eq!(hit, WatchHit { index: 0, address: 0xC000, value: 0x05, write: true, dma: false });
+    assert_eq!(machine.cpu.b, 0x05);
+}
+
+#[test]
+fn reads_in_range() {
+    let mut machine = machine(&common::counter_rom(), Watchpoint::new(0x0150, 0x0151, Watch::Read, None));
+    let (_, hit) = run_until_hit(&mut machine);
+    assert_eq!((hit.address, hit.write), (0x0150, false));
+}
+
+#[test]
+fn oam_dma() {
+    // 0x100: jp 0x150, 0x150: ld a, 0xC1; ldh (0x46), a; jr -2
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x150..0x156].copy_from_slice(&[0x3E, 0xC1, 0xE0, 0x46, 0x18, 0xFE]);
+
+    let mut machine = machine(&rom, Watchpoint::new(0xFE00, 0xFE9F, Watch::Access, None));
+    machine.cpu.memory.load_value_u8(0xC19F, 0x42);
+    let (_, hit) = run_until_hit(&mut machine);
+    assert_eq!(hit, WatchHit { index: 0, address: 0xFE00, value: 0x00, write: true, dma: true });
+
+    for _ in 0..200 {
+        machine.step();
+    }
+    assert_eq!(machine.cpu.memory.read_value_u8(0xFE9F), 0x42);
+}